@@ -0,0 +1,682 @@
+use std::{
+    env,
+    ffi::OsString,
+    io::{self, Read},
+    path::PathBuf,
+};
+
+use log::warn;
+
+const MESSAGE_KEYBOARD_LAYOUT_TAG: u8 = 0;
+const MESSAGE_WORKSPACE_LIST_TAG: u8 = 1;
+const MESSAGE_WORKSPACE_ACTIVE_TAG: u8 = 2;
+const MESSAGE_REQUEST_CLIENT_INIT_TAG: u8 = 3;
+const MESSAGE_COMMAND_TAG: u8 = 4;
+const MESSAGE_COMMAND_REPLY_TAG: u8 = 5;
+const MESSAGE_REQUEST_TREE_TAG: u8 = 6;
+const MESSAGE_TREE_TAG: u8 = 7;
+const MESSAGE_SUBSCRIBE_TAG: u8 = 8;
+const MESSAGE_FOCUSED_WINDOW_TAG: u8 = 9;
+const MESSAGE_WINDOW_OPENED_TAG: u8 = 10;
+const MESSAGE_WINDOW_CLOSED_TAG: u8 = 11;
+const MESSAGE_LAYOUT_CHANGED_TAG: u8 = 12;
+const MESSAGE_CONFIG_RELOADED_TAG: u8 = 13;
+const MESSAGE_CLIENT_INIT_REPLY_TAG: u8 = 14;
+const MESSAGE_MODE_CHANGED_TAG: u8 = 15;
+const MESSAGE_WORKSPACE_LIST_NAMED_TAG: u8 = 16;
+
+/// Has to match `WM_PROTOCOL_VERSION` in protocol/messages.def
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Every message kind this side understands, has to match `WM_MESSAGE` entries in
+/// protocol/messages.def
+pub const MESSAGE_KINDS: [(&str, u8); 17] = [
+    ("KeyboardLayout", MESSAGE_KEYBOARD_LAYOUT_TAG),
+    ("WorkspaceList", MESSAGE_WORKSPACE_LIST_TAG),
+    ("WorkspaceActive", MESSAGE_WORKSPACE_ACTIVE_TAG),
+    ("RequestClientInit", MESSAGE_REQUEST_CLIENT_INIT_TAG),
+    ("Command", MESSAGE_COMMAND_TAG),
+    ("CommandReply", MESSAGE_COMMAND_REPLY_TAG),
+    ("RequestTree", MESSAGE_REQUEST_TREE_TAG),
+    ("Tree", MESSAGE_TREE_TAG),
+    ("Subscribe", MESSAGE_SUBSCRIBE_TAG),
+    ("FocusedWindow", MESSAGE_FOCUSED_WINDOW_TAG),
+    ("WindowOpened", MESSAGE_WINDOW_OPENED_TAG),
+    ("WindowClosed", MESSAGE_WINDOW_CLOSED_TAG),
+    ("LayoutChanged", MESSAGE_LAYOUT_CHANGED_TAG),
+    ("ConfigReloaded", MESSAGE_CONFIG_RELOADED_TAG),
+    ("ClientInitReply", MESSAGE_CLIENT_INIT_REPLY_TAG),
+    ("ModeChanged", MESSAGE_MODE_CHANGED_TAG),
+    ("WorkspaceListNamed", MESSAGE_WORKSPACE_LIST_NAMED_TAG),
+];
+
+pub fn supported_message_tags() -> Vec<u8> {
+    MESSAGE_KINDS.iter().map(|(_, tag)| *tag).collect()
+}
+
+const FRAME_SIZE_LEN: usize = size_of::<u64>();
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 1 << 20;
+
+/// Set by the window manager for its children, overrides the path derived from the display
+pub const SOCKET_PATH_ENV: &str = "X11_WM_RUST_SOCKET";
+
+/// `$X11_WM_RUST_SOCKET` if set, otherwise `$XDG_RUNTIME_DIR/x11_wm_rust.<display>.socket`
+/// with `/tmp` used when there is no runtime dir
+pub fn socket_path_from_env() -> PathBuf {
+    socket_path(
+        env::var_os(SOCKET_PATH_ENV),
+        env::var_os("XDG_RUNTIME_DIR"),
+        env::var("DISPLAY").ok().as_deref(),
+    )
+}
+
+fn socket_path(
+    overridden: Option<OsString>,
+    runtime_dir: Option<OsString>,
+    display: Option<&str>,
+) -> PathBuf {
+    if let Some(path) = overridden.filter(|path| !path.is_empty()) {
+        return PathBuf::from(path);
+    }
+    let dir = runtime_dir
+        .filter(|dir| !dir.is_empty())
+        .map_or_else(|| PathBuf::from("/tmp"), PathBuf::from);
+    dir.join(format!(
+        "x11_wm_rust.{}.socket",
+        display_number(display.unwrap_or_default())
+    ))
+}
+
+/// "host:1.0" -> "1", each X server gets its own socket regardless of the screen
+fn display_number(display: &str) -> &str {
+    let number = display
+        .rsplit_once(':')
+        .map_or(display, |(_, number)| number);
+    let number = number.split_once('.').map_or(number, |(number, _)| number);
+    if number.is_empty() { "0" } else { number }
+}
+
+/// Named group of events a client can subscribe to
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Topic {
+    Workspace = 0,
+    KeyboardLayout = 1,
+    FocusedWindow = 2,
+    Window = 3,
+    Layout = 4,
+    Config = 5,
+    Mode = 6,
+}
+
+impl Topic {
+    pub const ALL: [Topic; 7] = [
+        Topic::Workspace,
+        Topic::KeyboardLayout,
+        Topic::FocusedWindow,
+        Topic::Window,
+        Topic::Layout,
+        Topic::Config,
+        Topic::Mode,
+    ];
+
+    /// What clients receive until they send `Subscribe`, keeps bars written before topics working
+    pub const DEFAULT: [Topic; 3] = [Topic::Workspace, Topic::KeyboardLayout, Topic::Mode];
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|topic| *topic as u8 == value)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Message<'a> {
+    KeyboardLayout(&'a str),
+    WorkspaceList(Vec<u32>),
+    WorkspaceActive(u32),
+    /// first message of a client, `supported` contains tags of messages the client can parse,
+    /// clients predating the handshake send it without payload which is decoded as version 0
+    RequestClientInit {
+        version: u32,
+        supported: Vec<u8>,
+    },
+    /// action in the same syntax as used by keybindings in the config, e.g. `focus_window left`
+    Command(&'a str),
+    /// result of the last `Command` sent by the client, error contains human readable reason
+    CommandReply(Result<(), &'a str>),
+    RequestTree,
+    /// whole monitor state (workspaces, windows, their rects and focus) serialized as JSON
+    Tree(&'a str),
+    /// replaces the set of topics the client receives events for
+    Subscribe(Vec<Topic>),
+    /// newly focused window and its title
+    FocusedWindow(u32, &'a str),
+    WindowOpened(u32),
+    WindowClosed(u32),
+    /// geometry of windows on the workspace with the given id has changed
+    LayoutChanged(u32),
+    ConfigReloaded,
+    /// tags of messages supported by the window manager or the reason the client was rejected
+    ClientInitReply(Result<Vec<u8>, &'a str>),
+    /// name of the binding mode that became active, `default` when no mode is entered
+    ModeChanged(&'a str),
+    /// same as `WorkspaceList` with the name of each workspace, empty for unnamed ones
+    WorkspaceListNamed(Vec<(u32, &'a str)>),
+}
+
+impl<'a> Message<'a> {
+    /// Topic of broadcast events, `None` for requests and replies
+    pub fn topic(&self) -> Option<Topic> {
+        match self {
+            Message::KeyboardLayout(_) => Some(Topic::KeyboardLayout),
+            Message::WorkspaceList(_)
+            | Message::WorkspaceActive(_)
+            | Message::WorkspaceListNamed(_) => Some(Topic::Workspace),
+            Message::FocusedWindow(..) => Some(Topic::FocusedWindow),
+            Message::WindowOpened(_) | Message::WindowClosed(_) => Some(Topic::Window),
+            Message::LayoutChanged(_) => Some(Topic::Layout),
+            Message::ConfigReloaded => Some(Topic::Config),
+            Message::ModeChanged(_) => Some(Topic::Mode),
+            Message::RequestClientInit { .. }
+            | Message::ClientInitReply(_)
+            | Message::Command(_)
+            | Message::CommandReply(_)
+            | Message::RequestTree
+            | Message::Tree(_)
+            | Message::Subscribe(_) => None,
+        }
+    }
+
+    pub fn tag(&self) -> u8 {
+        match self {
+            Message::KeyboardLayout(_) => MESSAGE_KEYBOARD_LAYOUT_TAG,
+            Message::WorkspaceList(_) => MESSAGE_WORKSPACE_LIST_TAG,
+            Message::WorkspaceActive(_) => MESSAGE_WORKSPACE_ACTIVE_TAG,
+            Message::RequestClientInit { .. } => MESSAGE_REQUEST_CLIENT_INIT_TAG,
+            Message::Command(_) => MESSAGE_COMMAND_TAG,
+            Message::CommandReply(_) => MESSAGE_COMMAND_REPLY_TAG,
+            Message::RequestTree => MESSAGE_REQUEST_TREE_TAG,
+            Message::Tree(_) => MESSAGE_TREE_TAG,
+            Message::Subscribe(_) => MESSAGE_SUBSCRIBE_TAG,
+            Message::FocusedWindow(..) => MESSAGE_FOCUSED_WINDOW_TAG,
+            Message::WindowOpened(_) => MESSAGE_WINDOW_OPENED_TAG,
+            Message::WindowClosed(_) => MESSAGE_WINDOW_CLOSED_TAG,
+            Message::LayoutChanged(_) => MESSAGE_LAYOUT_CHANGED_TAG,
+            Message::ConfigReloaded => MESSAGE_CONFIG_RELOADED_TAG,
+            Message::ClientInitReply(_) => MESSAGE_CLIENT_INIT_REPLY_TAG,
+            Message::ModeChanged(_) => MESSAGE_MODE_CHANGED_TAG,
+            Message::WorkspaceListNamed(_) => MESSAGE_WORKSPACE_LIST_NAMED_TAG,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        // first 8 bytes for size of the message
+        bytes.extend_from_slice(&[0u8; 8]);
+
+        // next 1 byte is the integer value of the Message type tag,
+        // followed by actual value
+        bytes.push(self.tag());
+        match self {
+            Message::KeyboardLayout(name) => {
+                bytes.extend_from_slice(name.as_bytes());
+            }
+            Message::WorkspaceList(workspaces) => {
+                bytes.extend_from_slice(&workspaces.len().to_le_bytes());
+                for workspace in workspaces {
+                    bytes.extend_from_slice(&workspace.to_le_bytes());
+                }
+            }
+            Message::WorkspaceActive(id) => {
+                bytes.extend_from_slice(&id.to_le_bytes());
+            }
+            Message::RequestClientInit { version, supported } => {
+                bytes.extend_from_slice(&version.to_le_bytes());
+                bytes.extend_from_slice(supported);
+            }
+            Message::Command(command) => {
+                bytes.extend_from_slice(command.as_bytes());
+            }
+            Message::CommandReply(result) => match result {
+                Ok(()) => bytes.push(0),
+                Err(err) => {
+                    bytes.push(1);
+                    bytes.extend_from_slice(err.as_bytes());
+                }
+            },
+            Message::RequestTree => {}
+            Message::Tree(json) => {
+                bytes.extend_from_slice(json.as_bytes());
+            }
+            Message::Subscribe(topics) => {
+                bytes.extend(topics.iter().map(|topic| *topic as u8));
+            }
+            Message::FocusedWindow(window, title) => {
+                bytes.extend_from_slice(&window.to_le_bytes());
+                bytes.extend_from_slice(title.as_bytes());
+            }
+            Message::WindowOpened(window) => {
+                bytes.extend_from_slice(&window.to_le_bytes());
+            }
+            Message::WindowClosed(window) => {
+                bytes.extend_from_slice(&window.to_le_bytes());
+            }
+            Message::LayoutChanged(workspace_id) => {
+                bytes.extend_from_slice(&workspace_id.to_le_bytes());
+            }
+            Message::ConfigReloaded => {}
+            Message::ClientInitReply(result) => match result {
+                Ok(supported) => {
+                    bytes.push(0);
+                    bytes.extend_from_slice(supported);
+                }
+                Err(err) => {
+                    bytes.push(1);
+                    bytes.extend_from_slice(err.as_bytes());
+                }
+            },
+            Message::ModeChanged(name) => {
+                bytes.extend_from_slice(name.as_bytes());
+            }
+            Message::WorkspaceListNamed(workspaces) => {
+                bytes.extend_from_slice(&workspaces.len().to_le_bytes());
+                for (id, name) in workspaces {
+                    bytes.extend_from_slice(&id.to_le_bytes());
+                    bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+                    bytes.extend_from_slice(name.as_bytes());
+                }
+            }
+        };
+
+        // write actual size value in the first 8 bytes
+        let message_size = (bytes.len() - 8) as u64;
+        bytes[0..8].copy_from_slice(&message_size.to_le_bytes());
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &'a [u8]) -> Option<Self> {
+        if bytes.is_empty() {
+            return None;
+        }
+        // first 1 byte is the integer value of the Message type tag
+        let msg_type_tag = bytes[0];
+        let data = &bytes[1..];
+        match msg_type_tag {
+            MESSAGE_KEYBOARD_LAYOUT_TAG => Self::str_from_bytes(data).map(Message::KeyboardLayout),
+            MESSAGE_WORKSPACE_LIST_TAG => {
+                let (len_bytes, ids_bytes) = data.split_at_checked(size_of::<usize>())?;
+                let len = usize::from_le_bytes(len_bytes.try_into().ok()?);
+                if ids_bytes.len() != len * size_of::<u32>() {
+                    warn!(
+                        "workspace list of length {} doesn't match payload size {}",
+                        len,
+                        ids_bytes.len()
+                    );
+                    return None;
+                }
+                let ids = ids_bytes
+                    .chunks_exact(size_of::<u32>())
+                    .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+                    .collect();
+                Some(Message::WorkspaceList(ids))
+            }
+            MESSAGE_WORKSPACE_ACTIVE_TAG => {
+                let id = u32::from_le_bytes(data.try_into().ok()?);
+                Some(Message::WorkspaceActive(id))
+            }
+            MESSAGE_REQUEST_CLIENT_INIT_TAG if data.is_empty() => {
+                Some(Message::RequestClientInit {
+                    version: 0,
+                    supported: vec![],
+                })
+            }
+            MESSAGE_REQUEST_CLIENT_INIT_TAG => {
+                let (version_bytes, supported) = data.split_at_checked(size_of::<u32>())?;
+                let version = u32::from_le_bytes(version_bytes.try_into().ok()?);
+                Some(Message::RequestClientInit {
+                    version,
+                    supported: supported.to_vec(),
+                })
+            }
+            MESSAGE_COMMAND_TAG => Self::str_from_bytes(data).map(Message::Command),
+            MESSAGE_COMMAND_REPLY_TAG => match data.split_first() {
+                Some((0, _)) => Some(Message::CommandReply(Ok(()))),
+                Some((_, err)) => {
+                    Self::str_from_bytes(err).map(|err| Message::CommandReply(Err(err)))
+                }
+                None => None,
+            },
+            MESSAGE_REQUEST_TREE_TAG => Some(Message::RequestTree),
+            MESSAGE_TREE_TAG => Self::str_from_bytes(data).map(Message::Tree),
+            MESSAGE_SUBSCRIBE_TAG => {
+                let topics = data
+                    .iter()
+                    .map(|&value| Topic::from_u8(value))
+                    .collect::<Option<Vec<_>>>();
+                if topics.is_none() {
+                    warn!("unknown topic in subscribe message: {:?}", data);
+                }
+                topics.map(Message::Subscribe)
+            }
+            MESSAGE_FOCUSED_WINDOW_TAG => {
+                let (window_bytes, title_bytes) = data.split_at_checked(size_of::<u32>())?;
+                let window = u32::from_le_bytes(window_bytes.try_into().ok()?);
+                Self::str_from_bytes(title_bytes).map(|title| Message::FocusedWindow(window, title))
+            }
+            MESSAGE_WINDOW_OPENED_TAG => {
+                let window = u32::from_le_bytes(data.try_into().ok()?);
+                Some(Message::WindowOpened(window))
+            }
+            MESSAGE_WINDOW_CLOSED_TAG => {
+                let window = u32::from_le_bytes(data.try_into().ok()?);
+                Some(Message::WindowClosed(window))
+            }
+            MESSAGE_LAYOUT_CHANGED_TAG => {
+                let workspace_id = u32::from_le_bytes(data.try_into().ok()?);
+                Some(Message::LayoutChanged(workspace_id))
+            }
+            MESSAGE_CONFIG_RELOADED_TAG => Some(Message::ConfigReloaded),
+            MESSAGE_CLIENT_INIT_REPLY_TAG => match data.split_first() {
+                Some((0, supported)) => Some(Message::ClientInitReply(Ok(supported.to_vec()))),
+                Some((_, err)) => {
+                    Self::str_from_bytes(err).map(|err| Message::ClientInitReply(Err(err)))
+                }
+                None => None,
+            },
+            MESSAGE_MODE_CHANGED_TAG => Self::str_from_bytes(data).map(Message::ModeChanged),
+            MESSAGE_WORKSPACE_LIST_NAMED_TAG => {
+                let (len_bytes, mut entries_bytes) = data.split_at_checked(size_of::<usize>())?;
+                let len = usize::from_le_bytes(len_bytes.try_into().ok()?);
+                let mut workspaces = Vec::new();
+                for _ in 0..len {
+                    let (id_bytes, rest) = entries_bytes.split_at_checked(size_of::<u32>())?;
+                    let (name_len_bytes, rest) = rest.split_at_checked(size_of::<u32>())?;
+                    let name_len = u32::from_le_bytes(name_len_bytes.try_into().ok()?) as usize;
+                    let (name_bytes, rest) = rest.split_at_checked(name_len)?;
+                    let id = u32::from_le_bytes(id_bytes.try_into().ok()?);
+                    workspaces.push((id, Self::str_from_bytes(name_bytes)?));
+                    entries_bytes = rest;
+                }
+                if !entries_bytes.is_empty() {
+                    warn!(
+                        "named workspace list of length {} has {} trailing bytes",
+                        len,
+                        entries_bytes.len()
+                    );
+                    return None;
+                }
+                Some(Message::WorkspaceListNamed(workspaces))
+            }
+            _ => None,
+        }
+    }
+
+    fn str_from_bytes(bytes: &'a [u8]) -> Option<&'a str> {
+        match std::str::from_utf8(bytes) {
+            Ok(str) => Some(str),
+            Err(err) => {
+                warn!("message payload is not a valid utf-8 string: {}", err);
+                None
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FrameError {
+    Io(io::Error),
+    /// declared payload size exceeds the decoder's limit
+    TooLarge(usize),
+    /// frame without the message type tag
+    Empty,
+}
+
+impl std::fmt::Display for FrameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameError::Io(err) => write!(f, "io error: {}", err),
+            FrameError::TooLarge(size) => write!(f, "frame of size {} exceeds the limit", size),
+            FrameError::Empty => write!(f, "empty frame"),
+        }
+    }
+}
+
+/// Reassembles size prefixed frames from a stream that may deliver them split
+/// across several reads or coalesced into one
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+    max_frame_size: usize,
+}
+
+impl FrameDecoder {
+    pub fn new(max_frame_size: usize) -> Self {
+        Self {
+            buffer: Vec::new(),
+            max_frame_size,
+        }
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Does a single read from the reader into the internal buffer, returns amount of bytes read,
+    /// 0 means the other side closed the stream
+    pub fn read_from<R: Read>(&mut self, reader: &mut R) -> io::Result<usize> {
+        let mut chunk = [0u8; 4096];
+        let n_bytes = reader.read(&mut chunk)?;
+        self.push(&chunk[..n_bytes]);
+        Ok(n_bytes)
+    }
+
+    /// Pops next complete frame payload (message tag + value), `Ok(None)` if more bytes are needed
+    pub fn next_frame(&mut self) -> Result<Option<Vec<u8>>, FrameError> {
+        let Some(size_bytes) = self.buffer.get(..FRAME_SIZE_LEN) else {
+            return Ok(None);
+        };
+        let frame_size = u64::from_le_bytes(size_bytes.try_into().unwrap()) as usize;
+        if frame_size > self.max_frame_size {
+            return Err(FrameError::TooLarge(frame_size));
+        }
+        if frame_size == 0 {
+            return Err(FrameError::Empty);
+        }
+        if self.buffer.len() < FRAME_SIZE_LEN + frame_size {
+            return Ok(None);
+        }
+        let frame = self.buffer[FRAME_SIZE_LEN..FRAME_SIZE_LEN + frame_size].to_vec();
+        self.buffer.drain(..FRAME_SIZE_LEN + frame_size);
+        Ok(Some(frame))
+    }
+
+    /// Blocks until the next complete frame arrives, `Ok(None)` if the stream was closed
+    pub fn read_frame<R: Read>(&mut self, reader: &mut R) -> Result<Option<Vec<u8>>, FrameError> {
+        loop {
+            if let Some(frame) = self.next_frame()? {
+                return Ok(Some(frame));
+            }
+            if self.read_from(reader).map_err(FrameError::Io)? == 0 {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(message: Message) {
+        let bytes = message.as_bytes();
+        let size = u64::from_le_bytes(bytes[..8].try_into().unwrap()) as usize;
+        assert_eq!(size, bytes.len() - 8);
+        assert_eq!(Message::from_bytes(&bytes[8..]), Some(message));
+    }
+
+    #[test]
+    fn round_trip_all_messages() {
+        round_trip(Message::KeyboardLayout("us"));
+        round_trip(Message::WorkspaceList(vec![1, 2, 9]));
+        round_trip(Message::WorkspaceList(vec![]));
+        round_trip(Message::WorkspaceActive(3));
+        round_trip(Message::RequestClientInit {
+            version: PROTOCOL_VERSION,
+            supported: supported_message_tags(),
+        });
+        round_trip(Message::ClientInitReply(Ok(supported_message_tags())));
+        round_trip(Message::ClientInitReply(Err("protocol version mismatch")));
+        round_trip(Message::Command("focus_window left"));
+        round_trip(Message::CommandReply(Ok(())));
+        round_trip(Message::RequestTree);
+        round_trip(Message::Tree("{\"workspaces\":[]}"));
+        round_trip(Message::Subscribe(Topic::ALL.to_vec()));
+        round_trip(Message::Subscribe(vec![]));
+        round_trip(Message::FocusedWindow(0x1e00003, "Terminal"));
+        round_trip(Message::FocusedWindow(0x1e00003, ""));
+        round_trip(Message::WindowOpened(0x1e00003));
+        round_trip(Message::WindowClosed(0x1e00003));
+        round_trip(Message::LayoutChanged(2));
+        round_trip(Message::ConfigReloaded);
+        round_trip(Message::ModeChanged("resize"));
+        round_trip(Message::WorkspaceListNamed(vec![
+            (1, "web"),
+            (2, ""),
+            (5, "chat"),
+        ]));
+        round_trip(Message::WorkspaceListNamed(vec![]));
+        round_trip(Message::CommandReply(Err(
+            "no command matching string: foo",
+        )));
+    }
+
+    #[test]
+    fn frame_decoder_reassembles_split_and_coalesced_frames() {
+        let mut stream = Message::WorkspaceActive(2).as_bytes();
+        stream.extend(Message::KeyboardLayout("us").as_bytes());
+        let mut decoder = FrameDecoder::new(DEFAULT_MAX_FRAME_SIZE);
+        let mut frames = Vec::new();
+        for byte in stream {
+            decoder.push(&[byte]);
+            while let Some(frame) = decoder.next_frame().unwrap() {
+                frames.push(frame);
+            }
+        }
+        assert_eq!(frames.len(), 2);
+        assert_eq!(
+            Message::from_bytes(&frames[0]),
+            Some(Message::WorkspaceActive(2))
+        );
+        assert_eq!(
+            Message::from_bytes(&frames[1]),
+            Some(Message::KeyboardLayout("us"))
+        );
+    }
+
+    #[test]
+    fn frame_decoder_rejects_oversized_and_empty_frames() {
+        let mut decoder = FrameDecoder::new(8);
+        decoder.push(&Message::Tree("{\"workspaces\":[]}").as_bytes());
+        assert!(matches!(decoder.next_frame(), Err(FrameError::TooLarge(_))));
+
+        let mut decoder = FrameDecoder::new(8);
+        decoder.push(&0u64.to_le_bytes());
+        assert!(matches!(decoder.next_frame(), Err(FrameError::Empty)));
+    }
+
+    #[test]
+    fn frame_decoder_reads_until_eof() {
+        let mut stream = Message::RequestTree.as_bytes();
+        stream.extend(Message::WindowOpened(7).as_bytes());
+        let mut reader = stream.as_slice();
+        let mut decoder = FrameDecoder::new(DEFAULT_MAX_FRAME_SIZE);
+        assert_eq!(
+            decoder.read_frame(&mut reader).unwrap(),
+            Some(vec![MESSAGE_REQUEST_TREE_TAG])
+        );
+        assert!(decoder.read_frame(&mut reader).unwrap().is_some());
+        assert_eq!(decoder.read_frame(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn request_client_init_without_payload_is_version_0() {
+        assert_eq!(
+            Message::from_bytes(&[MESSAGE_REQUEST_CLIENT_INIT_TAG]),
+            Some(Message::RequestClientInit {
+                version: 0,
+                supported: vec![]
+            })
+        );
+    }
+
+    #[test]
+    fn message_kinds_match_shared_definition_file() {
+        let definitions = include_str!("../../protocol/messages.def");
+        let mut version = None;
+        let mut kinds = Vec::new();
+        for line in definitions.lines().map(str::trim) {
+            if let Some(args) = line
+                .strip_prefix("WM_PROTOCOL_VERSION(")
+                .and_then(|rest| rest.strip_suffix(')'))
+            {
+                version = Some(args.trim().parse::<u32>().unwrap());
+            } else if let Some(args) = line
+                .strip_prefix("WM_MESSAGE(")
+                .and_then(|rest| rest.strip_suffix(')'))
+            {
+                let (name, tag) = args.split_once(',').unwrap();
+                kinds.push((name.trim(), tag.trim().parse::<u8>().unwrap()));
+            }
+        }
+        assert_eq!(version, Some(PROTOCOL_VERSION));
+        assert_eq!(kinds, MESSAGE_KINDS);
+    }
+
+    #[test]
+    fn from_bytes_rejects_malformed_payloads() {
+        assert_eq!(Message::from_bytes(&[]), None);
+        assert_eq!(Message::from_bytes(&[255]), None);
+        assert_eq!(
+            Message::from_bytes(&[MESSAGE_WORKSPACE_ACTIVE_TAG, 1, 0]),
+            None
+        );
+        assert_eq!(
+            Message::from_bytes(&[MESSAGE_COMMAND_TAG, 0xff, 0xfe]),
+            None
+        );
+        let mut workspace_list = vec![MESSAGE_WORKSPACE_LIST_TAG];
+        workspace_list.extend_from_slice(&2usize.to_le_bytes());
+        workspace_list.extend_from_slice(&1u32.to_le_bytes());
+        assert_eq!(Message::from_bytes(&workspace_list), None);
+        let mut named_workspace_list = vec![MESSAGE_WORKSPACE_LIST_NAMED_TAG];
+        named_workspace_list.extend_from_slice(&1usize.to_le_bytes());
+        named_workspace_list.extend_from_slice(&1u32.to_le_bytes());
+        named_workspace_list.extend_from_slice(&10u32.to_le_bytes());
+        named_workspace_list.extend_from_slice(b"web");
+        assert_eq!(Message::from_bytes(&named_workspace_list), None);
+        assert_eq!(Message::from_bytes(&[MESSAGE_SUBSCRIBE_TAG, 0, 200]), None);
+        assert_eq!(Message::from_bytes(&[MESSAGE_FOCUSED_WINDOW_TAG, 1]), None);
+    }
+
+    #[test]
+    fn socket_path_per_display() {
+        assert_eq!(
+            socket_path(None, Some("/run/user/1000".into()), Some(":100")),
+            PathBuf::from("/run/user/1000/x11_wm_rust.100.socket")
+        );
+        assert_eq!(
+            socket_path(None, None, Some("localhost:10.0")),
+            PathBuf::from("/tmp/x11_wm_rust.10.socket")
+        );
+        assert_eq!(
+            socket_path(Some("".into()), Some("".into()), None),
+            PathBuf::from("/tmp/x11_wm_rust.0.socket")
+        );
+        assert_eq!(
+            socket_path(
+                Some("/tmp/custom".into()),
+                Some("/run/user/1000".into()),
+                Some(":1")
+            ),
+            PathBuf::from("/tmp/custom")
+        );
+    }
+}
@@ -1,3 +1,7 @@
+pub mod bar_message;
+
+use serde::{Deserialize, Serialize};
+
 #[allow(dead_code)]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum RectSide {
@@ -8,7 +12,7 @@ pub enum RectSide {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Rect {
     pub x: i32,
     pub y: i32,
@@ -106,6 +110,17 @@ impl Rect {
         ((x2 - x1).pow(2) + (y2 - y1).pow(2)).isqrt()
     }
 
+    /// Rect `progress` of the way from this one to `to`, 0.0 gives this rect and 1.0 gives `to`
+    pub fn interpolate(&self, to: &Rect, progress: f64) -> Rect {
+        let lerp = |from: f64, to: f64| (from + (to - from) * progress).round();
+        Rect {
+            x: lerp(self.x as f64, to.x as f64) as i32,
+            y: lerp(self.y as f64, to.y as f64) as i32,
+            width: lerp(self.width as f64, to.width as f64) as u32,
+            height: lerp(self.height as f64, to.height as f64) as u32,
+        }
+    }
+
     #[allow(dead_code)]
     pub fn available_rect_after_adding_rects(
         &self,
@@ -994,4 +1009,29 @@ mod tests {
         let actual_avail_rect = parent_rect.available_rect_after_adding_rect(&added_rect);
         assert_eq!(expected_avail_rect, actual_avail_rect);
     }
+
+    #[test]
+    fn interpolate_rect() {
+        let from = Rect {
+            x: -100,
+            y: 0,
+            width: 200,
+            height: 100,
+        };
+        let to = Rect {
+            x: 100,
+            y: 1080,
+            width: 400,
+            height: 100,
+        };
+        assert_eq!(from.interpolate(&to, 0.0), from);
+        assert_eq!(from.interpolate(&to, 1.0), to);
+        let expected_rect = Rect {
+            x: 0,
+            y: 540,
+            width: 300,
+            height: 100,
+        };
+        assert_eq!(from.interpolate(&to, 0.5), expected_rect);
+    }
 }
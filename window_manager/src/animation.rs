@@ -0,0 +1,188 @@
+use std::{
+    collections::HashMap,
+    os::fd::RawFd,
+    time::{Duration, Instant},
+};
+
+use base::Rect;
+use log::warn;
+use x11_bindings::{bindings::xcb_window_t, timerfd::TimerFd};
+
+use crate::{config::Config, connection::Connection};
+
+/// Time between two frames of the running animations
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Rect and border a window is configured with and whether it's mapped, as the workspaces see it
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowGeometry {
+    pub window: xcb_window_t,
+    pub rect: Rect,
+    pub border_size: u32,
+    pub mapped: bool,
+}
+
+#[derive(Debug)]
+struct WindowAnimation {
+    from: Rect,
+    to: Rect,
+    border_size: u32,
+    start: Instant,
+    /// the window was moved out of view, it stays mapped until it gets there
+    unmap_when_done: bool,
+}
+
+impl WindowAnimation {
+    fn progress(&self, now: Instant, duration: Duration) -> f64 {
+        (now.duration_since(self.start).as_secs_f64() / duration.as_secs_f64()).min(1.0)
+    }
+}
+
+/// Windows sliding from the rect they had to the one the workspaces gave them, a frame is
+/// configured every time the timer expires
+pub struct Animations {
+    timer: TimerFd,
+    running: HashMap<xcb_window_t, WindowAnimation>,
+}
+
+impl Animations {
+    pub fn new() -> Result<Self, std::io::Error> {
+        Ok(Self {
+            timer: TimerFd::new()?,
+            running: HashMap::new(),
+        })
+    }
+
+    pub fn timer_fd(&self) -> RawFd {
+        self.timer.fd
+    }
+
+    /// Compares the window geometries before and after handling events. Animating windows are
+    /// retargeted and continue from where they are, so new input never waits for them. Other
+    /// moved windows start animating only when `animate` is set, e.g. after a keybinding
+    pub fn update(
+        &mut self,
+        before: &[WindowGeometry],
+        after: &[WindowGeometry],
+        animate: bool,
+        conn: &Connection,
+        config: &Config,
+    ) {
+        let Some(duration) = animation_duration(config) else {
+            self.finish_all(conn);
+            return;
+        };
+        if !animate && (self.running.is_empty() || before == after) {
+            return;
+        }
+        let now = Instant::now();
+        self.running
+            .retain(|window, _| after.iter().any(|geometry| geometry.window == *window));
+        for geometry in after {
+            if let Some(animation) = self.running.get_mut(&geometry.window) {
+                if animation.to != geometry.rect || animation.border_size != geometry.border_size {
+                    let progress = config
+                        .animation_easing
+                        .apply(animation.progress(now, duration));
+                    animation.from = animation.from.interpolate(&animation.to, progress);
+                    animation.to = geometry.rect.clone();
+                    animation.border_size = geometry.border_size;
+                    animation.start = now;
+                }
+                if !geometry.mapped && !animation.unmap_when_done {
+                    conn.map_window(geometry.window);
+                }
+                animation.unmap_when_done = !geometry.mapped;
+                continue;
+            }
+            if !animate {
+                continue;
+            }
+            let Some(previous) = before.iter().find(|g| g.window == geometry.window) else {
+                continue;
+            };
+            // border changes, e.g. fullscreen toggles, and windows out of view just jump
+            if previous.rect == geometry.rect
+                || previous.border_size != geometry.border_size
+                || (!previous.mapped && !geometry.mapped)
+            {
+                continue;
+            }
+            if !geometry.mapped {
+                conn.map_window(geometry.window);
+            }
+            self.running.insert(
+                geometry.window,
+                WindowAnimation {
+                    from: previous.rect.clone(),
+                    to: geometry.rect.clone(),
+                    border_size: geometry.border_size,
+                    start: now,
+                    unmap_when_done: !geometry.mapped,
+                },
+            );
+        }
+        // handling the events configured the windows at their final rects already
+        self.configure_frame(conn, config);
+    }
+
+    pub fn handle_timer(&mut self, conn: &Connection, config: &Config) {
+        if let Err(err) = self.timer.read() {
+            warn!("animation timer read error: {}", err);
+        }
+        self.configure_frame(conn, config);
+    }
+
+    /// Configures the animating windows at their current rects, finished ones are dropped
+    fn configure_frame(&mut self, conn: &Connection, config: &Config) {
+        let Some(duration) = animation_duration(config) else {
+            self.finish_all(conn);
+            return;
+        };
+        let now = Instant::now();
+        self.running.retain(|window, animation| {
+            let progress = animation.progress(now, duration);
+            let rect = animation
+                .from
+                .interpolate(&animation.to, config.animation_easing.apply(progress));
+            conn.window_configure(*window, &rect, animation.border_size);
+            let done = progress >= 1.0;
+            if done && animation.unmap_when_done {
+                conn.unmap_window(*window);
+            }
+            !done
+        });
+        self.arm_timer();
+    }
+
+    fn finish_all(&mut self, conn: &Connection) {
+        if self.running.is_empty() {
+            return;
+        }
+        for (window, animation) in self.running.drain() {
+            conn.window_configure(window, &animation.to, animation.border_size);
+            if animation.unmap_when_done {
+                conn.unmap_window(window);
+            }
+        }
+        self.arm_timer();
+    }
+
+    fn arm_timer(&self) {
+        let res = if self.running.is_empty() {
+            self.timer.disarm()
+        } else {
+            self.timer.arm(FRAME_INTERVAL)
+        };
+        if let Err(err) = res {
+            warn!("failed to set animation timer: {}", err);
+        }
+    }
+}
+
+fn animation_duration(config: &Config) -> Option<Duration> {
+    config
+        .animation_duration_ms
+        .filter(|duration_ms| *duration_ms > 0)
+        .map(Duration::from_millis)
+}
@@ -1,170 +1,294 @@
 use std::{
-    io::{Read, Write},
+    collections::VecDeque,
+    fs::{self, Permissions},
+    io::{self, ErrorKind, Write},
     os::{
         fd::{AsRawFd, RawFd},
-        unix::net::UnixStream,
+        unix::{
+            fs::PermissionsExt,
+            net::{UnixListener, UnixStream},
+        },
     },
+    path::Path,
 };
 
+use base::bar_message::{FrameDecoder, Topic};
 use log::warn;
 
-const MESSAGE_KEYBOARD_LAYOUT_TAG: u8 = 0;
-const MESSAGE_WORKSPACE_LIST_TAG: u8 = 1;
-const MESSAGE_WORKSPACE_ACTIVE_TAG: u8 = 2;
-const MESSAGE_REQUEST_CLIENT_INIT_TAG: u8 = 3;
-
-#[derive(Debug, PartialEq, Eq, Clone)]
-pub enum Message<'a> {
-    KeyboardLayout(&'a str),
-    WorkspaceList(Vec<u32>),
-    WorkspaceActive(u32),
-    RequestClientInit,
-    Eof,
-}
+pub use base::bar_message::{
+    DEFAULT_MAX_FRAME_SIZE, Message, PROTOCOL_VERSION, SOCKET_PATH_ENV, socket_path_from_env,
+    supported_message_tags,
+};
 
-impl<'a> Message<'a> {
-    pub fn as_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
-        // first 8 bytes for size of the message
-        bytes.extend_from_slice(&[0u8; 8]);
-
-        // next 1 byte is the integer value of the Message type tag,
-        // followed by actual value
-        match self {
-            Message::KeyboardLayout(name) => {
-                bytes.push(MESSAGE_KEYBOARD_LAYOUT_TAG);
-                bytes.extend_from_slice(name.as_bytes());
-            }
-            Message::WorkspaceList(workspaces) => {
-                bytes.push(MESSAGE_WORKSPACE_LIST_TAG);
-                bytes.extend_from_slice(&workspaces.len().to_le_bytes());
-                for workspace in workspaces {
-                    bytes.extend_from_slice(&workspace.to_le_bytes());
-                }
-            }
-            Message::WorkspaceActive(id) => {
-                bytes.push(MESSAGE_WORKSPACE_ACTIVE_TAG);
-                bytes.extend_from_slice(&id.to_le_bytes());
-            }
-            Message::RequestClientInit => {
-                bytes.push(MESSAGE_REQUEST_CLIENT_INIT_TAG);
-            }
-            Message::Eof => unreachable!(),
-        };
+// outbound queue holds this many frames of the maximum size before the client is dropped
+const OUTBOUND_QUEUE_FRAMES: usize = 4;
 
-        // write actual size value in the first 8 bytes
-        let message_size = (bytes.len() - 8) as u64;
-        bytes[0..8].copy_from_slice(&message_size.to_le_bytes());
+/// Creates the socket accessible only by the owner, the umask covers the window between bind and
+/// chmod
+pub fn bind_listener(path: &Path) -> io::Result<UnixListener> {
+    let old_umask = unsafe { libc::umask(0o177) };
+    let listener = UnixListener::bind(path);
+    unsafe { libc::umask(old_umask) };
+    let listener = listener?;
+    fs::set_permissions(path, Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
 
-        bytes
+/// Checks with SO_PEERCRED that the client runs as the same user as the window manager,
+/// commands like `exec` must not be available to anyone else
+pub fn is_trusted_peer(stream: &UnixStream) -> bool {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut cred_len = size_of::<libc::ucred>() as libc::socklen_t;
+    let res = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut cred_len,
+        )
+    };
+    if res == -1 {
+        warn!(
+            "rejecting unix client, failed to get its credentials: {}",
+            io::Error::last_os_error()
+        );
+        return false;
     }
-
-    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
-        if bytes.is_empty() {
-            return None;
-        }
-        // first 1 byte is the integer value of the Message type tag
-        let msg_type_tag = bytes[0];
-        match msg_type_tag {
-            MESSAGE_REQUEST_CLIENT_INIT_TAG => Some(Message::RequestClientInit),
-            _ => None,
-        }
+    let uid = unsafe { libc::getuid() };
+    if cred.uid != uid {
+        warn!(
+            "rejecting unix client pid {} running as uid {}, window manager runs as uid {}",
+            cred.pid, cred.uid, uid
+        );
+        return false;
     }
+    true
+}
 
-    pub fn read_from_unix_stream(unix_stream: &mut UnixStream) -> Option<Self> {
-        let mut buffer = [0; 4096];
-        match unix_stream.read(&mut buffer[..size_of::<usize>()]) {
-            Ok(n_size_bytes) => {
-                if n_size_bytes == size_of::<usize>() {
-                    if let Ok(arr) = buffer[..size_of::<usize>()].try_into() {
-                        let message_size = usize::from_le_bytes(arr);
-                        match unix_stream.read(&mut buffer[..message_size]) {
-                            Ok(n_bytes) => Message::from_bytes(&buffer[..n_bytes]),
-                            Err(err) => {
-                                warn!(
-                                    "failed to read message payload of size: {}, err: {}",
-                                    message_size, err
-                                );
-                                None
-                            }
-                        }
-                    } else {
-                        warn!(
-                            "failed to get message size from bytes: {:?}",
-                            &buffer[..size_of::<usize>()]
-                        );
-                        None
-                    }
-                } else if n_size_bytes == 0 {
-                    Some(Message::Eof)
-                } else {
-                    warn!(
-                        "received message size in bytes that != size_of::<usize>(): {}",
-                        n_size_bytes
-                    );
-                    None
+struct UnixClient {
+    stream: UnixStream,
+    topics: Vec<Topic>,
+    decoder: FrameDecoder,
+    outbound: VecDeque<u8>,
+    supported: Option<Vec<u8>>, // message tags announced in RequestClientInit, None means all
+    /// rejected client, dropped once the reply already queued for it is written
+    close_after_flush: bool,
+}
+
+impl UnixClient {
+    /// Writes as much of the outbound queue as the socket accepts without blocking,
+    /// returns false if the client has to be dropped
+    fn flush(&mut self) -> bool {
+        while !self.outbound.is_empty() {
+            let (bytes, _) = self.outbound.as_slices();
+            match self.stream.write(bytes) {
+                Ok(0) => {
+                    warn!("client {} doesn't accept any more data", self.fd());
+                    return false;
+                }
+                Ok(n_bytes) => {
+                    self.outbound.drain(..n_bytes);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    warn!("failed to write to client {}, err: {}", self.fd(), err);
+                    return false;
                 }
-            }
-            Err(err) => {
-                warn!("failed to get message size from unix stream: {}", err);
-                None
             }
         }
+        true
+    }
+
+    fn fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
     }
 }
 
+/// Result of reading from a client socket until it would block
+pub struct ClientInput {
+    /// complete frames, each to be parsed with `Message::from_bytes`
+    pub frames: Vec<Vec<u8>>,
+    /// client closed the connection or sent a broken frame, to be removed after handling `frames`
+    pub disconnected: bool,
+}
+
 pub struct UnixClients {
-    unix_clients: Vec<UnixStream>,
+    unix_clients: Vec<UnixClient>,
+    max_frame_size: usize,
 }
 
 impl UnixClients {
-    pub fn new() -> Self {
+    pub fn new(max_frame_size: usize) -> Self {
         Self {
             unix_clients: Vec::new(),
+            max_frame_size,
         }
     }
 
+    /// Queues message for every client subscribed to its topic
     pub fn notify_all(&mut self, message: Message) {
-        let mut to_remove: Vec<RawFd> = Vec::new();
-        for client_stream in self.unix_clients.iter_mut() {
-            if let Err(err) = client_stream.write_all(&message.as_bytes()) {
-                warn!(
-                    "failed to write message {:?} to client, err: {}",
-                    message, err
-                );
-                to_remove.push(client_stream.as_raw_fd());
-            }
-            // trace!("message sent to unix stream client: {:?}", message);
-        }
-        for &fd in &to_remove {
-            if let Some((index, _)) = self
-                .unix_clients
-                .iter()
-                .enumerate()
-                .find(|&(_, client)| client.as_raw_fd() == fd)
-            {
-                self.unix_clients.remove(index);
+        let Some(topic) = message.topic() else {
+            warn!("message {:?} has no topic, not broadcasting it", message);
+            return;
+        };
+        let bytes = message.as_bytes();
+        let subscribed = self
+            .unix_clients
+            .iter()
+            .filter(|client| client.topics.contains(&topic))
+            .map(|client| client.fd())
+            .collect::<Vec<_>>();
+        for fd in subscribed {
+            if !self.enqueue(fd, message.tag(), &bytes) {
+                self.remove_client(fd);
             }
         }
-        to_remove.clear();
     }
 
+    /// Expects socket in non-blocking mode, edge triggered epoll watch for both reading and
+    /// writing is expected to be registered for it
     pub fn add_client(&mut self, unix_stream: UnixStream) {
-        self.unix_clients.push(unix_stream);
+        self.unix_clients.push(UnixClient {
+            stream: unix_stream,
+            topics: Topic::DEFAULT.to_vec(),
+            decoder: FrameDecoder::new(self.max_frame_size),
+            outbound: VecDeque::new(),
+            supported: None,
+            close_after_flush: false,
+        });
     }
 
-    pub fn find_client_by_fd(&mut self, fd: RawFd) -> Option<&mut UnixStream> {
+    fn find_client_by_fd(&mut self, fd: RawFd) -> Option<&mut UnixClient> {
         self.unix_clients
             .iter_mut()
-            .find(|stream| stream.as_raw_fd() == fd)
+            .find(|client| client.fd() == fd)
+    }
+
+    /// Reads everything the client has sent so far, frames may arrive over several calls
+    pub fn receive(&mut self, fd: RawFd) -> Option<ClientInput> {
+        let client = self.find_client_by_fd(fd)?;
+        let mut input = ClientInput {
+            frames: Vec::new(),
+            disconnected: false,
+        };
+        loop {
+            match client.decoder.read_from(&mut client.stream) {
+                Ok(0) => {
+                    input.disconnected = true;
+                    break;
+                }
+                Ok(_) => {}
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    warn!("failed to read from client {}, err: {}", fd, err);
+                    input.disconnected = true;
+                    break;
+                }
+            }
+            // drain frames after every read so that the decoder can reject oversized ones early
+            loop {
+                match client.decoder.next_frame() {
+                    Ok(Some(frame)) => input.frames.push(frame),
+                    Ok(None) => break,
+                    Err(err) => {
+                        warn!("invalid frame from client {}, err: {}", fd, err);
+                        input.disconnected = true;
+                        break;
+                    }
+                }
+            }
+            if input.disconnected {
+                break;
+            }
+        }
+        // a rejected client is only read to notice it hanging up
+        if client.close_after_flush {
+            input.frames.clear();
+        }
+        Some(input)
+    }
+
+    pub fn subscribe(&mut self, fd: RawFd, topics: Vec<Topic>) {
+        if let Some(client) = self.find_client_by_fd(fd) {
+            client.topics = topics;
+        }
+    }
+
+    pub fn set_supported(&mut self, fd: RawFd, supported: Vec<u8>) {
+        if let Some(client) = self.find_client_by_fd(fd) {
+            client.supported = Some(supported);
+        }
+    }
+
+    /// Stops queueing messages for the client and drops it as soon as its outbound queue is
+    /// written, e.g. after telling it why it is rejected
+    pub fn close_after_flush(&mut self, fd: RawFd) {
+        let Some(client) = self.find_client_by_fd(fd) else {
+            return;
+        };
+        client.topics.clear();
+        client.close_after_flush = true;
+        if client.outbound.is_empty() {
+            self.remove_client(fd);
+        }
+    }
+
+    pub fn send_to(&mut self, fd: RawFd, message: Message) {
+        if !self.enqueue(fd, message.tag(), &message.as_bytes()) {
+            self.remove_client(fd);
+        }
+    }
+
+    /// Continues writing queued messages once epoll reports the socket writable again
+    pub fn flush(&mut self, fd: RawFd) {
+        if let Some(client) = self.find_client_by_fd(fd) {
+            if !client.flush() || (client.close_after_flush && client.outbound.is_empty()) {
+                self.remove_client(fd);
+            }
+        }
+    }
+
+    /// Skips messages the client can't parse, returns false if the client has to be dropped
+    fn enqueue(&mut self, fd: RawFd, tag: u8, bytes: &[u8]) -> bool {
+        let max_outbound_size = self.max_frame_size * OUTBOUND_QUEUE_FRAMES;
+        let Some(client) = self.find_client_by_fd(fd) else {
+            return true;
+        };
+        if client.close_after_flush {
+            return true;
+        }
+        if client
+            .supported
+            .as_ref()
+            .is_some_and(|supported| !supported.contains(&tag))
+        {
+            return true;
+        }
+        if client.outbound.len() + bytes.len() > max_outbound_size {
+            warn!(
+                "outbound queue of client {} exceeded {} bytes, dropping the client",
+                fd, max_outbound_size
+            );
+            return false;
+        }
+        client.outbound.extend(bytes);
+        client.flush()
     }
 
+    /// Closing the socket also removes it from epoll's watch list
     pub fn remove_client(&mut self, fd: RawFd) {
         if let Some((index, _)) = self
             .unix_clients
             .iter()
             .enumerate()
-            .find(|&(_, client)| client.as_raw_fd() == fd)
+            .find(|&(_, client)| client.fd() == fd)
         {
             self.unix_clients.remove(index);
         }
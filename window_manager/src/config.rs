@@ -1,7 +1,15 @@
-use std::{fs, io, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::Path,
+};
 
+use base::Rect;
+use regex::Regex;
 use serde::Deserialize;
 
+use crate::keybindings::keybinding_from_bind;
+
 #[allow(dead_code)]
 #[derive(Debug)]
 pub enum ConfigErrors {
@@ -10,6 +18,100 @@ pub enum ConfigErrors {
     ValidationError(String),
 }
 
+/// `[[bind]]` table, structured alternative to a keybinding string
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct BindEntry {
+    pub keys: String,
+    pub action: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// mode the binding belongs to, top level keybindings when missing
+    pub mode: Option<String>,
+    #[serde(default)]
+    pub release: bool,
+}
+
+/// `[[rule]]` table, applied to windows when they're mapped. Every given matcher has to match,
+/// actions of later matching rules override the ones of earlier rules
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct WindowRule {
+    /// class name of WM_CLASS
+    pub class: Option<String>,
+    /// instance name of WM_CLASS
+    pub instance: Option<String>,
+    /// regex searched in _NET_WM_NAME
+    pub title: Option<String>,
+    #[serde(skip)]
+    pub title_regex: Option<Regex>,
+    /// WM_WINDOW_ROLE
+    pub role: Option<String>,
+    /// _NET_WM_WINDOW_TYPE without the prefix, e.g. "dialog" or "splash"
+    #[serde(rename = "type")]
+    pub window_type: Option<String>,
+    /// whether the window has WM_TRANSIENT_FOR set
+    pub transient: Option<bool>,
+
+    pub workspace: Option<u32>,
+    pub floating: Option<bool>,
+    /// initial width of a tiled window, same unit as default_screen_width_percent_tiling
+    pub width_percent: Option<f64>,
+    /// initial rect of a floating window, ignoring its size hints
+    pub rect: Option<Rect>,
+    /// border color while the window isn't focused
+    pub border_color: Option<String>,
+    #[serde(skip)]
+    pub border_color_int: Option<u32>,
+    /// the window doesn't take focus when mapped
+    pub no_focus: Option<bool>,
+    /// a floating window that follows the workspace switches
+    pub sticky: Option<bool>,
+    /// a window covering the whole monitor above the docks, as the `fullscreen` action does
+    pub fullscreen: Option<bool>,
+    /// _NET_WM_WINDOW_OPACITY from 0.0 to 1.0, applied by the compositor
+    pub opacity: Option<f64>,
+}
+
+/// Whether moving focus left or right scrolls the strip to center the focused column
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CenterFocusedColumn {
+    /// scroll only as much as needed to fully show the focused column
+    #[default]
+    Never,
+    Always,
+    /// center when the focused column and the previously focused one don't fit on screen together
+    OnOverflow,
+}
+
+/// Easing curve of window animations
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AnimationEasing {
+    Linear,
+    #[default]
+    EaseOutCubic,
+    EaseInOutCubic,
+}
+
+impl AnimationEasing {
+    /// Eased progress for the linear `progress` from 0.0 to 1.0
+    pub fn apply(self, progress: f64) -> f64 {
+        match self {
+            AnimationEasing::Linear => progress,
+            AnimationEasing::EaseOutCubic => 1.0 - (1.0 - progress).powi(3),
+            AnimationEasing::EaseInOutCubic => {
+                if progress < 0.5 {
+                    4.0 * progress.powi(3)
+                } else {
+                    1.0 - (2.0 - 2.0 * progress).powi(3) / 2.0
+                }
+            }
+        }
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Config {
     pub startup_commands: Vec<String>,
@@ -29,6 +131,46 @@ pub struct Config {
     pub override_to_floating: Vec<String>,
     pub wallpapers_command: Option<String>,
     pub wallpapers_path: Option<String>,
+    pub ipc_max_message_size: Option<usize>,
+    pub ipc_socket_path: Option<String>,
+    /// binding tables of modes entered with `enter_mode <name>`, keyed by mode name
+    pub modes: Option<HashMap<String, Vec<String>>>,
+    /// how long a chord like `Alt+W,3` waits for its next key
+    pub chord_timeout_ms: Option<u64>,
+    /// `[--root|--border] Modifiers+Button action`, Alt+Button1 moves and Alt+Button3 resizes
+    /// floating windows when missing
+    pub mousebindings: Option<Vec<String>>,
+    /// `[[bind]]` tables, added to the keybindings of their mode after the string ones
+    pub bind: Option<Vec<BindEntry>>,
+    /// `[[rule]]` tables matched against every newly mapped window
+    pub rule: Option<Vec<WindowRule>>,
+    /// `<id>:<name>`, e.g. `1:web`, names can be used instead of ids by workspace actions
+    pub workspace_names: Option<Vec<String>>,
+    #[serde(skip)]
+    pub workspace_names_by_id: BTreeMap<u32, String>,
+    /// prune empty unfocused workspaces and keep one empty workspace after the last one,
+    /// prev/next workspace then move between existing workspaces instead of ids 1 to 9
+    #[serde(default)]
+    pub dynamic_workspaces: bool,
+    /// switching to the focused workspace returns to the previously focused one instead
+    #[serde(default)]
+    pub workspace_auto_back_and_forth: bool,
+    /// fractions of the available width `cycle_width` snaps the focused column to, gaps between
+    /// columns are kept so e.g. two columns of 0.5 fill the screen
+    pub preset_column_widths: Option<Vec<f64>>,
+    /// when moving focus left or right centers the focused column, `never` when missing
+    #[serde(default)]
+    pub center_focused_column: CenterFocusedColumn,
+    /// keep the first column at the left edge while the whole strip fits on screen, whatever
+    /// `center_focused_column` says
+    #[serde(default)]
+    pub left_align_fitting_strip: bool,
+    /// windows moved by workspace switches, strip scrolling and other actions slide to their new
+    /// rect over this many milliseconds, no animations when missing or 0
+    pub animation_duration_ms: Option<u64>,
+    /// `ease_out_cubic` when missing
+    #[serde(default)]
+    pub animation_easing: AnimationEasing,
 }
 
 impl Config {
@@ -72,6 +214,67 @@ impl Config {
                             )));
                         }
                     }
+                    for bind in config.bind.iter().flatten() {
+                        if let Err(err) = keybinding_from_bind(bind) {
+                            return Err(ConfigErrors::ValidationError(format!(
+                                "invalid [[bind]] entry with keys = {:?}, action = {:?}, args = {:?}: {}",
+                                bind.keys, bind.action, bind.args, err
+                            )));
+                        }
+                    }
+                    for (idx, rule) in config.rule.iter_mut().flatten().enumerate() {
+                        if let Err(err) = Self::validate_rule(rule) {
+                            return Err(ConfigErrors::ValidationError(format!(
+                                "invalid [[rule]] entry #{}: {}",
+                                idx + 1, err
+                            )));
+                        }
+                    }
+                    if let Some(preset_column_widths) = &config.preset_column_widths {
+                        if preset_column_widths.is_empty() {
+                            return Err(ConfigErrors::ValidationError(
+                                "preset_column_widths is empty".to_string(),
+                            ));
+                        }
+                        if let Some(width) = preset_column_widths
+                            .iter()
+                            .find(|width| !(**width > 0.0 && **width <= 1.0))
+                        {
+                            return Err(ConfigErrors::ValidationError(format!(
+                                "preset_column_widths entry {} is outside of (0.0, 1.0]",
+                                width
+                            )));
+                        }
+                    }
+                    for workspace_name_str in config.workspace_names.iter().flatten() {
+                        match Self::try_workspace_name_from_str(workspace_name_str) {
+                            Ok((workspace_id, name)) => {
+                                if config.workspace_names_by_id.contains_key(&workspace_id) {
+                                    return Err(ConfigErrors::ValidationError(format!(
+                                        "invalid workspace name {:?}: workspace {} is already named",
+                                        workspace_name_str, workspace_id
+                                    )));
+                                }
+                                if let Some((other_id, _)) = config
+                                    .workspace_names_by_id
+                                    .iter()
+                                    .find(|(_, other_name)| **other_name == name)
+                                {
+                                    return Err(ConfigErrors::ValidationError(format!(
+                                        "invalid workspace name {:?}: name {} is already used by workspace {}",
+                                        workspace_name_str, name, other_id
+                                    )));
+                                }
+                                config.workspace_names_by_id.insert(workspace_id, name);
+                            }
+                            Err(err) => {
+                                return Err(ConfigErrors::ValidationError(format!(
+                                    "invalid workspace name {:?}: {}",
+                                    workspace_name_str, err
+                                )));
+                            }
+                        }
+                    }
                     Ok(config)
                 }
                 Err(err) => Err(ConfigErrors::TomlParseError(err)),
@@ -80,6 +283,48 @@ impl Config {
         }
     }
 
+    /// Checks the workspace id, compiles the title regex and parses the border color of the rule
+    fn validate_rule(rule: &mut WindowRule) -> Result<(), String> {
+        if rule.workspace == Some(0) {
+            return Err("invalid workspace id: 0".to_string());
+        }
+        if let Some(title) = &rule.title {
+            match Regex::new(title) {
+                Ok(title_regex) => rule.title_regex = Some(title_regex),
+                Err(err) => return Err(format!("invalid title regex {:?}: {}", title, err)),
+            }
+        }
+        if let Some(color_str) = &rule.border_color {
+            match Self::try_color_from_str(color_str) {
+                Some(color_int) => rule.border_color_int = Some(color_int),
+                None => return Err(format!("border_color contains invalid hex value: {}", color_str)),
+            }
+        }
+        if let Some(width_percent) = rule.width_percent
+            && !(width_percent > 0.0 && width_percent <= 1.0)
+        {
+            return Err(format!("width_percent {} is outside of (0.0, 1.0]", width_percent));
+        }
+        if let Some(opacity) = rule.opacity
+            && !(0.0..=1.0).contains(&opacity)
+        {
+            return Err(format!("opacity {} is outside of [0.0, 1.0]", opacity));
+        }
+        Ok(())
+    }
+
+    fn try_workspace_name_from_str(workspace_name_str: &str) -> Result<(u32, String), String> {
+        let Some((id_str, name)) = workspace_name_str.split_once(':') else {
+            return Err("expected <id>:<name>".to_string());
+        };
+        let workspace_id = match id_str.trim().parse::<u32>() {
+            Ok(workspace_id) if workspace_id > 0 => workspace_id,
+            _ => return Err(format!("invalid workspace id: {}", id_str)),
+        };
+        validate_workspace_name(name)?;
+        Ok((workspace_id, name.to_string()))
+    }
+
     fn try_color_from_str(color_str: &str) -> Option<u32> {
         // take only first 6 characters as xcb_change_window_attributes supports only 24-bit color range
         let hex = color_str.trim_start_matches('#').chars().take(6).collect::<String>();
@@ -162,12 +407,106 @@ impl Default for Config {
             override_to_floating: [""].iter().map(|e|e.to_string()).collect(),
             wallpapers_command: None,
             wallpapers_path: None,
+            ipc_max_message_size: None,
+            ipc_socket_path: None,
+            modes: None,
+            chord_timeout_ms: None,
+            mousebindings: None,
+            bind: None,
+            rule: None,
+            workspace_names: None,
+            workspace_names_by_id: BTreeMap::new(),
+            dynamic_workspaces: false,
+            workspace_auto_back_and_forth: false,
+            preset_column_widths: None,
+            center_focused_column: CenterFocusedColumn::Never,
+            left_align_fitting_strip: false,
+            animation_duration_ms: None,
+            animation_easing: AnimationEasing::EaseOutCubic,
         }
     }
 }
 
+/// Names are single words that can't be mistaken for a workspace id or `next` and `prev`
+pub fn validate_workspace_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        Err(format!("workspace name {:?} is empty or contains whitespace", name))
+    } else if name.parse::<u32>().is_ok() || matches!(name, "next" | "prev" | "back_and_forth") {
+        Err(format!("workspace name {:?} is reserved", name))
+    } else {
+        Ok(())
+    }
+}
+
 #[allow(dead_code)]
 #[inline]
 fn create_color_with_alpha(red: u8, green: u8, blue: u8, alpha: u8) -> u32 {
     ((alpha as u32) << 24) | ((red as u32) << 16) | ((green as u32) << 8) | (blue as u32)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REQUIRED_CONFIG: &str = r##"
+startup_commands = []
+keybindings = ["Alt+Q kill_focused_window"]
+minimum_width_tiling = 10
+minimum_height_tiling = 10
+default_screen_width_percent_tiling = 0.324
+outer_gap_horiz = 10
+outer_gap_vert = 10
+inner_gap = 5
+border_size = 3
+border_color_inactive_str = "#3c3a3a"
+border_color_active_str = "#82948c"
+switch_to_workspace_on_focused_window_moved = false
+override_to_floating = []
+"##;
+
+    /// Loads the required settings followed by `extra` through a config file
+    fn load(name: &str, extra: &str) -> Result<Config, ConfigErrors> {
+        let path = std::env::temp_dir().join(format!(
+            "x11_wm_rust_test_{}_{}.toml",
+            std::process::id(),
+            name
+        ));
+        fs::write(&path, format!("{}{}", REQUIRED_CONFIG, extra)).unwrap();
+        let config = Config::new(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        config
+    }
+
+    #[test]
+    fn valid_bind() {
+        let config = load(
+            "valid_bind",
+            r#"
+[[bind]]
+keys = "Alt+Shift+Return"
+action = "exec"
+args = ["alacritty", "--class", "floating_terminal"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.bind.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn invalid_bind_names_the_entry() {
+        let err = load(
+            "invalid_bind",
+            r#"
+[[bind]]
+keys = "Alt+Shift+K"
+action = "window_size_change"
+args = ["vertical"]
+"#,
+        )
+        .unwrap_err();
+        let ConfigErrors::ValidationError(err) = err else {
+            panic!("expected a validation error, got {:?}", err);
+        };
+        assert!(err.contains("Alt+Shift+K"), "{}", err);
+        assert!(err.contains("window_size_change"), "{}", err);
+    }
+}
@@ -1,392 +1,82 @@
-use std::{cmp::Reverse, process::Command};
+use std::{cmp::Reverse, collections::HashMap, process::Command, time::Duration};
 
-use log::{error, trace};
+use log::{error, trace, warn};
 use x11_bindings::{
     bindings::{
         XCB_MOD_MASK_1, XCB_MOD_MASK_2, XCB_MOD_MASK_4, XCB_MOD_MASK_CONTROL, XCB_MOD_MASK_LOCK,
-        XCB_MOD_MASK_SHIFT, xcb_keycode_t, xcb_mod_mask_t,
+        XCB_MOD_MASK_SHIFT, XKB_KEY_Escape, XKB_KEY_Hyper_R, XKB_KEY_ISO_Level3_Shift,
+        XKB_KEY_ISO_Level5_Shift, XKB_KEY_Mode_switch, XKB_KEY_Shift_L, XKB_KEY_Shift_R,
+        xcb_keycode_t, xcb_mod_mask_t, xkb_keysym_t,
     },
-    connection::Connection,
+    connection::{self, Connection},
 };
 
-use crate::{bar_message::UnixClients, config::Config, monitor::Monitor};
-
-// extracted by running `xev` command line tool`
-#[allow(non_camel_case_types)]
-#[repr(u8)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Keycodes {
-    Escape = 9,
-    Num_1 = 10,
-    Num_2 = 11,
-    Num_3 = 12,
-    Num_4 = 13,
-    Num_5 = 14,
-    Num_6 = 15,
-    Num_7 = 16,
-    Num_8 = 17,
-    Num_9 = 18,
-    Num_0 = 19,
-    minus = 20,
-    equal = 21,
-    BackSpace = 22,
-    Tab = 23,
-    q = 24,
-    w = 25,
-    e = 26,
-    r = 27,
-    t = 28,
-    y = 29,
-    u = 30,
-    i = 31,
-    o = 32,
-    p = 33,
-    bracketleft = 34,
-    bracketright = 35,
-    Return = 36,
-    Control_L = 37,
-    a = 38,
-    s = 39,
-    d = 40,
-    f = 41,
-    g = 42,
-    h = 43,
-    j = 44,
-    k = 45,
-    l = 46,
-    semicolon = 47,
-    apostrophe = 48,
-    Shift_L = 50,
-    backslash = 51,
-    z = 52,
-    x = 53,
-    c = 54,
-    v = 55,
-    b = 56,
-    n = 57,
-    m = 58,
-    comma = 59,
-    period = 60,
-    slash = 61,
-    Shift_R = 62,
-    Alt_L = 64,
-    space = 65,
-    F1 = 67,
-    F2 = 68,
-    F3 = 69,
-    F4 = 70,
-    F5 = 71,
-    F6 = 72,
-    F7 = 73,
-    F8 = 74,
-    F9 = 75,
-    F10 = 76,
-    KP_Home = 79,
-    KP_Up = 80,
-    KP_Prior = 81,
-    KP_Subtract = 82,
-    KP_Left = 83,
-    KP_Begin = 84,
-    KP_Right = 85,
-    KP_Add = 86,
-    KP_End = 87,
-    KP_Down = 88,
-    KP_Next = 89,
-    KP_Insert = 90,
-    KP_Delete = 91,
-    less = 94,
-    F11 = 95,
-    F12 = 96,
-    KP_Enter = 104,
-    Control_R = 105,
-    KP_Divide = 106,
-    Print = 107,
-    Alt_R = 108,
-    Home = 110,
-    Up = 111,
-    Page_Up = 112,
-    Left = 113,
-    Right = 114,
-    End = 115,
-    Down = 116,
-    Page_Down = 117,
-    Insert = 118,
-    Delete = 119,
-    KP_Equal = 125,
-    plusminus = 126,
-    Pause = 127,
-    KP_Decimal = 129,
-    Super_L = 133,
-    Super_R = 134,
-    Menu = 135,
-    Cancel = 136,
-    Redo = 137,
-    Undo = 139,
-    Find = 144,
-    Help = 146,
-}
-
-impl From<Keycodes> for u8 {
-    fn from(value: Keycodes) -> Self {
-        value as u8
-    }
-}
-
-impl TryFrom<u8> for Keycodes {
-    type Error = ();
-
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
-        match value {
-            9 => Ok(Keycodes::Escape),
-            10 => Ok(Keycodes::Num_1),
-            11 => Ok(Keycodes::Num_2),
-            12 => Ok(Keycodes::Num_3),
-            13 => Ok(Keycodes::Num_4),
-            14 => Ok(Keycodes::Num_5),
-            15 => Ok(Keycodes::Num_6),
-            16 => Ok(Keycodes::Num_7),
-            17 => Ok(Keycodes::Num_8),
-            18 => Ok(Keycodes::Num_9),
-            19 => Ok(Keycodes::Num_0),
-            20 => Ok(Keycodes::minus),
-            21 => Ok(Keycodes::equal),
-            22 => Ok(Keycodes::BackSpace),
-            23 => Ok(Keycodes::Tab),
-            24 => Ok(Keycodes::q),
-            25 => Ok(Keycodes::w),
-            26 => Ok(Keycodes::e),
-            27 => Ok(Keycodes::r),
-            28 => Ok(Keycodes::t),
-            29 => Ok(Keycodes::y),
-            30 => Ok(Keycodes::u),
-            31 => Ok(Keycodes::i),
-            32 => Ok(Keycodes::o),
-            33 => Ok(Keycodes::p),
-            34 => Ok(Keycodes::bracketleft),
-            35 => Ok(Keycodes::bracketright),
-            36 => Ok(Keycodes::Return),
-            37 => Ok(Keycodes::Control_L),
-            38 => Ok(Keycodes::a),
-            39 => Ok(Keycodes::s),
-            40 => Ok(Keycodes::d),
-            41 => Ok(Keycodes::f),
-            42 => Ok(Keycodes::g),
-            43 => Ok(Keycodes::h),
-            44 => Ok(Keycodes::j),
-            45 => Ok(Keycodes::k),
-            46 => Ok(Keycodes::l),
-            47 => Ok(Keycodes::semicolon),
-            48 => Ok(Keycodes::apostrophe),
-            50 => Ok(Keycodes::Shift_L),
-            51 => Ok(Keycodes::backslash),
-            52 => Ok(Keycodes::z),
-            53 => Ok(Keycodes::x),
-            54 => Ok(Keycodes::c),
-            55 => Ok(Keycodes::v),
-            56 => Ok(Keycodes::b),
-            57 => Ok(Keycodes::n),
-            58 => Ok(Keycodes::m),
-            59 => Ok(Keycodes::comma),
-            60 => Ok(Keycodes::period),
-            61 => Ok(Keycodes::slash),
-            62 => Ok(Keycodes::Shift_R),
-            64 => Ok(Keycodes::Alt_L),
-            65 => Ok(Keycodes::space),
-            67 => Ok(Keycodes::F1),
-            68 => Ok(Keycodes::F2),
-            69 => Ok(Keycodes::F3),
-            70 => Ok(Keycodes::F4),
-            71 => Ok(Keycodes::F5),
-            72 => Ok(Keycodes::F6),
-            73 => Ok(Keycodes::F7),
-            74 => Ok(Keycodes::F8),
-            75 => Ok(Keycodes::F9),
-            76 => Ok(Keycodes::F10),
-            79 => Ok(Keycodes::KP_Home),
-            80 => Ok(Keycodes::KP_Up),
-            81 => Ok(Keycodes::KP_Prior),
-            82 => Ok(Keycodes::KP_Subtract),
-            83 => Ok(Keycodes::KP_Left),
-            84 => Ok(Keycodes::KP_Begin),
-            85 => Ok(Keycodes::KP_Right),
-            86 => Ok(Keycodes::KP_Add),
-            87 => Ok(Keycodes::KP_End),
-            88 => Ok(Keycodes::KP_Down),
-            89 => Ok(Keycodes::KP_Next),
-            90 => Ok(Keycodes::KP_Insert),
-            91 => Ok(Keycodes::KP_Delete),
-            94 => Ok(Keycodes::less),
-            95 => Ok(Keycodes::F11),
-            96 => Ok(Keycodes::F12),
-            104 => Ok(Keycodes::KP_Enter),
-            105 => Ok(Keycodes::Control_R),
-            106 => Ok(Keycodes::KP_Divide),
-            107 => Ok(Keycodes::Print),
-            108 => Ok(Keycodes::Alt_R),
-            110 => Ok(Keycodes::Home),
-            111 => Ok(Keycodes::Up),
-            112 => Ok(Keycodes::Page_Up),
-            113 => Ok(Keycodes::Left),
-            114 => Ok(Keycodes::Right),
-            115 => Ok(Keycodes::End),
-            116 => Ok(Keycodes::Down),
-            117 => Ok(Keycodes::Page_Down),
-            118 => Ok(Keycodes::Insert),
-            119 => Ok(Keycodes::Delete),
-            125 => Ok(Keycodes::KP_Equal),
-            126 => Ok(Keycodes::plusminus),
-            127 => Ok(Keycodes::Pause),
-            129 => Ok(Keycodes::KP_Decimal),
-            133 => Ok(Keycodes::Super_L),
-            134 => Ok(Keycodes::Super_R),
-            135 => Ok(Keycodes::Menu),
-            136 => Ok(Keycodes::Cancel),
-            137 => Ok(Keycodes::Redo),
-            139 => Ok(Keycodes::Undo),
-            144 => Ok(Keycodes::Find),
-            146 => Ok(Keycodes::Help),
-            _ => Err(()),
-        }
-    }
-}
-
-impl TryFrom<&str> for Keycodes {
-    type Error = ();
-
-    fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let lower_case_value = value.to_lowercase();
-        match lower_case_value.as_str() {
-            "escape" => Ok(Keycodes::Escape),
-            "1" => Ok(Keycodes::Num_1),
-            "2" => Ok(Keycodes::Num_2),
-            "3" => Ok(Keycodes::Num_3),
-            "4" => Ok(Keycodes::Num_4),
-            "5" => Ok(Keycodes::Num_5),
-            "6" => Ok(Keycodes::Num_6),
-            "7" => Ok(Keycodes::Num_7),
-            "8" => Ok(Keycodes::Num_8),
-            "9" => Ok(Keycodes::Num_9),
-            "0" => Ok(Keycodes::Num_0),
-            "minus" => Ok(Keycodes::minus),
-            "equal" => Ok(Keycodes::equal),
-            "backspace" => Ok(Keycodes::BackSpace),
-            "tab" => Ok(Keycodes::Tab),
-            "q" => Ok(Keycodes::q),
-            "w" => Ok(Keycodes::w),
-            "e" => Ok(Keycodes::e),
-            "r" => Ok(Keycodes::r),
-            "t" => Ok(Keycodes::t),
-            "y" => Ok(Keycodes::y),
-            "u" => Ok(Keycodes::u),
-            "i" => Ok(Keycodes::i),
-            "o" => Ok(Keycodes::o),
-            "p" => Ok(Keycodes::p),
-            "bracketleft" => Ok(Keycodes::bracketleft),
-            "bracketright" => Ok(Keycodes::bracketright),
-            "return" => Ok(Keycodes::Return),
-            "enter" => Ok(Keycodes::Return),
-            "control_l" => Ok(Keycodes::Control_L),
-            "control" => Ok(Keycodes::Control_L),
-            "ctrl" => Ok(Keycodes::Control_L),
-            "a" => Ok(Keycodes::a),
-            "s" => Ok(Keycodes::s),
-            "d" => Ok(Keycodes::d),
-            "f" => Ok(Keycodes::f),
-            "g" => Ok(Keycodes::g),
-            "h" => Ok(Keycodes::h),
-            "j" => Ok(Keycodes::j),
-            "k" => Ok(Keycodes::k),
-            "l" => Ok(Keycodes::l),
-            "semicolon" => Ok(Keycodes::semicolon),
-            "apostrophe" => Ok(Keycodes::apostrophe),
-            "shift_l" => Ok(Keycodes::Shift_L),
-            "shift" => Ok(Keycodes::Shift_L),
-            "backslash" => Ok(Keycodes::backslash),
-            "z" => Ok(Keycodes::z),
-            "x" => Ok(Keycodes::x),
-            "c" => Ok(Keycodes::c),
-            "v" => Ok(Keycodes::v),
-            "b" => Ok(Keycodes::b),
-            "n" => Ok(Keycodes::n),
-            "m" => Ok(Keycodes::m),
-            "comma" => Ok(Keycodes::comma),
-            "period" => Ok(Keycodes::period),
-            "slash" => Ok(Keycodes::slash),
-            "shift_r" => Ok(Keycodes::Shift_R),
-            "alt_l" => Ok(Keycodes::Alt_L),
-            "alt" => Ok(Keycodes::Alt_L),
-            "space" => Ok(Keycodes::space),
-            "f1" => Ok(Keycodes::F1),
-            "f2" => Ok(Keycodes::F2),
-            "f3" => Ok(Keycodes::F3),
-            "f4" => Ok(Keycodes::F4),
-            "f5" => Ok(Keycodes::F5),
-            "f6" => Ok(Keycodes::F6),
-            "f7" => Ok(Keycodes::F7),
-            "f8" => Ok(Keycodes::F8),
-            "f9" => Ok(Keycodes::F9),
-            "f10" => Ok(Keycodes::F10),
-            "kp_home" => Ok(Keycodes::KP_Home),
-            "kp_up" => Ok(Keycodes::KP_Up),
-            "kp_prior" => Ok(Keycodes::KP_Prior),
-            "kp_subtract" => Ok(Keycodes::KP_Subtract),
-            "kp_left" => Ok(Keycodes::KP_Left),
-            "kp_begin" => Ok(Keycodes::KP_Begin),
-            "kp_right" => Ok(Keycodes::KP_Right),
-            "kp_add" => Ok(Keycodes::KP_Add),
-            "kp_end" => Ok(Keycodes::KP_End),
-            "kp_down" => Ok(Keycodes::KP_Down),
-            "kp_next" => Ok(Keycodes::KP_Next),
-            "kp_insert" => Ok(Keycodes::KP_Insert),
-            "kp_delete" => Ok(Keycodes::KP_Delete),
-            "less" => Ok(Keycodes::less),
-            "f11" => Ok(Keycodes::F11),
-            "f12" => Ok(Keycodes::F12),
-            "kp_enter" => Ok(Keycodes::KP_Enter),
-            "control_r" => Ok(Keycodes::Control_R),
-            "kp_divide" => Ok(Keycodes::KP_Divide),
-            "print" => Ok(Keycodes::Print),
-            "alt_r" => Ok(Keycodes::Alt_R),
-            "home" => Ok(Keycodes::Home),
-            "up" => Ok(Keycodes::Up),
-            "page_up" => Ok(Keycodes::Page_Up),
-            "left" => Ok(Keycodes::Left),
-            "right" => Ok(Keycodes::Right),
-            "end" => Ok(Keycodes::End),
-            "down" => Ok(Keycodes::Down),
-            "page_down" => Ok(Keycodes::Page_Down),
-            "insert" => Ok(Keycodes::Insert),
-            "delete" => Ok(Keycodes::Delete),
-            "kp_equal" => Ok(Keycodes::KP_Equal),
-            "plusminus" => Ok(Keycodes::plusminus),
-            "pause" => Ok(Keycodes::Pause),
-            "kp_decimal" => Ok(Keycodes::KP_Decimal),
-            "super_l" => Ok(Keycodes::Super_L),
-            "super" => Ok(Keycodes::Super_L),
-            "super_r" => Ok(Keycodes::Super_R),
-            "menu" => Ok(Keycodes::Menu),
-            "cancel" => Ok(Keycodes::Cancel),
-            "redo" => Ok(Keycodes::Redo),
-            "undo" => Ok(Keycodes::Undo),
-            "find" => Ok(Keycodes::Find),
-            "help" => Ok(Keycodes::Help),
-            _ => Err(()),
-        }
-    }
-}
-
-impl TryFrom<Keycodes> for xcb_mod_mask_t {
-    type Error = ();
-
-    fn try_from(value: Keycodes) -> Result<Self, Self::Error> {
-        match value {
-            Keycodes::Shift_L | Keycodes::Shift_R => Ok(XCB_MOD_MASK_SHIFT),
-            Keycodes::Control_L | Keycodes::Control_R => Ok(XCB_MOD_MASK_CONTROL),
-            Keycodes::Alt_L | Keycodes::Alt_R => Ok(XCB_MOD_MASK_1),
-            Keycodes::Super_L | Keycodes::Super_R => Ok(XCB_MOD_MASK_4),
-            _ => Err(()),
-        }
+use crate::{
+    bar_message::{Message, UnixClients},
+    config::{BindEntry, Config, validate_workspace_name},
+    monitor::Monitor,
+};
+
+/// Mode made of the top level `keybindings` of the config, active on startup
+pub const DEFAULT_MODE: &str = "default";
+
+/// How long a started chord waits for its next key unless `chord_timeout_ms` is configured
+pub const DEFAULT_CHORD_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// Modifier names accepted before the key in a binding, e.g. `Alt+Shift+q`
+pub fn modifier_from_str(modifier_str: &str) -> Option<xcb_mod_mask_t> {
+    match modifier_str.to_lowercase().as_str() {
+        "shift" | "shift_l" | "shift_r" => Some(XCB_MOD_MASK_SHIFT),
+        "ctrl" | "control" | "control_l" | "control_r" => Some(XCB_MOD_MASK_CONTROL),
+        "alt" | "alt_l" | "alt_r" | "mod1" => Some(XCB_MOD_MASK_1),
+        "super" | "super_l" | "super_r" | "mod4" => Some(XCB_MOD_MASK_4),
+        _ => None,
+    }
+}
+
+/// Key part of a binding is a keysym name like `q`, `Return` or `XF86AudioMute`, looked up
+/// against the live keymap so it follows the active layout instead of US keycodes
+fn keysym_from_str(keysym_str: &str) -> Option<xkb_keysym_t> {
+    // names kept from the time bindings were written as keycodes
+    let keysym_str = match keysym_str.to_lowercase().as_str() {
+        "enter" => "Return",
+        "page_up" => "Prior",
+        "page_down" => "Next",
+        // modifier names used as the key itself bind the left one, e.g. `Super` tap
+        "shift" => "Shift_L",
+        "ctrl" | "control" => "Control_L",
+        "alt" => "Alt_L",
+        "super" => "Super_L",
+        _ => keysym_str,
+    };
+    connection::keysym_from_name(keysym_str)
+}
+
+/// Parses one key of a binding, e.g. `Alt+Shift+q`, into modifiers, their count and the keysym
+fn key_from_str(key_str: &str) -> Result<(xcb_mod_mask_t, usize, xkb_keysym_t), String> {
+    let mut modifiers: xcb_mod_mask_t = 0;
+    let mut modifiers_count: usize = 0;
+
+    let keys_comb = key_str.split('+').collect::<Vec<_>>();
+    let count = &keys_comb.len();
+    for mod_str in keys_comb.iter().take(count - 1) {
+        let modifier = modifier_from_str(mod_str)
+            .ok_or_else(|| format!("failed to extract modifier from string: {}", mod_str))?;
+        modifiers |= modifier;
+        modifiers_count += 1;
     }
+
+    let keysym_str = keys_comb.last().unwrap();
+    let keysym = keysym_from_str(keysym_str)
+        .ok_or_else(|| format!("failed to extract keysym from string: {}", keysym_str))?;
+    Ok((modifiers, modifiers_count, keysym))
+}
+
+/// Pressing modifiers of the next key in a chord must not cancel it, alone they are bound as taps
+fn is_modifier_keysym(keysym: xkb_keysym_t) -> bool {
+    (XKB_KEY_Shift_L..=XKB_KEY_Hyper_R).contains(&keysym)
+        || (XKB_KEY_ISO_Level3_Shift..=XKB_KEY_ISO_Level5_Shift).contains(&keysym)
+        || keysym == XKB_KEY_Mode_switch
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -403,17 +93,56 @@ pub enum Dimension {
     Vertical,
 }
 
+/// Workspace given by its id or by a name from `workspace_names` or `rename_workspace`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum WorkspaceTarget {
+    Id(u32),
+    Name(String),
+}
+
+impl WorkspaceTarget {
+    fn from_str(workspace_str: &str) -> Self {
+        match workspace_str.parse::<u32>() {
+            Ok(workspace_id) => WorkspaceTarget::Id(workspace_id),
+            Err(_) => WorkspaceTarget::Name(workspace_str.to_owned()),
+        }
+    }
+}
+
 #[allow(dead_code)]
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum KeybindingAction {
     Exec(String),
     FocusWindow(Direction),
     MoveWindow(Direction),
     ResizeWindow(Dimension, i32),
-    SwitchToWorkspace(u32),
-    MoveFocusedWindowToWorkspace(u32),
+    /// snaps the focused column to the next or previous of `preset_column_widths`
+    CycleWidthNext,
+    CycleWidthPrev,
+    /// the focused column takes the whole available width, or gets its width back
+    MaximizeColumn,
+    /// the focused window covers the whole monitor above the docks, or gets its rect back
+    ToggleFullscreen,
+    SwitchToWorkspace(WorkspaceTarget),
+    SwitchToNextWorkspace,
+    SwitchToPrevWorkspace,
+    /// the previously focused workspace
+    SwitchToWorkspaceBackAndForth,
+    MoveFocusedWindowToWorkspace(WorkspaceTarget),
+    /// names the focused workspace, `None` removes its name
+    RenameWorkspace(Option<String>),
     KillFocusedWindow,
     CenterFocusedWindow,
+    ToggleFloating,
+    ScrollStrip(Direction, i32),
+    /// stacks the focused window at the bottom of the column on its left or right
+    ConsumeWindow(Direction),
+    /// moves the focused window out of its column into a new one on the right
+    ExpelWindow,
+    EnterMode(String),
+    /// most recently used windows of all workspaces, kept while the modifier is held
+    CycleWindowsNext,
+    CycleWindowsPrev,
 }
 
 #[allow(dead_code)]
@@ -421,10 +150,158 @@ pub enum KeybindingAction {
 pub struct Keybinding {
     pub modifiers: xcb_mod_mask_t,
     pub modifiers_count: usize,
-    pub keycode: Keycodes,
+    pub keysym: xkb_keysym_t,
+    /// keycodes producing `keysym` in the current keymap, re-resolved when the mapping changes
+    pub keycodes: Vec<xcb_keycode_t>,
+    /// keys to be pressed after the grabbed one, e.g. `3` in `Alt+W,3`, empty for plain bindings
+    pub chord: Vec<ChordKey>,
+    /// triggered on key release, bindings of a bare modifier are always release ones and fire
+    /// only if no other key was pressed while the modifier was held
+    pub release: bool,
     pub action: KeybindingAction,
 }
 
+impl Keybinding {
+    fn matches(&self, modifiers: xcb_mod_mask_t, keycode: xcb_keycode_t) -> bool {
+        (modifiers & self.modifiers) == self.modifiers && self.keycodes.contains(&keycode)
+    }
+}
+
+/// Follow-up key of a chord, only looked at while the keyboard is grabbed for the chord
+#[derive(Debug, PartialEq, Eq)]
+pub struct ChordKey {
+    pub modifiers: xcb_mod_mask_t,
+    pub keysym: xkb_keysym_t,
+    pub keycodes: Vec<xcb_keycode_t>,
+}
+
+impl ChordKey {
+    fn matches(&self, modifiers: xcb_mod_mask_t, keycode: xcb_keycode_t) -> bool {
+        (modifiers & self.modifiers) == self.modifiers && self.keycodes.contains(&keycode)
+    }
+}
+
+/// Chord with some of its keys pressed, waiting for the next one
+#[derive(Debug, PartialEq, Eq)]
+struct PendingChord {
+    /// indices of bindings in the active mode that still match
+    candidates: Vec<usize>,
+    /// how many keys of `chord` are already pressed
+    matched: usize,
+    /// last pressed key, its autorepeat is ignored
+    keycode: xcb_keycode_t,
+}
+
+/// Binding tables of all modes, only the active one is grabbed
+#[derive(Debug, PartialEq, Eq)]
+pub struct Keybindings {
+    modes: HashMap<String, Vec<Keybinding>>,
+    active_mode: String,
+    pending_chord: Option<PendingChord>,
+    /// modifier pressed last with no other key after it, candidate for a modifier tap binding
+    tap_keycode: Option<xcb_keycode_t>,
+}
+
+impl Keybindings {
+    /// While pending the keyboard is grabbed, the caller arms a timeout calling `cancel_chord`
+    pub fn is_chord_pending(&self) -> bool {
+        self.pending_chord.is_some()
+    }
+
+    pub fn cancel_chord(&mut self, conn: &Connection) {
+        if self.pending_chord.take().is_some() {
+            trace!("chord cancelled");
+            conn.ungrab_keyboard();
+        }
+    }
+
+    fn start_chord(&mut self, candidates: Vec<usize>, keycode: xcb_keycode_t, conn: &Connection) {
+        if let Err(err) = conn.grab_keyboard() {
+            warn!("failed to grab keyboard for chord, err: {:?}", err);
+            return;
+        }
+        self.pending_chord = Some(PendingChord {
+            candidates,
+            matched: 0,
+            keycode,
+        });
+    }
+
+    /// Advances the pending chord, returns action of the binding it completed
+    fn continue_chord(
+        &mut self,
+        conn: &Connection,
+        modifiers: xcb_mod_mask_t,
+        keycode: xcb_keycode_t,
+    ) -> Option<KeybindingAction> {
+        let pending = self.pending_chord.take()?;
+        let active = self.active();
+        let next = pending
+            .candidates
+            .iter()
+            .copied()
+            .filter(|&index| active[index].chord[pending.matched].matches(modifiers, keycode))
+            .collect::<Vec<_>>();
+        if next.is_empty() {
+            let keysym = conn.xkb_keysym_for_keycode(keycode);
+            if keycode == pending.keycode || is_modifier_keysym(keysym) {
+                self.pending_chord = Some(pending);
+                return None;
+            }
+            if keysym != XKB_KEY_Escape {
+                trace!("no chord continues with keycode {}", keycode);
+            }
+            conn.ungrab_keyboard();
+            return None;
+        }
+        let matched = pending.matched + 1;
+        if let Some(&index) = next
+            .iter()
+            .find(|&&index| active[index].chord.len() == matched)
+        {
+            conn.ungrab_keyboard();
+            return Some(active[index].action.clone());
+        }
+        self.pending_chord = Some(PendingChord {
+            candidates: next,
+            matched,
+            keycode,
+        });
+        None
+    }
+
+    pub fn active_mode(&self) -> &str {
+        &self.active_mode
+    }
+
+    fn active(&self) -> &[Keybinding] {
+        self.modes
+            .get(&self.active_mode)
+            .map_or(&[], |keybindings| keybindings.as_slice())
+    }
+
+    /// Swaps grabbed keys for the ones of `mode` and tells IPC clients about it
+    pub fn enter_mode(
+        &mut self,
+        mode: &str,
+        conn: &Connection,
+        unix_clients: &mut UnixClients,
+    ) -> Result<(), String> {
+        if !self.modes.contains_key(mode) {
+            return Err(format!("no binding mode named: {}", mode));
+        }
+        if self.active_mode == mode {
+            return Ok(());
+        }
+        keybindings_ungrab(self, conn);
+        self.active_mode = mode.to_owned();
+        keybindings_grab(self, conn);
+        trace!("entered binding mode: {}", mode);
+        unix_clients.notify_all(Message::ModeChanged(mode));
+        Ok(())
+    }
+}
+
 pub fn execute_command_from_str(cmd_str: &str) {
     if cmd_str.contains(" && ") {
         let parts = cmd_str.split(" && ").collect::<Vec<_>>();
@@ -472,8 +349,9 @@ pub fn execute_command_from_str_wait(cmd_str: &str) {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn handle_key_press(
-    keybindings: &Vec<Keybinding>,
+    keybindings: &mut Keybindings,
     conn: &Connection,
     config: &Config,
     monitor: &mut Monitor,
@@ -486,365 +364,650 @@ pub fn handle_key_press(
         modifier, keycode
     );
     let modifiers = modifier as u32 & !(XCB_MOD_MASK_2 | XCB_MOD_MASK_LOCK);
-    if modifiers == 0 {
-        error!("modifiers == 0");
+    if monitor.is_window_switcher_active() && conn.xkb_keysym_for_keycode(keycode) == XKB_KEY_Escape
+    {
+        monitor.cancel_window_switcher(conn, config, unix_clients);
+        conn.ungrab_keyboard();
         return;
     }
-    let keycode = keycode as u8;
-    for keybinding in keybindings {
-        if (modifiers & keybinding.modifiers) == keybinding.modifiers
-            && keycode == keybinding.keycode as u8
+    keybindings.tap_keycode =
+        is_modifier_keysym(conn.xkb_keysym_for_keycode(keycode)).then_some(keycode);
+    let action = if keybindings.is_chord_pending() {
+        keybindings.continue_chord(conn, modifiers, keycode)
+    } else {
+        // bindings without modifiers are only grabbed in modes, so a bare key press is fine here,
+        // plain bindings win over chords starting with the same key
+        let active = keybindings.active();
+        let action = active
+            .iter()
+            .find(|keybinding| {
+                !keybinding.release
+                    && keybinding.chord.is_empty()
+                    && keybinding.matches(modifiers, keycode)
+            })
+            .map(|keybinding| keybinding.action.clone());
+        if action.is_none() {
+            let candidates = active
+                .iter()
+                .enumerate()
+                .filter(|(_, keybinding)| {
+                    !keybinding.release
+                        && !keybinding.chord.is_empty()
+                        && keybinding.matches(modifiers, keycode)
+                })
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>();
+            if !candidates.is_empty() {
+                keybindings.start_chord(candidates, keycode, conn);
+            }
+        }
+        action
+    };
+    if let Some(action) = action {
+        if let Err(err) =
+            execute_keybinding_action(&action, keybindings, conn, config, monitor, unix_clients)
         {
-            match &keybinding.action {
-                KeybindingAction::Exec(cmd) => {
-                    execute_command_from_str(cmd.as_str());
-                }
-                KeybindingAction::FocusWindow(direction) => {
-                    monitor.handle_focus_window_change(conn, config, *direction, unix_clients);
-                }
-                KeybindingAction::MoveWindow(direction) => {
-                    monitor.handle_move_window(conn, config, *direction, unix_clients);
-                }
-                KeybindingAction::ResizeWindow(dimension, size_change_pixels) => {
-                    monitor.handle_resize_window(conn, config, *dimension, *size_change_pixels);
-                }
-                KeybindingAction::SwitchToWorkspace(workspace_id) => {
-                    monitor.handle_switch_to_workspace(conn, config, *workspace_id, unix_clients);
-                }
-                KeybindingAction::MoveFocusedWindowToWorkspace(workspace_id) => {
-                    monitor.handle_move_focused_window_to_workspace(
-                        conn,
-                        config,
-                        *workspace_id,
-                        config.switch_to_workspace_on_focused_window_moved,
-                        unix_clients,
-                    );
-                }
-                KeybindingAction::KillFocusedWindow => {
-                    monitor.handle_kill_focused_window(conn, config);
+            error!("{}", err);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn handle_key_release(
+    keybindings: &mut Keybindings,
+    conn: &Connection,
+    config: &Config,
+    monitor: &mut Monitor,
+    modifier: u16,
+    keycode: xcb_keycode_t,
+    unix_clients: &mut UnixClients,
+) {
+    trace!(
+        "handle key release, modifier: {}, keycode: {}",
+        modifier, keycode
+    );
+    let tapped = keybindings.tap_keycode.take() == Some(keycode);
+    if monitor.is_window_switcher_active() {
+        // Shift only changes the direction, e.g. Alt+Shift+Tab
+        let keysym = conn.xkb_keysym_for_keycode(keycode);
+        if is_modifier_keysym(keysym) && keysym != XKB_KEY_Shift_L && keysym != XKB_KEY_Shift_R {
+            monitor.commit_window_switcher();
+            conn.ungrab_keyboard();
+        }
+        return;
+    }
+    if keybindings.is_chord_pending() {
+        return;
+    }
+    let modifiers = modifier as u32 & !(XCB_MOD_MASK_2 | XCB_MOD_MASK_LOCK);
+    let action = keybindings
+        .active()
+        .iter()
+        .find(|keybinding| {
+            keybinding.release
+                && keybinding.matches(modifiers, keycode)
+                && (tapped || !is_modifier_keysym(keybinding.keysym))
+        })
+        .map(|keybinding| keybinding.action.clone());
+    if let Some(action) = action {
+        if let Err(err) =
+            execute_keybinding_action(&action, keybindings, conn, config, monitor, unix_clients)
+        {
+            error!("{}", err);
+        }
+    }
+}
+
+pub fn execute_keybinding_action(
+    action: &KeybindingAction,
+    keybindings: &mut Keybindings,
+    conn: &Connection,
+    config: &Config,
+    monitor: &mut Monitor,
+    unix_clients: &mut UnixClients,
+) -> Result<(), String> {
+    match action {
+        KeybindingAction::Exec(cmd) => {
+            execute_command_from_str(cmd.as_str());
+        }
+        KeybindingAction::FocusWindow(direction) => {
+            monitor.handle_focus_window_change(conn, config, *direction, unix_clients);
+        }
+        KeybindingAction::MoveWindow(direction) => {
+            monitor.handle_move_window(conn, config, *direction, unix_clients);
+        }
+        KeybindingAction::ResizeWindow(dimension, size_change_pixels) => {
+            monitor.handle_resize_window(conn, config, *dimension, *size_change_pixels);
+        }
+        KeybindingAction::CycleWidthNext => {
+            monitor.handle_cycle_column_width(conn, config, true);
+        }
+        KeybindingAction::CycleWidthPrev => {
+            monitor.handle_cycle_column_width(conn, config, false);
+        }
+        KeybindingAction::MaximizeColumn => {
+            monitor.handle_maximize_column(conn, config);
+        }
+        KeybindingAction::ToggleFullscreen => {
+            monitor.handle_toggle_fullscreen(conn, config);
+        }
+        KeybindingAction::SwitchToWorkspace(workspace_target) => {
+            let workspace_id = monitor.workspace_id_from_target(workspace_target)?;
+            if config.workspace_auto_back_and_forth
+                && monitor.get_focused_workspace_id() == Some(workspace_id)
+            {
+                monitor.handle_switch_to_workspace_back_and_forth(conn, config, unix_clients);
+            } else {
+                monitor.handle_switch_to_workspace(conn, config, workspace_id, unix_clients);
+            }
+        }
+        KeybindingAction::SwitchToNextWorkspace => {
+            monitor.handle_switch_to_next_workspace(conn, config, unix_clients);
+        }
+        KeybindingAction::SwitchToPrevWorkspace => {
+            monitor.handle_switch_to_prev_workspace(conn, config, unix_clients);
+        }
+        KeybindingAction::SwitchToWorkspaceBackAndForth => {
+            monitor.handle_switch_to_workspace_back_and_forth(conn, config, unix_clients);
+        }
+        KeybindingAction::MoveFocusedWindowToWorkspace(workspace_target) => {
+            let workspace_id = monitor.workspace_id_from_target(workspace_target)?;
+            monitor.handle_move_focused_window_to_workspace(
+                conn,
+                config,
+                workspace_id,
+                config.switch_to_workspace_on_focused_window_moved,
+                unix_clients,
+            );
+        }
+        KeybindingAction::KillFocusedWindow => {
+            monitor.handle_kill_focused_window(conn, config);
+        }
+        KeybindingAction::CenterFocusedWindow => {
+            monitor.center_focused_window(conn, config);
+        }
+        KeybindingAction::ToggleFloating => {
+            monitor.handle_toggle_floating(conn, config);
+        }
+        KeybindingAction::ScrollStrip(direction, pixels) => {
+            monitor.handle_scroll_strip(conn, config, *direction, *pixels);
+        }
+        KeybindingAction::ConsumeWindow(direction) => {
+            monitor.handle_consume_window(conn, config, *direction);
+        }
+        KeybindingAction::ExpelWindow => {
+            monitor.handle_expel_window(conn, config);
+        }
+        KeybindingAction::RenameWorkspace(name) => {
+            return monitor.handle_rename_workspace(conn, name.clone(), unix_clients);
+        }
+        KeybindingAction::EnterMode(mode) => {
+            return keybindings.enter_mode(mode, conn, unix_clients);
+        }
+        KeybindingAction::CycleWindowsNext | KeybindingAction::CycleWindowsPrev => {
+            if !monitor.is_window_switcher_active() {
+                // grabbed until the modifier is released, the binding's key grab ends on its release
+                if let Err(err) = conn.grab_keyboard() {
+                    return Err(format!(
+                        "failed to grab keyboard for window switcher, err: {:?}",
+                        err
+                    ));
                 }
-                KeybindingAction::CenterFocusedWindow => {
-                    monitor.center_focused_window(conn, config);
+                if !monitor.start_window_switcher() {
+                    conn.ungrab_keyboard();
+                    return Ok(());
                 }
-            };
-            break;
+            }
+            let forward = *action == KeybindingAction::CycleWindowsNext;
+            monitor.handle_cycle_windows(conn, config, forward, unix_clients);
+        }
+    };
+    Ok(())
+}
+
+pub fn keybindings_grab(keybindings: &Keybindings, conn: &Connection) {
+    for keybinding in keybindings.active() {
+        for keycode in &keybinding.keycodes {
+            conn.grab_key(keybinding.modifiers, *keycode);
+            conn.grab_key(keybinding.modifiers | XCB_MOD_MASK_2, *keycode);
+            conn.grab_key(keybinding.modifiers | XCB_MOD_MASK_LOCK, *keycode);
+            conn.grab_key(
+                keybinding.modifiers | XCB_MOD_MASK_2 | XCB_MOD_MASK_LOCK,
+                *keycode,
+            );
         }
     }
 }
 
-pub fn keybindings_grab(keybindings: &Vec<Keybinding>, conn: &Connection) {
-    for keybinding in keybindings {
-        conn.grab_key(keybinding.modifiers, keybinding.keycode as u8);
-        conn.grab_key(
-            keybinding.modifiers | XCB_MOD_MASK_2,
-            keybinding.keycode as u8,
-        );
-        conn.grab_key(
-            keybinding.modifiers | XCB_MOD_MASK_LOCK,
-            keybinding.keycode as u8,
-        );
-        conn.grab_key(
-            keybinding.modifiers | XCB_MOD_MASK_2 | XCB_MOD_MASK_LOCK,
-            keybinding.keycode as u8,
-        );
+pub fn keybindings_ungrab(keybindings: &Keybindings, conn: &Connection) {
+    for keybinding in keybindings.active() {
+        for keycode in &keybinding.keycodes {
+            conn.ungrab_key(keybinding.modifiers, *keycode);
+            conn.ungrab_key(keybinding.modifiers | XCB_MOD_MASK_2, *keycode);
+            conn.ungrab_key(keybinding.modifiers | XCB_MOD_MASK_LOCK, *keycode);
+            conn.ungrab_key(
+                keybinding.modifiers | XCB_MOD_MASK_2 | XCB_MOD_MASK_LOCK,
+                *keycode,
+            );
+        }
     }
 }
 
-pub fn keybindings_ungrab(keybindings: &Vec<Keybinding>, conn: &Connection) {
-    for keybinding in keybindings {
-        conn.ungrab_key(keybinding.modifiers, keybinding.keycode as u8);
-        conn.ungrab_key(
-            keybinding.modifiers | XCB_MOD_MASK_2,
-            keybinding.keycode as u8,
-        );
-        conn.ungrab_key(
-            keybinding.modifiers | XCB_MOD_MASK_LOCK,
-            keybinding.keycode as u8,
-        );
-        conn.ungrab_key(
-            keybinding.modifiers | XCB_MOD_MASK_2 | XCB_MOD_MASK_LOCK,
-            keybinding.keycode as u8,
-        );
+pub fn keybindings_from_config(config: &Config, conn: &Connection) -> Keybindings {
+    let mut modes = HashMap::new();
+    modes.insert(
+        DEFAULT_MODE.to_owned(),
+        keybindings_from_strings(&config.keybindings),
+    );
+    for (mode, keybinding_strs) in config.modes.iter().flatten() {
+        if mode == DEFAULT_MODE {
+            error!(
+                "mode '{}' is made of top level keybindings, ignoring its table in modes",
+                DEFAULT_MODE
+            );
+            continue;
+        }
+        modes.insert(mode.clone(), keybindings_from_strings(keybinding_strs));
     }
+    // entries were validated when the config was loaded
+    for bind in config.bind.iter().flatten() {
+        match keybinding_from_bind(bind) {
+            Ok(keybinding) => modes
+                .entry(bind.mode.clone().unwrap_or_else(|| DEFAULT_MODE.to_owned()))
+                .or_default()
+                .push(keybinding),
+            Err(err) => error!("{}, [[bind]] keys: {}", err, bind.keys),
+        }
+    }
+    for (mode, keybindings) in modes.iter_mut() {
+        keybindings.sort_by_key(|e| Reverse(e.modifiers_count));
+        // Escape always leads back unless the mode binds it to something else
+        if mode != DEFAULT_MODE
+            && !keybindings
+                .iter()
+                .any(|keybinding| keybinding.keysym == XKB_KEY_Escape && keybinding.modifiers == 0)
+        {
+            keybindings.push(Keybinding {
+                modifiers: 0,
+                modifiers_count: 0,
+                keysym: XKB_KEY_Escape,
+                keycodes: Vec::new(),
+                chord: Vec::new(),
+                release: false,
+                action: KeybindingAction::EnterMode(DEFAULT_MODE.to_owned()),
+            });
+        }
+    }
+    for keybinding in modes.values().flatten() {
+        if let KeybindingAction::EnterMode(mode) = &keybinding.action {
+            if !modes.contains_key(mode) {
+                error!("keybinding enters mode '{}' which is not defined", mode);
+            }
+        }
+    }
+    let mut keybindings = Keybindings {
+        modes,
+        active_mode: DEFAULT_MODE.to_owned(),
+        pending_chord: None,
+        tap_keycode: None,
+    };
+    keybindings_resolve_keycodes(&mut keybindings, conn);
+
+    keybindings
 }
 
-pub fn keybindings_from_config(config: &Config) -> Vec<Keybinding> {
+fn keybindings_from_strings(keybinding_strs: &[String]) -> Vec<Keybinding> {
     let mut keybindings: Vec<Keybinding> = Vec::new();
-    keybindings.reserve(config.keybindings.len());
+    keybindings.reserve(keybinding_strs.len());
 
-    for keybinding_str in &config.keybindings {
+    for keybinding_str in keybinding_strs {
         if let Some(keybinding) = keybinding_from_string(keybinding_str) {
             keybindings.push(keybinding);
         }
     }
-    keybindings.sort_by_key(|e| Reverse(e.modifiers_count));
 
     keybindings
 }
 
-fn keybinding_from_string(keybinding_str: &str) -> Option<Keybinding> {
-    let mut modifiers: xcb_mod_mask_t = 0;
-    let mut modifiers_count: usize = 0;
+/// Looks keysyms up in the current keymap, call after the keyboard mapping changed with the
+/// keybindings ungrabbed
+pub fn keybindings_resolve_keycodes(keybindings: &mut Keybindings, conn: &Connection) {
+    for keybinding in keybindings.modes.values_mut().flatten() {
+        keybinding.keycodes = conn.xkb_keycodes_for_keysym(keybinding.keysym);
+        for chord_key in &mut keybinding.chord {
+            chord_key.keycodes = conn.xkb_keycodes_for_keysym(chord_key.keysym);
+        }
+        let unresolved = std::iter::once(&keybinding.keycodes)
+            .chain(keybinding.chord.iter().map(|chord_key| &chord_key.keycodes))
+            .any(|keycodes| keycodes.is_empty());
+        if unresolved {
+            warn!(
+                "keysym of keybinding isn't produced by any key in the current keymap, keybinding action: {:?}",
+                keybinding.action
+            );
+        }
+    }
+}
 
-    let mut parts = keybinding_str.trim().split_whitespace();
-    if let Some(keys_comb_str) = parts.next() {
-        let keys_comb = keys_comb_str.split('+').collect::<Vec<_>>();
-        let count = &keys_comb.len();
-        for mod_str in keys_comb.iter().take(count - 1) {
-            if let Ok(mod_keycode) = Keycodes::try_from(*mod_str) {
-                if let Ok(modifier) = xcb_mod_mask_t::try_from(mod_keycode) {
-                    modifiers |= modifier;
-                    modifiers_count += 1;
-                } else {
-                    error!("failed to extract modifier from keycode: {:?}", mod_keycode);
-                    return None;
-                }
-            } else {
-                error!(
-                    "failed to extract keycode modifier from string: {}",
-                    mod_str
-                );
-                return None;
-            }
+/// Keys of a chord are separated by commas, e.g. `Alt+W,3 switch_to_workspace 3`, bindings
+/// prefixed with `--release` trigger when the key goes up, e.g. `--release Print exec scrot`
+fn keybinding_from_string(keybinding_str: &str) -> Option<Keybinding> {
+    let keybinding_str = keybinding_str.trim();
+    let (release, keybinding_str) = match keybinding_str.strip_prefix("--release") {
+        Some(rest) if rest.starts_with(char::is_whitespace) => (true, rest.trim_start()),
+        _ => (false, keybinding_str),
+    };
+    let (keys_comb_str, action_str) = keybinding_str
+        .split_once(char::is_whitespace)
+        .unwrap_or((keybinding_str, ""));
+    if keys_comb_str.is_empty() {
+        return None;
+    }
+    match keybinding_from_parts(keys_comb_str, action_str, release) {
+        Ok(keybinding) => Some(keybinding),
+        Err(err) => {
+            error!("{}, keybinding: {}", err, keybinding_str);
+            None
         }
+    }
+}
 
-        let keycode_str = keys_comb.last().unwrap();
-        let keycode_maybe = Keycodes::try_from(*keycode_str);
-        if keycode_maybe.is_err() {
-            error!("failed to extract keycode from string: {}", keycode_str);
-            return None;
+/// Same as the string form with the action and its arguments given separately, so
+/// `keys = "Alt+H", action = "focus_window", args = ["left"]` equals `Alt+H focus_window left`
+pub fn keybinding_from_bind(bind: &BindEntry) -> Result<Keybinding, String> {
+    let action_str = std::iter::once(bind.action.as_str())
+        .chain(bind.args.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" ");
+    keybinding_from_parts(bind.keys.trim(), &action_str, bind.release)
+}
+
+fn keybinding_from_parts(
+    keys_comb_str: &str,
+    action_str: &str,
+    release: bool,
+) -> Result<Keybinding, String> {
+    if keys_comb_str.is_empty() {
+        return Err("no keys provided".to_string());
+    }
+    let mut keys = keys_comb_str.split(',');
+    let (modifiers, modifiers_count, keysym) = key_from_str(keys.next().unwrap())?;
+    let mut chord = Vec::new();
+    for key_str in keys {
+        let (modifiers, _, keysym) = key_from_str(key_str)?;
+        chord.push(ChordKey {
+            modifiers,
+            keysym,
+            keycodes: Vec::new(),
+        });
+    }
+    if release && !chord.is_empty() {
+        return Err("chords can't be release bindings".to_string());
+    }
+    if action_str.trim().is_empty() {
+        return Err("failed to find command in keybinding".to_string());
+    }
+    // a modifier alone can only be told apart from its combinations once it is released
+    let release = release || (chord.is_empty() && is_modifier_keysym(keysym));
+    let action = keybinding_action_from_str(action_str)?;
+    Ok(Keybinding {
+        modifiers,
+        modifiers_count,
+        keysym,
+        keycodes: Vec::new(),
+        chord,
+        release,
+        action,
+    })
+}
+
+/// Parses the action part of a keybinding, e.g. `focus_window left` or `exec alacritty`.
+/// The same syntax is accepted for commands sent by IPC clients over the unix socket.
+pub fn keybinding_action_from_str(action_str: &str) -> Result<KeybindingAction, String> {
+    let mut parts = action_str.split_whitespace();
+    let command = match parts.next() {
+        Some(command) => command,
+        None => return Err("no command provided".to_string()),
+    };
+    match command {
+        "exec" => {
+            let command_parts = parts.collect::<Vec<_>>().join(" ");
+            if command_parts.is_empty() {
+                return Err(format!("no program provided for command: {}", command));
+            }
+            Ok(KeybindingAction::Exec(command_parts))
         }
-        if let Some(command) = parts.next() {
-            match command {
-                "exec" => {
-                    let command_parts = parts.collect::<Vec<_>>().join(" ");
-                    // trace!("command_parts: {}", command_parts);
-                    return Some(Keybinding {
-                        modifiers,
-                        modifiers_count,
-                        keycode: keycode_maybe.unwrap(),
-                        action: KeybindingAction::Exec(command_parts),
-                    });
-                }
-                "focus_window" => {
-                    if let Some(focus_change_direction) = parts.next() {
-                        match focus_change_direction {
-                            "left" => {
-                                return Some(Keybinding {
-                                    modifiers,
-                                    modifiers_count,
-                                    keycode: keycode_maybe.unwrap(),
-                                    action: KeybindingAction::FocusWindow(Direction::Left),
-                                });
-                            }
-                            "right" => {
-                                return Some(Keybinding {
-                                    modifiers,
-                                    modifiers_count,
-                                    keycode: keycode_maybe.unwrap(),
-                                    action: KeybindingAction::FocusWindow(Direction::Right),
-                                });
-                            }
-                            "up" => {
-                                return Some(Keybinding {
-                                    modifiers,
-                                    modifiers_count,
-                                    keycode: keycode_maybe.unwrap(),
-                                    action: KeybindingAction::FocusWindow(Direction::Up),
-                                });
-                            }
-                            "down" => {
-                                return Some(Keybinding {
-                                    modifiers,
-                                    modifiers_count,
-                                    keycode: keycode_maybe.unwrap(),
-                                    action: KeybindingAction::FocusWindow(Direction::Down),
-                                });
-                            }
-                            _ => {
-                                error!(
-                                    "unknown focus change direction name: {}",
-                                    focus_change_direction
-                                );
-                            }
-                        }
-                    } else {
-                        error!(
-                            "no direction supplied for focus window change command: {:?}",
-                            parts
-                        );
-                    }
+        "focus_window" => match parts.next() {
+            Some(focus_change_direction) => match direction_from_str(focus_change_direction) {
+                Some(direction) => Ok(KeybindingAction::FocusWindow(direction)),
+                None => Err(format!(
+                    "unknown focus change direction name: {}",
+                    focus_change_direction
+                )),
+            },
+            None => Err(format!(
+                "no direction supplied for focus window change command: {}",
+                command
+            )),
+        },
+        "move_window" => match parts.next() {
+            Some(move_window_direction) => match direction_from_str(move_window_direction) {
+                Some(direction) => Ok(KeybindingAction::MoveWindow(direction)),
+                None => Err(format!(
+                    "unknown move window direction name: {}",
+                    move_window_direction
+                )),
+            },
+            None => Err(format!(
+                "no direction supplied for move window command: {}",
+                command
+            )),
+        },
+        "window_size_change" => {
+            let resize_dimension = match parts.next() {
+                Some(resize_dimension) => resize_dimension,
+                None => {
+                    return Err(format!(
+                        "no dimension supplied for window size change command: {}",
+                        command
+                    ));
                 }
-                "move_window" => {
-                    if let Some(move_window_direction) = parts.next() {
-                        match move_window_direction {
-                            "left" => {
-                                return Some(Keybinding {
-                                    modifiers,
-                                    modifiers_count,
-                                    keycode: keycode_maybe.unwrap(),
-                                    action: KeybindingAction::MoveWindow(Direction::Left),
-                                });
-                            }
-                            "right" => {
-                                return Some(Keybinding {
-                                    modifiers,
-                                    modifiers_count,
-                                    keycode: keycode_maybe.unwrap(),
-                                    action: KeybindingAction::MoveWindow(Direction::Right),
-                                });
-                            }
-                            "up" => {
-                                return Some(Keybinding {
-                                    modifiers,
-                                    modifiers_count,
-                                    keycode: keycode_maybe.unwrap(),
-                                    action: KeybindingAction::MoveWindow(Direction::Up),
-                                });
-                            }
-                            "down" => {
-                                return Some(Keybinding {
-                                    modifiers,
-                                    modifiers_count,
-                                    keycode: keycode_maybe.unwrap(),
-                                    action: KeybindingAction::MoveWindow(Direction::Down),
-                                });
-                            }
-                            _ => {
-                                error!(
-                                    "unknown move window direction name: {}",
-                                    move_window_direction
-                                );
-                            }
-                        }
-                    } else {
-                        error!("no direction supplied for move window command: {:?}", parts);
-                    }
-                }
-                "window_size_change" => {
-                    if let Some(resize_dimension) = parts.next() {
-                        let maybe_size_change_str = parts.next();
-                        if maybe_size_change_str.is_none() {
-                            error!("no pixels in which size would be changed was specified");
-                            return None;
-                        }
-                        let maybe_size_change = maybe_size_change_str.unwrap().parse::<i32>();
-                        if maybe_size_change.is_err() {
-                            error!(
-                                "invalid size change pixels value: {}, error: {:?}",
-                                maybe_size_change_str.unwrap(),
-                                maybe_size_change.err()
-                            );
-                            return None;
-                        }
-                        let size_change_pixels = maybe_size_change.unwrap();
-                        match resize_dimension {
-                            "horizontal" => {
-                                return Some(Keybinding {
-                                    modifiers,
-                                    modifiers_count,
-                                    keycode: keycode_maybe.unwrap(),
-                                    action: KeybindingAction::ResizeWindow(
-                                        Dimension::Horizontal,
-                                        size_change_pixels,
-                                    ),
-                                });
-                            }
-                            "vertical" => {
-                                return Some(Keybinding {
-                                    modifiers,
-                                    modifiers_count,
-                                    keycode: keycode_maybe.unwrap(),
-                                    action: KeybindingAction::ResizeWindow(
-                                        Dimension::Vertical,
-                                        size_change_pixels,
-                                    ),
-                                });
-                            }
-                            _ => {
-                                error!("unknown resize dimension name: {}", resize_dimension);
-                            }
-                        }
-                    } else {
-                        error!(
-                            "no direction supplied for focus window change command: {:?}",
-                            parts
-                        );
-                    }
-                }
-                "switch_to_workspace" => {
-                    if let Some(workspace_id_str) = parts.next() {
-                        match workspace_id_str.parse::<u32>() {
-                            Ok(workspace_id) => {
-                                return Some(Keybinding {
-                                    modifiers,
-                                    modifiers_count,
-                                    keycode: keycode_maybe.unwrap(),
-                                    action: KeybindingAction::SwitchToWorkspace(workspace_id),
-                                });
-                            }
-                            Err(err) => {
-                                error!(
-                                    "invalid unsigned integer '{}' provided as a workspace id for switch to workspace command: {}, error: {:?}",
-                                    workspace_id_str, command, err
-                                );
-                            }
-                        }
-                    } else {
-                        error!(
-                            "no workspace id provided for switch to workspace command: {}",
-                            command
-                        )
-                    }
-                }
-                "move_focused_window_to_workspace" => {
-                    if let Some(workspace_id_str) = parts.next() {
-                        match workspace_id_str.parse::<u32>() {
-                            Ok(workspace_id) => {
-                                return Some(Keybinding {
-                                    modifiers,
-                                    modifiers_count,
-                                    keycode: keycode_maybe.unwrap(),
-                                    action: KeybindingAction::MoveFocusedWindowToWorkspace(
-                                        workspace_id,
-                                    ),
-                                });
-                            }
-                            Err(err) => {
-                                error!(
-                                    "invalid unsigned integer '{}' provided as a workspace id for move focused window to workspace command: {}, error: {:?}",
-                                    workspace_id_str, command, err
-                                );
-                            }
-                        }
-                    } else {
-                        error!(
-                            "no workspace id provided for move focused window to workspace command: {}",
-                            command
-                        )
-                    }
+            };
+            let size_change_str = match parts.next() {
+                Some(size_change_str) => size_change_str,
+                None => {
+                    return Err(
+                        "no pixels in which size would be changed was specified".to_string()
+                    );
                 }
-                "kill_focused_window" => {
-                    return Some(Keybinding {
-                        modifiers,
-                        modifiers_count,
-                        keycode: keycode_maybe.unwrap(),
-                        action: KeybindingAction::KillFocusedWindow,
-                    });
+            };
+            let size_change_pixels = size_change_str.parse::<i32>().map_err(|err| {
+                format!(
+                    "invalid size change pixels value: {}, error: {:?}",
+                    size_change_str, err
+                )
+            })?;
+            match resize_dimension {
+                "horizontal" => Ok(KeybindingAction::ResizeWindow(
+                    Dimension::Horizontal,
+                    size_change_pixels,
+                )),
+                "vertical" => Ok(KeybindingAction::ResizeWindow(
+                    Dimension::Vertical,
+                    size_change_pixels,
+                )),
+                _ => Err(format!(
+                    "unknown resize dimension name: {}",
+                    resize_dimension
+                )),
+            }
+        }
+        "cycle_width" => match parts.next() {
+            Some("next") | None => Ok(KeybindingAction::CycleWidthNext),
+            Some("prev") => Ok(KeybindingAction::CycleWidthPrev),
+            Some(direction) => Err(format!("unknown cycle width direction name: {}", direction)),
+        },
+        "maximize_column" => Ok(KeybindingAction::MaximizeColumn),
+        "fullscreen" => Ok(KeybindingAction::ToggleFullscreen),
+        "switch_to_workspace" => match parts.next() {
+            Some("next") => Ok(KeybindingAction::SwitchToNextWorkspace),
+            Some("prev") => Ok(KeybindingAction::SwitchToPrevWorkspace),
+            Some("back_and_forth") => Ok(KeybindingAction::SwitchToWorkspaceBackAndForth),
+            Some(workspace_str) => Ok(KeybindingAction::SwitchToWorkspace(
+                WorkspaceTarget::from_str(workspace_str),
+            )),
+            None => Err(format!(
+                "no workspace id or name provided for switch to workspace command: {}",
+                command
+            )),
+        },
+        "move_focused_window_to_workspace" => match parts.next() {
+            Some(workspace_str) => Ok(KeybindingAction::MoveFocusedWindowToWorkspace(
+                WorkspaceTarget::from_str(workspace_str),
+            )),
+            None => Err(format!(
+                "no workspace id or name provided for move focused window to workspace command: {}",
+                command
+            )),
+        },
+        "rename_workspace" => match parts.next() {
+            Some(name) => validate_workspace_name(name)
+                .map(|_| KeybindingAction::RenameWorkspace(Some(name.to_owned()))),
+            None => Ok(KeybindingAction::RenameWorkspace(None)),
+        },
+        "kill_focused_window" => Ok(KeybindingAction::KillFocusedWindow),
+        "enter_mode" => match parts.next() {
+            Some(mode) => Ok(KeybindingAction::EnterMode(mode.to_owned())),
+            None => Err(format!("no mode name provided for command: {}", command)),
+        },
+        "center_focused_window" => Ok(KeybindingAction::CenterFocusedWindow),
+        "toggle_floating" => Ok(KeybindingAction::ToggleFloating),
+        "consume_window" => match parts.next().map(direction_from_str) {
+            Some(Some(direction @ (Direction::Left | Direction::Right))) => {
+                Ok(KeybindingAction::ConsumeWindow(direction))
+            }
+            Some(_) => Err(format!(
+                "window can only be consumed into the column on the left or right, command: {}",
+                command
+            )),
+            None => Err(format!(
+                "no direction supplied for consume window command: {}",
+                command
+            )),
+        },
+        "expel_window" => Ok(KeybindingAction::ExpelWindow),
+        "cycle_windows" => match parts.next() {
+            Some("next") | None => Ok(KeybindingAction::CycleWindowsNext),
+            Some("prev") => Ok(KeybindingAction::CycleWindowsPrev),
+            Some(direction) => Err(format!(
+                "unknown cycle windows direction name: {}",
+                direction
+            )),
+        },
+        "scroll_strip" => {
+            let direction = match parts.next().map(direction_from_str) {
+                Some(Some(direction @ (Direction::Left | Direction::Right))) => direction,
+                Some(_) => {
+                    return Err(format!(
+                        "strip can only be scrolled left or right, command: {}",
+                        command
+                    ));
                 }
-                "center_focused_window" => {
-                    return Some(Keybinding {
-                        modifiers,
-                        modifiers_count,
-                        keycode: keycode_maybe.unwrap(),
-                        action: KeybindingAction::CenterFocusedWindow,
-                    });
+                None => {
+                    return Err(format!(
+                        "no direction supplied for scroll strip command: {}",
+                        command
+                    ));
                 }
-                _ => error!("no command matching string: {}", command),
-            }
-        } else {
-            error!("failed to find command in keybinding: {}", keybinding_str);
+            };
+            let pixels_str = parts.next().ok_or_else(|| {
+                "no pixels by which the strip would be scrolled were specified".to_string()
+            })?;
+            let pixels = pixels_str.parse::<i32>().map_err(|err| {
+                format!(
+                    "invalid scroll pixels value: {}, error: {:?}",
+                    pixels_str, err
+                )
+            })?;
+            Ok(KeybindingAction::ScrollStrip(direction, pixels))
+        }
+        _ => Err(format!("no command matching string: {}", command)),
+    }
+}
+
+fn direction_from_str(direction_str: &str) -> Option<Direction> {
+    match direction_str {
+        "left" => Some(Direction::Left),
+        "right" => Some(Direction::Right),
+        "up" => Some(Direction::Up),
+        "down" => Some(Direction::Down),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bind(keys: &str, action: &str, args: &[&str], release: bool) -> BindEntry {
+        BindEntry {
+            keys: keys.to_string(),
+            action: action.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            mode: None,
+            release,
         }
     }
-    return None;
+
+    #[test]
+    fn bind_equals_string_form() {
+        let from_bind = keybinding_from_bind(&bind("Alt+H", "focus_window", &["left"], false));
+        let from_string = keybinding_from_string("Alt+H               focus_window left");
+        assert_eq!(from_bind.ok(), from_string);
+
+        let keybinding =
+            keybinding_from_bind(&bind(" Alt+W,3 ", "switch_to_workspace", &["3"], false)).unwrap();
+        assert_eq!(keybinding.modifiers, XCB_MOD_MASK_1);
+        assert_eq!(keybinding.chord.len(), 1);
+        assert_eq!(
+            keybinding.action,
+            KeybindingAction::SwitchToWorkspace(WorkspaceTarget::Id(3))
+        );
+    }
+
+    #[test]
+    fn bind_unknown_action() {
+        let err =
+            keybinding_from_bind(&bind("Alt+H", "focus_windows", &["left"], false)).unwrap_err();
+        assert!(err.contains("focus_windows"), "{}", err);
+    }
+
+    #[test]
+    fn bind_missing_arg() {
+        assert!(keybinding_from_bind(&bind("Alt+H", "focus_window", &[], false)).is_err());
+        assert!(
+            keybinding_from_bind(&bind("Alt+H", "window_size_change", &["vertical"], false))
+                .is_err()
+        );
+        assert!(keybinding_from_bind(&bind("Alt+Enter", "exec", &[], false)).is_err());
+    }
+
+    #[test]
+    fn bind_empty_keys() {
+        assert!(keybinding_from_bind(&bind("", "kill_focused_window", &[], false)).is_err());
+        assert!(keybinding_from_bind(&bind("  ", "kill_focused_window", &[], false)).is_err());
+    }
+
+    #[test]
+    fn bind_release() {
+        let keybinding = keybinding_from_bind(&bind("Print", "exec", &["scrot"], true)).unwrap();
+        assert!(keybinding.release);
+        // a bare modifier is always a release binding
+        let keybinding = keybinding_from_bind(&bind("Super", "exec", &["rofi"], false)).unwrap();
+        assert!(keybinding.release);
+
+        let err = keybinding_from_bind(&bind("Alt+W,3", "switch_to_workspace", &["3"], true))
+            .unwrap_err();
+        assert!(err.contains("release"), "{}", err);
+    }
 }
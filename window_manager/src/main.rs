@@ -1,38 +1,49 @@
 use std::{
-    io::Write,
-    os::{
-        fd::{AsRawFd, RawFd},
-        unix::net::UnixListener,
-    },
-    path::Path,
+    collections::BTreeMap,
+    os::fd::{AsRawFd, RawFd},
+    path::PathBuf,
+    time::Duration,
 };
 
 use config::{Config, ConfigErrors};
 use env_logger::Env;
 use keybindings::{
-    execute_command_from_str, handle_key_press, keybindings_from_config, keybindings_grab,
+    execute_command_from_str, handle_key_press, handle_key_release, keybindings_from_config,
+    keybindings_grab,
 };
 use log::{error, trace, warn};
 use monitor::Monitor;
+use mousebindings::{
+    handle_button_press, mousebindings_from_config, mousebindings_grab, mousebindings_ungrab,
+};
 use x11_bindings::{
     bindings::{
-        XCB_CW_EVENT_MASK, XCB_EVENT_MASK_SUBSTRUCTURE_NOTIFY, XCB_EVENT_MASK_SUBSTRUCTURE_REDIRECT,
+        XCB_CW_EVENT_MASK, XCB_EVENT_MASK_BUTTON_PRESS, XCB_EVENT_MASK_SUBSTRUCTURE_NOTIFY,
+        XCB_EVENT_MASK_SUBSTRUCTURE_REDIRECT, XCB_NOTIFY_MODE_GRAB, XCB_NOTIFY_MODE_UNGRAB,
     },
     connection::{self, Connection},
     epoll::Epoll,
     inotify::Inotify,
+    timerfd::TimerFd,
 };
 
 use crate::{
-    bar_message::{Message, UnixClients},
-    keybindings::{execute_command_from_str_wait, keybindings_ungrab},
+    animation::Animations,
+    bar_message::{Message, PROTOCOL_VERSION, UnixClients, supported_message_tags},
+    keybindings::{
+        DEFAULT_CHORD_TIMEOUT, execute_command_from_str_wait, execute_keybinding_action,
+        keybinding_action_from_str, keybindings_resolve_keycodes, keybindings_ungrab,
+    },
 };
 
+mod animation;
 mod bar_message;
 mod config;
 mod keybindings;
 mod monitor;
+mod mousebindings;
 mod window;
+mod window_rules;
 mod workspace;
 
 fn main() {
@@ -76,11 +87,22 @@ fn main() {
     epoll
         .add_watch(inotify.fd)
         .expect("failed to add inotify fd to epoll watch list");
+    let chord_timer = TimerFd::new().expect("failed to create chord timer");
+    epoll
+        .add_watch(chord_timer.fd)
+        .expect("failed to add chord timer fd to epoll watch list");
+    let mut animations = Animations::new().expect("failed to create animation timer");
+    epoll
+        .add_watch(animations.timer_fd())
+        .expect("failed to add animation timer fd to epoll watch list");
 
     if let Err(err) = conn.change_window_attrs_checked(
         conn.root(),
         XCB_CW_EVENT_MASK,
-        XCB_EVENT_MASK_SUBSTRUCTURE_REDIRECT | XCB_EVENT_MASK_SUBSTRUCTURE_NOTIFY,
+        // button presses on the bare root window trigger root mouse bindings
+        XCB_EVENT_MASK_SUBSTRUCTURE_REDIRECT
+            | XCB_EVENT_MASK_SUBSTRUCTURE_NOTIFY
+            | XCB_EVENT_MASK_BUTTON_PRESS,
     ) {
         error!("Failed to acquire control over the root window.");
         error!("Error details: {}", err);
@@ -91,24 +113,41 @@ fn main() {
     let mut monitor = Monitor::new(&conn);
     trace!("monitor dimensions: {:?}", monitor.rect);
 
-    let mut keybindings = keybindings_from_config(&config);
+    let mut keybindings = keybindings_from_config(&config, &conn);
     keybindings_grab(&keybindings, &conn);
+    let mut mousebindings = mousebindings_from_config(&config);
+    mousebindings_grab(&mousebindings, &conn);
     // trace!("keybindings: {:#?}", keybindings);
 
     conn.change_cursor("left_ptr");
     conn.flush();
 
-    const SERVER_SOCKET_PATH_STR: &str = "/tmp/x11_wm_rust.socket";
-    let server_socket_path = Path::new(SERVER_SOCKET_PATH_STR);
+    let server_socket_path = config
+        .ipc_socket_path
+        .as_ref()
+        .map_or_else(bar_message::socket_path_from_env, PathBuf::from);
     if server_socket_path.exists() {
-        let _ = std::fs::remove_file(server_socket_path);
+        let _ = std::fs::remove_file(&server_socket_path);
     }
     let unix_listener =
-        UnixListener::bind(SERVER_SOCKET_PATH_STR).expect("failed to create unix listener");
+        bar_message::bind_listener(&server_socket_path).expect("failed to create unix listener");
+    trace!("listening on {}", server_socket_path.display());
+    // SAFETY: no other threads are running yet, startup commands below inherit the variable
+    unsafe { std::env::set_var(bar_message::SOCKET_PATH_ENV, &server_socket_path) };
     epoll
         .add_watch(unix_listener.as_raw_fd())
         .expect("failed to add unix listener to epoll watch list");
-    let mut unix_clients = UnixClients::new();
+    let mut unix_clients = UnixClients::new(
+        config
+            .ipc_max_message_size
+            .unwrap_or(bar_message::DEFAULT_MAX_FRAME_SIZE),
+    );
+    monitor.update_workspace_names(
+        &BTreeMap::new(),
+        &config.workspace_names_by_id,
+        &conn,
+        &mut unix_clients,
+    );
 
     for cmd_str in &config.startup_commands {
         execute_command_from_str(cmd_str);
@@ -125,31 +164,65 @@ fn main() {
     }
 
     let mut keyboard_layout_name_current = "".to_string();
+    let mut focused_window_current = 0;
     loop {
         let events = epoll
             .wait()
             .expect("epoll failed while waiting for new events");
+        // geometry changes caused by keybindings and commands are animated
+        let geometries_before = monitor.window_geometries(&config);
+        let mut animate = false;
         for event in events.clone() {
             if event.u64 == x11_conn_fd as u64 {
                 while let Some(event_res) = conn.poll_for_event() {
                     match event_res {
                         Ok(event) => match event {
                             connection::XcbEvents::KeyPress { modifier, keycode } => {
+                                animate = true;
                                 handle_key_press(
-                                    &keybindings,
+                                    &mut keybindings,
                                     &conn,
                                     &config,
                                     &mut monitor,
                                     modifier,
                                     keycode,
                                     &mut unix_clients,
-                                )
+                                );
+                                if keybindings.is_chord_pending() {
+                                    let timeout = config
+                                        .chord_timeout_ms
+                                        .map_or(DEFAULT_CHORD_TIMEOUT, Duration::from_millis);
+                                    if let Err(err) = chord_timer.arm(timeout) {
+                                        warn!("failed to arm chord timer: {}", err);
+                                    }
+                                }
+                            }
+                            connection::XcbEvents::KeyRelease { modifier, keycode } => {
+                                animate = true;
+                                handle_key_release(
+                                    &mut keybindings,
+                                    &conn,
+                                    &config,
+                                    &mut monitor,
+                                    modifier,
+                                    keycode,
+                                    &mut unix_clients,
+                                );
                             }
                             connection::XcbEvents::MapRequst { window } => {
                                 monitor.handle_map_request(&conn, &config, window)
                             }
                             connection::XcbEvents::FocusIn { window, mode } => {
-                                monitor.handle_focus_in(&conn, &config, window, mode)
+                                monitor.handle_focus_in(&conn, &config, window, mode);
+                                if mode != XCB_NOTIFY_MODE_GRAB
+                                    && mode != XCB_NOTIFY_MODE_UNGRAB
+                                    && window != conn.root()
+                                    && window != focused_window_current
+                                {
+                                    focused_window_current = window;
+                                    let title = conn.window_title(window).unwrap_or_default();
+                                    unix_clients.notify_all(Message::FocusedWindow(window, &title));
+                                }
                             }
                             connection::XcbEvents::FocusOut { window, mode } => {
                                 monitor.handle_focus_out(&conn, &config, window, mode)
@@ -159,15 +232,30 @@ fn main() {
                             }
                             connection::XcbEvents::LeaveNotify { window: _ } => {}
                             connection::XcbEvents::ButtonPress {
-                                x,
-                                y,
-                                window,
+                                x: _,
+                                y: _,
+                                root_x,
+                                root_y,
+                                window: _,
+                                child,
                                 state,
                                 detail,
                                 time,
                             } => {
-                                monitor.handle_button_press(
-                                    x, y, window, state, detail, &conn, &config, time,
+                                animate = true;
+                                handle_button_press(
+                                    &mousebindings,
+                                    &mut keybindings,
+                                    &conn,
+                                    &config,
+                                    &mut monitor,
+                                    root_x,
+                                    root_y,
+                                    child,
+                                    state,
+                                    detail,
+                                    time,
+                                    &mut unix_clients,
                                 );
                             }
                             connection::XcbEvents::ButtonRelease { x: _, y: _ } => {
@@ -193,6 +281,17 @@ fn main() {
                                         .notify_all(Message::KeyboardLayout(keyboard_layout_name));
                                 }
                             }
+                            connection::XcbEvents::KeyboardMappingChanged => {
+                                trace!("keyboard mapping changed, resolving keybindings again");
+                                if conn.xkb_reload_keymap() {
+                                    keybindings.cancel_chord(&conn);
+                                    keybindings_ungrab(&keybindings, &conn);
+                                    keybindings_resolve_keycodes(&mut keybindings, &conn);
+                                    keybindings_grab(&keybindings, &conn);
+                                } else {
+                                    warn!("failed to reload keymap, keeping the old keybindings");
+                                }
+                            }
                             connection::XcbEvents::RandrScreenChange { width, height } => {
                                 trace!("RandrScreenChange: width: {}, heigth: {}", width, height);
                                 monitor.update_with_new_dimensions(width, height, &conn, &config);
@@ -216,6 +315,18 @@ fn main() {
                             connection::XcbEvents::UnmapNotify { window } => {
                                 trace!("unmap notify for window: {}", window);
                             }
+                            connection::XcbEvents::FullscreenRequest { window, fullscreen } => {
+                                monitor
+                                    .handle_fullscreen_request(&conn, &config, window, fullscreen);
+                            }
+                            connection::XcbEvents::PropertyNotify { window, atom } => {
+                                if window == focused_window_current
+                                    && conn.is_window_title_atom(atom)
+                                {
+                                    let title = conn.window_title(window).unwrap_or_default();
+                                    unix_clients.notify_all(Message::FocusedWindow(window, &title));
+                                }
+                            }
                         },
                         Err(error) => warn!("Error event: {:?}", error),
                     };
@@ -278,19 +389,43 @@ fn main() {
                                             }
                                         }
 
-                                        let new_keybindings = keybindings_from_config(&config);
+                                        let new_keybindings =
+                                            keybindings_from_config(&new_config, &conn);
                                         if keybindings != new_keybindings {
+                                            keybindings.cancel_chord(&conn);
                                             keybindings_ungrab(&keybindings, &conn);
                                             keybindings_grab(&new_keybindings, &conn);
+                                            if keybindings.active_mode()
+                                                != new_keybindings.active_mode()
+                                            {
+                                                unix_clients.notify_all(Message::ModeChanged(
+                                                    new_keybindings.active_mode(),
+                                                ));
+                                            }
                                             keybindings = new_keybindings;
                                         }
 
+                                        let new_mousebindings =
+                                            mousebindings_from_config(&new_config);
+                                        if mousebindings != new_mousebindings {
+                                            mousebindings_ungrab(&mousebindings, &conn);
+                                            mousebindings_grab(&new_mousebindings, &conn);
+                                            mousebindings = new_mousebindings;
+                                        }
+
                                         monitor.remap_windows_with_upd_config(
                                             &conn,
                                             &config,
                                             &new_config,
                                         );
+                                        monitor.update_workspace_names(
+                                            &config.workspace_names_by_id,
+                                            &new_config.workspace_names_by_id,
+                                            &conn,
+                                            &mut unix_clients,
+                                        );
                                         config = new_config;
+                                        unix_clients.notify_all(Message::ConfigReloaded);
 
                                         conn.flush();
                                     }
@@ -312,10 +447,26 @@ fn main() {
                         Err(err) => warn!("inotify read error: {:?}", err),
                     }
                 }
+            } else if event.u64 == chord_timer.fd as u64 {
+                if let Err(err) = chord_timer.read() {
+                    warn!("chord timer read error: {}", err);
+                }
+                keybindings.cancel_chord(&conn);
+                conn.flush();
+            } else if event.u64 == animations.timer_fd() as u64 {
+                animations.handle_timer(&conn, &config);
+                conn.flush();
             } else if event.u64 == unix_listener.as_raw_fd() as u64 {
                 match unix_listener.accept() {
                     Ok((client, _)) => {
-                        if let Err(err) = epoll.add_watch(client.as_raw_fd()) {
+                        if !bar_message::is_trusted_peer(&client) {
+                            // dropping the stream closes the connection
+                        } else if let Err(err) = client.set_nonblocking(true) {
+                            warn!("failed to make unix client socket non-blocking: {}", err);
+                        } else if let Err(err) = epoll.add_watch_with_events(
+                            client.as_raw_fd(),
+                            (libc::EPOLLIN | libc::EPOLLOUT | libc::EPOLLET) as u32,
+                        ) {
                             warn!("failed to add unix client to epoll watch list: {}", err);
                         } else {
                             unix_clients.add_client(client);
@@ -324,78 +475,157 @@ fn main() {
                     Err(err) => warn!("unix listener failed to accept connection: {}", err),
                 }
             } else {
-                if let Some(client_stream) = unix_clients.find_client_by_fd(event.u64 as RawFd) {
-                    if let Some(message) = Message::read_from_unix_stream(client_stream) {
-                        trace!("message from unix stream client: {:?}", message);
-                        match message {
-                            Message::RequestClientInit => {
-                                if !keyboard_layout_name_current.is_empty() {
-                                    let message =
-                                        Message::KeyboardLayout(&keyboard_layout_name_current);
-                                    if let Err(err) = client_stream.write_all(&message.as_bytes()) {
+                let client_fd = event.u64 as RawFd;
+                if event.events & libc::EPOLLOUT as u32 != 0 {
+                    unix_clients.flush(client_fd);
+                }
+                if event.events & (libc::EPOLLIN | libc::EPOLLHUP | libc::EPOLLERR) as u32 == 0 {
+                    continue;
+                }
+                match unix_clients.receive(client_fd) {
+                    Some(input) => {
+                        let mut disconnect = input.disconnected;
+                        for frame in &input.frames {
+                            let Some(message) = Message::from_bytes(frame) else {
+                                warn!(
+                                    "malformed message from unix stream client {}, disconnecting",
+                                    client_fd
+                                );
+                                disconnect = true;
+                                break;
+                            };
+                            trace!("message from unix stream client: {:?}", message);
+                            match message {
+                                Message::RequestClientInit { version, supported } => {
+                                    if version != PROTOCOL_VERSION {
+                                        let err = format!(
+                                            "protocol version mismatch, client: {}, window manager: {}",
+                                            version, PROTOCOL_VERSION
+                                        );
                                         warn!(
-                                            "failed to write keyboard layout name to client on RequestClientInit: {}",
-                                            err
+                                            "rejecting unix stream client {}: {}",
+                                            client_fd, err
+                                        );
+                                        unix_clients.send_to(
+                                            client_fd,
+                                            Message::ClientInitReply(Err(&err)),
                                         );
+                                        unix_clients.close_after_flush(client_fd);
+                                        break;
                                     }
+                                    unix_clients.set_supported(client_fd, supported);
+                                    unix_clients.send_to(
+                                        client_fd,
+                                        Message::ClientInitReply(Ok(supported_message_tags())),
+                                    );
+
+                                    if !keyboard_layout_name_current.is_empty() {
+                                        let message =
+                                            Message::KeyboardLayout(&keyboard_layout_name_current);
+                                        trace!(
+                                            "keyboard layout name sent to unix stream client: {:?}",
+                                            message
+                                        );
+                                        unix_clients.send_to(client_fd, message);
+                                    }
+
+                                    let workspaces_ids = monitor
+                                        .workspaces
+                                        .iter()
+                                        .map(|workspace| workspace.id)
+                                        .collect::<Vec<_>>();
+                                    let message = Message::WorkspaceList(workspaces_ids);
                                     trace!(
-                                        "keyboard layout name sent to unix stream client: {:?}",
+                                        "workspaces ids sent to unix stream client: {:?}",
                                         message
                                     );
-                                }
-
-                                let workspaces_ids = monitor
-                                    .workspaces
-                                    .iter()
-                                    .map(|workspace| workspace.id)
-                                    .collect::<Vec<_>>();
-                                let message = Message::WorkspaceList(workspaces_ids);
-                                if let Err(err) = client_stream.write_all(&message.as_bytes()) {
-                                    warn!(
-                                        "failed to write workspaces ids to client on RequestClientInit: {}",
-                                        err
+                                    unix_clients.send_to(client_fd, message);
+                                    unix_clients.send_to(
+                                        client_fd,
+                                        Message::WorkspaceListNamed(monitor.named_workspace_list()),
                                     );
-                                }
-                                trace!("workspaces ids sent to unix stream client: {:?}", message);
 
-                                if let Some(focused_workspace_id) =
-                                    monitor.get_focused_workspace_id()
-                                {
-                                    let message = Message::WorkspaceActive(focused_workspace_id);
-                                    if let Err(err) = client_stream.write_all(&message.as_bytes()) {
-                                        warn!(
-                                            "failed to write focused workspace id to client on RequestClientInit: {}",
-                                            err
+                                    if let Some(focused_workspace_id) =
+                                        monitor.get_focused_workspace_id()
+                                    {
+                                        let message =
+                                            Message::WorkspaceActive(focused_workspace_id);
+                                        trace!(
+                                            "focused workspace id sent to unix stream client: {:?}",
+                                            message
                                         );
+                                        unix_clients.send_to(client_fd, message);
                                     }
-                                    trace!(
-                                        "focused workspace id sent to unix stream client: {:?}",
-                                        message
+
+                                    unix_clients.send_to(
+                                        client_fd,
+                                        Message::ModeChanged(keybindings.active_mode()),
                                     );
                                 }
-                            }
-                            Message::Eof => {
-                                let fd = client_stream.as_raw_fd();
-                                if let Err(err) = epoll.remove_watch(fd) {
-                                    warn!(
-                                        "failed to remove fd {} from epoll's watch list, err: {}",
-                                        fd, err
-                                    );
+                                Message::Command(command_str) => {
+                                    let reply = match keybinding_action_from_str(command_str) {
+                                        Ok(action) => {
+                                            animate = true;
+                                            let result = execute_keybinding_action(
+                                                &action,
+                                                &mut keybindings,
+                                                &conn,
+                                                &config,
+                                                &mut monitor,
+                                                &mut unix_clients,
+                                            );
+                                            conn.flush();
+                                            if let Err(err) = &result {
+                                                warn!(
+                                                    "failed to execute command from unix stream client: {}",
+                                                    err
+                                                );
+                                            }
+                                            result
+                                        }
+                                        Err(err) => {
+                                            warn!(
+                                                "invalid command from unix stream client: {}",
+                                                err
+                                            );
+                                            Err(err)
+                                        }
+                                    };
+                                    let reply = match &reply {
+                                        Ok(()) => Ok(()),
+                                        Err(err) => Err(err.as_str()),
+                                    };
+                                    unix_clients.send_to(client_fd, Message::CommandReply(reply));
                                 }
-                                if let Err(err) = client_stream.shutdown(std::net::Shutdown::Both) {
-                                    warn!("failed to shutdown client stream: {}", err);
+                                Message::Subscribe(topics) => {
+                                    unix_clients.subscribe(client_fd, topics);
                                 }
-                                unix_clients.remove_client(fd);
+                                Message::RequestTree => match serde_json::to_string(&monitor) {
+                                    Ok(json) => {
+                                        unix_clients.send_to(client_fd, Message::Tree(&json))
+                                    }
+                                    Err(err) => warn!("failed to serialize monitor state: {}", err),
+                                },
+                                _ => {}
                             }
-                            _ => {}
                         }
-                    } else {
-                        warn!("wasn't able to read message sent by client");
+                        if disconnect {
+                            unix_clients.remove_client(client_fd);
+                        }
                     }
-                } else {
-                    warn!("received message from unknown client");
+                    None => warn!("received message from unknown client"),
                 }
             }
         }
+        monitor.update_dynamic_workspaces(&conn, &config, &mut unix_clients);
+        animations.update(
+            &geometries_before,
+            &monitor.window_geometries(&config),
+            animate,
+            &conn,
+            &config,
+        );
+        conn.flush();
+        monitor.notify_state_changes(&config, &mut unix_clients);
     }
 }
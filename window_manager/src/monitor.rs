@@ -1,30 +1,70 @@
-use std::{time::UNIX_EPOCH, u32};
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::UNIX_EPOCH,
+    u32,
+};
 
 use base::Rect;
 use log::{error, trace};
+use serde::Serialize;
 use x11_bindings::bindings::{
     XCB_CW_BORDER_PIXEL, XCB_NOTIFY_MODE_GRAB, XCB_NOTIFY_MODE_UNGRAB, xcb_button_t,
-    xcb_notify_mode_t, xcb_timestamp_t, xcb_window_t,
+    xcb_notify_mode_t, xcb_window_t,
 };
 
 use crate::{
+    animation::WindowGeometry,
     bar_message::{Message, UnixClients},
     config::Config,
     connection::{Connection, WindowType},
-    keybindings::{Dimension, Direction},
+    keybindings::{Dimension, Direction, WorkspaceTarget},
     window::WindowsCollection,
+    window_rules::{WindowProperties, window_rule_actions},
     workspace::Workspace,
 };
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Monitor {
     pub rect: Rect,
     pub workspaces: Vec<Workspace>,
     docked: WindowsCollection,
     focused_workspace_idx: usize,
+    #[serde(skip)]
     to_check_deleted: Vec<(xcb_window_t, u64)>, // window and timestamp when it was requested to be deleted
     dmenu_window: Option<xcb_window_t>,
+    #[serde(skip)]
+    notified_windows: Vec<xcb_window_t>, // managed windows as last reported to unix clients
+    #[serde(skip)]
+    notified_layout: Vec<(xcb_window_t, Rect)>,
+    /// floating windows moved along on workspace switches, set by window rules
+    #[serde(skip)]
+    sticky_windows: Vec<xcb_window_t>,
+    /// unfocused border colors set by window rules
+    #[serde(skip)]
+    border_colors: HashMap<xcb_window_t, u32>,
+    /// names from `workspace_names` of the config or `rename_workspace`, by workspace id
+    workspace_names: BTreeMap<u32, String>,
+    /// workspace focused before the current one, target of `switch_to_workspace back_and_forth`
+    #[serde(skip)]
+    prev_focused_workspace_id: Option<u32>,
+    /// windows of all workspaces in the order they got input focus, the most recent last
+    #[serde(skip)]
+    window_history: Vec<xcb_window_t>,
+    #[serde(skip)]
+    window_switcher: Option<WindowSwitcher>,
+}
+
+/// `cycle_windows` in progress, candidates are previewed until the modifier is released
+#[derive(Debug)]
+struct WindowSwitcher {
+    /// most recently focused first, the window focused when the switcher started at index 0
+    candidates: Vec<xcb_window_t>,
+    idx: usize,
+    /// restored when the switcher is cancelled
+    original_workspace_id: u32,
+    original_window: Option<xcb_window_t>,
+    original_prev_focused_workspace_id: Option<u32>,
 }
 
 impl Monitor {
@@ -36,6 +76,14 @@ impl Monitor {
             focused_workspace_idx: 0,
             to_check_deleted: vec![],
             dmenu_window: None,
+            notified_windows: vec![],
+            notified_layout: vec![],
+            sticky_windows: vec![],
+            border_colors: HashMap::new(),
+            workspace_names: BTreeMap::new(),
+            prev_focused_workspace_id: None,
+            window_history: vec![],
+            window_switcher: None,
         }
     }
 
@@ -145,18 +193,64 @@ impl Monitor {
                 window_type = WindowType::Floating;
             }
         }
+
+        let window_properties = WindowProperties::new(window, &class_instance_maybe, conn);
+        let rule_actions = window_rule_actions(config, &window_properties);
+        trace!(
+            "window properties: {:?}, rule actions: {:?}",
+            window_properties, rule_actions
+        );
+        if window_type != WindowType::Docked {
+            match rule_actions.floating {
+                Some(true) => window_type = WindowType::Floating,
+                Some(false) => window_type = WindowType::Normal,
+                None => {}
+            }
+            if rule_actions.sticky {
+                window_type = WindowType::Floating;
+            }
+            if let Some(opacity) = rule_actions.opacity {
+                conn.window_set_opacity(window, opacity);
+            }
+            if let Some(border_color) = rule_actions.border_color {
+                self.border_colors.insert(window, border_color);
+            }
+        }
+        let focused_workspace_id = self.workspaces.get(self.focused_workspace_idx).unwrap().id;
+        // sticky windows stay on the focused workspace as they follow it anyway
+        let target_workspace_idx = match rule_actions.workspace {
+            Some(workspace_id) if !rule_actions.sticky => {
+                self.workspace_idx_or_create(workspace_id)
+            }
+            _ => self.focused_workspace_idx,
+        };
+        let focus = !rule_actions.no_focus;
         match window_type {
             WindowType::Normal => {
-                let focused_workspace =
-                    self.workspaces.get_mut(self.focused_workspace_idx).unwrap();
-                focused_workspace.handle_new_normal_window(window, &avail_rect, conn, config);
+                let target_workspace = self.workspaces.get_mut(target_workspace_idx).unwrap();
+                target_workspace.handle_new_normal_window(
+                    window,
+                    rule_actions
+                        .width_percent
+                        .unwrap_or(config.default_screen_width_percent_tiling),
+                    focus,
+                    focused_workspace_id,
+                    &avail_rect,
+                    conn,
+                    config,
+                );
             }
             WindowType::Floating => {
-                let focused_workspace =
-                    self.workspaces.get_mut(self.focused_workspace_idx).unwrap();
-                focused_workspace.handle_new_floating_window(
+                if rule_actions.sticky {
+                    self.sticky_windows.push(window);
+                }
+                let target_workspace = self.workspaces.get_mut(target_workspace_idx).unwrap();
+                target_workspace.handle_new_floating_window(
                     window,
                     rect_hints_maybe,
+                    rule_actions.rect,
+                    focus,
+                    focused_workspace_id,
                     &avail_rect,
                     conn,
                     config,
@@ -192,17 +286,7 @@ impl Monitor {
                             );
                         }
                     } else {
-                        let target_workspace_idx = if let Some((idx, _)) = self
-                            .workspaces
-                            .iter()
-                            .enumerate()
-                            .find(|(_, w)| w.id == workspace_id)
-                        {
-                            idx
-                        } else {
-                            self.workspaces.push(Workspace::new(workspace_id, false));
-                            self.workspaces.len() - 1
-                        };
+                        let target_workspace_idx = self.workspace_idx_or_create(workspace_id);
                         let avail_rect = self
                             .rect
                             .available_rect_after_adding_rects(self.docked.rect_iter());
@@ -217,9 +301,72 @@ impl Monitor {
             }
         }
 
+        if let (true, Some(border_color)) = (rule_actions.no_focus, rule_actions.border_color) {
+            conn.change_window_attrs(window, XCB_CW_BORDER_PIXEL, border_color);
+        }
+        if window_type != WindowType::Docked
+            && (rule_actions.fullscreen || conn.window_requests_fullscreen(window))
+        {
+            self.workspaces
+                .get_mut(target_workspace_idx)
+                .unwrap()
+                .set_window_fullscreen(window, true, &self.rect, conn, config);
+        }
+
         conn.flush();
     }
 
+    /// Index of the workspace with the given id, creating it if needed. Workspaces are kept
+    /// sorted by id so the list neighbours are the previous and next workspaces
+    fn workspace_idx_or_create(&mut self, workspace_id: u32) -> usize {
+        match self
+            .workspaces
+            .binary_search_by_key(&workspace_id, |workspace| workspace.id)
+        {
+            Ok(idx) => idx,
+            Err(idx) => {
+                self.workspaces
+                    .insert(idx, Workspace::new(workspace_id, false));
+                if idx <= self.focused_workspace_idx {
+                    self.focused_workspace_idx += 1;
+                }
+                idx
+            }
+        }
+    }
+
+    /// Id of the workspace before the focused one: the previous existing workspace with
+    /// `dynamic_workspaces`, the previous id down to 1 otherwise
+    fn prev_workspace_id(&self, config: &Config) -> Option<u32> {
+        let focused_workspace_id = self.workspaces.get(self.focused_workspace_idx).unwrap().id;
+        if config.dynamic_workspaces {
+            self.focused_workspace_idx
+                .checked_sub(1)
+                .map(|idx| self.workspaces.get(idx).unwrap().id)
+        } else if focused_workspace_id > 1 {
+            Some(focused_workspace_id - 1)
+        } else {
+            None
+        }
+    }
+
+    /// Id of the workspace after the focused one: the next existing workspace with
+    /// `dynamic_workspaces` (a new one after the last), the next id up to 9 otherwise
+    fn next_workspace_id(&self, config: &Config) -> Option<u32> {
+        let focused_workspace_id = self.workspaces.get(self.focused_workspace_idx).unwrap().id;
+        if config.dynamic_workspaces {
+            Some(
+                self.workspaces
+                    .get(self.focused_workspace_idx + 1)
+                    .map_or(focused_workspace_id + 1, |workspace| workspace.id),
+            )
+        } else if focused_workspace_id < 9 {
+            Some(focused_workspace_id + 1)
+        } else {
+            None
+        }
+    }
+
     pub fn handle_focus_in(
         &mut self,
         conn: &Connection,
@@ -236,6 +383,11 @@ impl Monitor {
             // trace!("ignoring focus_in notification due to window being root");
             return;
         }
+        // previews of the window switcher don't count as use of the window
+        if self.window_switcher.is_none() {
+            self.window_history.retain(|w| *w != window);
+            self.window_history.push(window);
+        }
 
         self.workspaces
             .get(self.focused_workspace_idx)
@@ -264,11 +416,12 @@ impl Monitor {
         if !conn.window_exists(window) {
             return;
         }
-        conn.change_window_attrs(
-            window,
-            XCB_CW_BORDER_PIXEL,
-            config.border_color_inactive_int.unwrap(),
-        );
+        let border_color = self
+            .border_colors
+            .get(&window)
+            .copied()
+            .unwrap_or(config.border_color_inactive_int.unwrap());
+        conn.change_window_attrs(window, XCB_CW_BORDER_PIXEL, border_color);
         conn.flush();
     }
 
@@ -294,28 +447,25 @@ impl Monitor {
                 .get_mut(self.focused_workspace_idx)
                 .unwrap()
                 .handle_change_focus_window_right(conn, config, &avail_rect),
+            // within the column first, then to the neighbouring workspace
             Direction::Up => {
-                let focused_workspace_id =
-                    self.workspaces.get(self.focused_workspace_idx).unwrap().id;
-                if focused_workspace_id > 1 {
-                    self.handle_switch_to_workspace(
-                        conn,
-                        config,
-                        focused_workspace_id - 1,
-                        unix_clients,
-                    );
+                if !self
+                    .workspaces
+                    .get_mut(self.focused_workspace_idx)
+                    .unwrap()
+                    .focus_window_in_column(true, conn, config)
+                {
+                    self.handle_switch_to_prev_workspace(conn, config, unix_clients);
                 }
             }
             Direction::Down => {
-                let focused_workspace_id =
-                    self.workspaces.get(self.focused_workspace_idx).unwrap().id;
-                if focused_workspace_id < 9 {
-                    self.handle_switch_to_workspace(
-                        conn,
-                        config,
-                        focused_workspace_id + 1,
-                        unix_clients,
-                    );
+                if !self
+                    .workspaces
+                    .get_mut(self.focused_workspace_idx)
+                    .unwrap()
+                    .focus_window_in_column(false, conn, config)
+                {
+                    self.handle_switch_to_next_workspace(conn, config, unix_clients);
                 }
             }
         };
@@ -344,33 +494,34 @@ impl Monitor {
                 .get_mut(self.focused_workspace_idx)
                 .unwrap()
                 .handle_move_window_right(conn, config, &avail_rect),
+            // within the column first, then to the neighbouring workspace
             Direction::Up => {
-                let focused_workspace_id = self
+                let moved = self
                     .workspaces
                     .get_mut(self.focused_workspace_idx)
                     .unwrap()
-                    .id;
-                if focused_workspace_id > 1 {
+                    .move_window_in_column(true, conn, config);
+                if !moved && let Some(workspace_id) = self.prev_workspace_id(config) {
                     self.handle_move_focused_window_to_workspace(
                         conn,
                         config,
-                        focused_workspace_id - 1,
+                        workspace_id,
                         true,
                         unix_clients,
                     );
                 }
             }
             Direction::Down => {
-                let focused_workspace_id = self
+                let moved = self
                     .workspaces
                     .get_mut(self.focused_workspace_idx)
                     .unwrap()
-                    .id;
-                if focused_workspace_id < 9 {
+                    .move_window_in_column(false, conn, config);
+                if !moved && let Some(workspace_id) = self.next_workspace_id(config) {
                     self.handle_move_focused_window_to_workspace(
                         conn,
                         config,
-                        focused_workspace_id + 1,
+                        workspace_id,
                         true,
                         unix_clients,
                     );
@@ -407,8 +558,57 @@ impl Monitor {
                     size_change_pixels,
                 );
             }
-            Dimension::Vertical => todo!(),
+            Dimension::Vertical => {
+                focused_workspace.handle_resize_window_vertical(conn, config, size_change_pixels);
+            }
+        };
+        conn.flush();
+    }
+
+    pub fn handle_maximize_column(&mut self, conn: &Connection, config: &Config) {
+        let avail_rect = self
+            .rect
+            .available_rect_after_adding_rects(self.docked.rect_iter());
+        self.workspaces
+            .get_mut(self.focused_workspace_idx)
+            .unwrap()
+            .toggle_maximize_column(conn, config, &avail_rect);
+        conn.flush();
+    }
+
+    pub fn handle_toggle_fullscreen(&mut self, conn: &Connection, config: &Config) {
+        self.workspaces
+            .get_mut(self.focused_workspace_idx)
+            .unwrap()
+            .toggle_focused_window_fullscreen(&self.rect, conn, config);
+        conn.flush();
+    }
+
+    /// _NET_WM_STATE_FULLSCREEN change asked by a client, `fullscreen` is None for a toggle
+    pub fn handle_fullscreen_request(
+        &mut self,
+        conn: &Connection,
+        config: &Config,
+        window: xcb_window_t,
+        fullscreen: Option<bool>,
+    ) {
+        let Some(workspace_idx) = self.workspace_idx_of_window(window) else {
+            return;
         };
+        let workspace = self.workspaces.get_mut(workspace_idx).unwrap();
+        let fullscreen = fullscreen.unwrap_or(!workspace.is_window_fullscreen(window));
+        workspace.set_window_fullscreen(window, fullscreen, &self.rect, conn, config);
+        conn.flush();
+    }
+
+    pub fn handle_cycle_column_width(&mut self, conn: &Connection, config: &Config, forward: bool) {
+        let avail_rect = self
+            .rect
+            .available_rect_after_adding_rects(self.docked.rect_iter());
+        self.workspaces
+            .get_mut(self.focused_workspace_idx)
+            .unwrap()
+            .cycle_column_width(forward, conn, config, &avail_rect);
         conn.flush();
     }
 
@@ -432,37 +632,226 @@ impl Monitor {
             .available_rect_after_adding_rects(self.docked.rect_iter());
 
         let hide_below = workspace_id < focused_workspace_id;
+        let focused_workspace = self.workspaces.get_mut(self.focused_workspace_idx).unwrap();
+        let sticky_windows = self
+            .sticky_windows
+            .iter()
+            .filter_map(|window| {
+                focused_workspace
+                    .pop_floating_window(*window)
+                    .map(|(rect, fullscreen)| (*window, rect, fullscreen))
+            })
+            .collect::<Vec<_>>();
+        focused_workspace.hide_all_windows(&avail_rect, conn, config, hide_below);
+
+        self.focused_workspace_idx = self.workspace_idx_or_create(workspace_id);
+        self.prev_focused_workspace_id = Some(focused_workspace_id);
         self.workspaces
             .get_mut(self.focused_workspace_idx)
             .unwrap()
-            .hide_all_windows(&avail_rect, conn, config, hide_below);
+            .show_all_windows(&avail_rect, conn, config);
+        for (window, rect, fullscreen) in sticky_windows {
+            let workspace = self.workspaces.get_mut(self.focused_workspace_idx).unwrap();
+            workspace.handle_existing_floating_window(
+                window,
+                &rect,
+                &avail_rect,
+                focused_workspace_id,
+                false,
+                conn,
+                config,
+            );
+            if fullscreen {
+                workspace.set_window_fullscreen(window, true, &self.rect, conn, config);
+            }
+        }
+
+        conn.flush();
+
+        self.notify_workspace_list(conn, unix_clients);
+        unix_clients.notify_all(Message::WorkspaceActive(workspace_id));
+    }
+
+    pub fn handle_switch_to_prev_workspace(
+        &mut self,
+        conn: &Connection,
+        config: &Config,
+        unix_clients: &mut UnixClients,
+    ) {
+        if let Some(workspace_id) = self.prev_workspace_id(config) {
+            self.handle_switch_to_workspace(conn, config, workspace_id, unix_clients);
+        }
+    }
+
+    pub fn handle_switch_to_next_workspace(
+        &mut self,
+        conn: &Connection,
+        config: &Config,
+        unix_clients: &mut UnixClients,
+    ) {
+        if let Some(workspace_id) = self.next_workspace_id(config) {
+            self.handle_switch_to_workspace(conn, config, workspace_id, unix_clients);
+        }
+    }
+
+    pub fn handle_switch_to_workspace_back_and_forth(
+        &mut self,
+        conn: &Connection,
+        config: &Config,
+        unix_clients: &mut UnixClients,
+    ) {
+        if let Some(workspace_id) = self.prev_focused_workspace_id {
+            self.handle_switch_to_workspace(conn, config, workspace_id, unix_clients);
+        }
+    }
+
+    pub fn is_window_switcher_active(&self) -> bool {
+        self.window_switcher.is_some()
+    }
 
-        let new_focused_workspace_idx = if let Some((idx, _)) = self
+    /// Starts cycling through the tiled and floating windows of all workspaces in most recently
+    /// used order, false if there is no other window to switch to
+    pub fn start_window_switcher(&mut self) -> bool {
+        let mut candidates = self
+            .window_history
+            .iter()
+            .rev()
+            .copied()
+            .filter(|window| self.workspace_idx_of_window(*window).is_some())
+            .collect::<Vec<_>>();
+        let never_focused = self
             .workspaces
             .iter()
-            .enumerate()
-            .find(|(_, w)| w.id == workspace_id)
-        {
-            idx
+            .flat_map(|workspace| workspace.window_iter())
+            .copied()
+            .filter(|window| {
+                !candidates.contains(window) && self.workspace_idx_of_window(*window).is_some()
+            })
+            .collect::<Vec<_>>();
+        candidates.extend(never_focused);
+        let original_window = self
+            .workspaces
+            .get(self.focused_workspace_idx)
+            .unwrap()
+            .focused_window();
+        if let Some(original_window) = original_window {
+            candidates.retain(|window| *window != original_window);
+            candidates.insert(0, original_window);
+        }
+        if candidates.len() < 2 {
+            return false;
+        }
+        self.window_switcher = Some(WindowSwitcher {
+            candidates,
+            idx: 0,
+            original_workspace_id: self.workspaces.get(self.focused_workspace_idx).unwrap().id,
+            original_window,
+            original_prev_focused_workspace_id: self.prev_focused_workspace_id,
+        });
+        true
+    }
+
+    /// Previews the next (or previous) candidate of the window switcher
+    pub fn handle_cycle_windows(
+        &mut self,
+        conn: &Connection,
+        config: &Config,
+        forward: bool,
+        unix_clients: &mut UnixClients,
+    ) {
+        let Some(window_switcher) = &mut self.window_switcher else {
+            return;
+        };
+        if window_switcher.candidates.is_empty() {
+            return;
+        }
+        let count = window_switcher.candidates.len();
+        window_switcher.idx = if forward {
+            (window_switcher.idx + 1) % count
         } else {
-            self.workspaces.push(Workspace::new(workspace_id, false));
-            self.workspaces.len() - 1
+            (window_switcher.idx + count - 1) % count
         };
-        self.focused_workspace_idx = new_focused_workspace_idx;
+        let window = window_switcher.candidates[window_switcher.idx];
+        trace!("window switcher preview: {}", window);
+        self.focus_window_anywhere(conn, config, window, unix_clients);
+    }
+
+    /// Keeps the previewed window focused and makes it the most recently used one
+    pub fn commit_window_switcher(&mut self) {
+        let Some(window_switcher) = self.window_switcher.take() else {
+            return;
+        };
+        let focused_workspace_id = self.workspaces.get(self.focused_workspace_idx).unwrap().id;
+        if focused_workspace_id != window_switcher.original_workspace_id {
+            self.prev_focused_workspace_id = Some(window_switcher.original_workspace_id);
+        } else {
+            self.prev_focused_workspace_id = window_switcher.original_prev_focused_workspace_id;
+        }
+        if let Some(&window) = window_switcher.candidates.get(window_switcher.idx) {
+            self.window_history.retain(|w| *w != window);
+            self.window_history.push(window);
+        }
+    }
+
+    /// Returns to the workspace and window focused when the switcher started
+    pub fn cancel_window_switcher(
+        &mut self,
+        conn: &Connection,
+        config: &Config,
+        unix_clients: &mut UnixClients,
+    ) {
+        let Some(window_switcher) = self.window_switcher.take() else {
+            return;
+        };
+        self.handle_switch_to_workspace(
+            conn,
+            config,
+            window_switcher.original_workspace_id,
+            unix_clients,
+        );
+        if let Some(window) = window_switcher.original_window {
+            let avail_rect = self
+                .rect
+                .available_rect_after_adding_rects(self.docked.rect_iter());
+            self.workspaces
+                .get_mut(self.focused_workspace_idx)
+                .unwrap()
+                .focus_window_via_keyboard(window, conn, config, &avail_rect);
+        }
+        self.prev_focused_workspace_id = window_switcher.original_prev_focused_workspace_id;
+        conn.flush();
+    }
+
+    /// Switches to the workspace of a tiled or floating window and focuses it
+    fn focus_window_anywhere(
+        &mut self,
+        conn: &Connection,
+        config: &Config,
+        window: xcb_window_t,
+        unix_clients: &mut UnixClients,
+    ) {
+        let Some(workspace_idx) = self.workspace_idx_of_window(window) else {
+            return;
+        };
+        let workspace_id = self.workspaces.get(workspace_idx).unwrap().id;
+        self.handle_switch_to_workspace(conn, config, workspace_id, unix_clients);
+        let avail_rect = self
+            .rect
+            .available_rect_after_adding_rects(self.docked.rect_iter());
         self.workspaces
             .get_mut(self.focused_workspace_idx)
             .unwrap()
-            .show_all_windows(&avail_rect, conn, config);
-
+            .focus_window_via_keyboard(window, conn, config, &avail_rect);
         conn.flush();
+    }
 
-        unix_clients.notify_all(Message::WorkspaceList(
-            self.workspaces
-                .iter()
-                .map(|workspace| workspace.id)
-                .collect(),
-        ));
-        unix_clients.notify_all(Message::WorkspaceActive(workspace_id));
+    /// Index of the workspace a window is tiled or floating on
+    fn workspace_idx_of_window(&self, window: xcb_window_t) -> Option<usize> {
+        self.workspaces.iter().position(|workspace| {
+            workspace
+                .find_window_info_by_xcb_id(window)
+                .is_some_and(|(_, window_type)| window_type != WindowType::Docked)
+        })
     }
 
     pub fn handle_move_focused_window_to_workspace(
@@ -490,17 +879,7 @@ impl Monitor {
             .unwrap()
             .pop_focused_window(&avail_rect, conn, config)
         {
-            let new_focused_workspace_idx = if let Some((idx, _)) = self
-                .workspaces
-                .iter()
-                .enumerate()
-                .find(|(_, w)| w.id == workspace_id)
-            {
-                idx
-            } else {
-                self.workspaces.push(Workspace::new(workspace_id, false));
-                self.workspaces.len() - 1
-            };
+            let new_focused_workspace_idx = self.workspace_idx_or_create(workspace_id);
             match window_type {
                 WindowType::Normal => {
                     self.workspaces
@@ -519,7 +898,15 @@ impl Monitor {
                     self.workspaces
                         .get_mut(new_focused_workspace_idx)
                         .unwrap()
-                        .handle_existing_floating_window(window, &rect, &avail_rect, conn, config);
+                        .handle_existing_floating_window(
+                            window,
+                            &rect,
+                            &avail_rect,
+                            focused_workspace_id,
+                            true,
+                            conn,
+                            config,
+                        );
                 }
                 WindowType::Docked => todo!(),
             }
@@ -534,17 +921,13 @@ impl Monitor {
                         workspace_id < focused_workspace_id,
                     );
                 self.focused_workspace_idx = new_focused_workspace_idx;
+                self.prev_focused_workspace_id = Some(focused_workspace_id);
                 self.workspaces
                     .get_mut(self.focused_workspace_idx)
                     .unwrap()
                     .show_all_windows(&avail_rect, conn, config);
 
-                unix_clients.notify_all(Message::WorkspaceList(
-                    self.workspaces
-                        .iter()
-                        .map(|workspace| workspace.id)
-                        .collect(),
-                ));
+                self.notify_workspace_list(conn, unix_clients);
                 unix_clients.notify_all(Message::WorkspaceActive(workspace_id));
             }
             conn.flush();
@@ -570,6 +953,61 @@ impl Monitor {
         conn.flush();
     }
 
+    pub fn handle_toggle_floating(&mut self, conn: &Connection, config: &Config) {
+        let avail_rect = self
+            .rect
+            .available_rect_after_adding_rects(self.docked.rect_iter());
+        self.workspaces
+            .get_mut(self.focused_workspace_idx)
+            .unwrap()
+            .toggle_focused_window_floating(&avail_rect, conn, config);
+        conn.flush();
+    }
+
+    pub fn handle_scroll_strip(
+        &mut self,
+        conn: &Connection,
+        config: &Config,
+        direction: Direction,
+        pixels: i32,
+    ) {
+        let avail_rect = self
+            .rect
+            .available_rect_after_adding_rects(self.docked.rect_iter());
+        self.workspaces
+            .get_mut(self.focused_workspace_idx)
+            .unwrap()
+            .scroll_strip(direction, pixels, conn, config, &avail_rect);
+        conn.flush();
+    }
+
+    pub fn handle_consume_window(
+        &mut self,
+        conn: &Connection,
+        config: &Config,
+        direction: Direction,
+    ) {
+        let avail_rect = self
+            .rect
+            .available_rect_after_adding_rects(self.docked.rect_iter());
+        self.workspaces
+            .get_mut(self.focused_workspace_idx)
+            .unwrap()
+            .consume_window(direction, conn, config, &avail_rect);
+        conn.flush();
+    }
+
+    pub fn handle_expel_window(&mut self, conn: &Connection, config: &Config) {
+        let avail_rect = self
+            .rect
+            .available_rect_after_adding_rects(self.docked.rect_iter());
+        self.workspaces
+            .get_mut(self.focused_workspace_idx)
+            .unwrap()
+            .expel_window(conn, config, &avail_rect);
+        conn.flush();
+    }
+
     pub fn center_focused_window(&mut self, conn: &Connection, config: &Config) {
         let avail_rect = self
             .rect
@@ -658,21 +1096,48 @@ impl Monitor {
             .handle_motion_notify(x, y, window, state, conn, config, &avail_rect);
     }
 
-    pub fn handle_button_press(
+    /// Whether the point lies on the border of a managed window of the focused workspace,
+    /// None when the window isn't managed there
+    pub fn is_on_window_border(
+        &self,
+        window: xcb_window_t,
+        root_x: i32,
+        root_y: i32,
+        config: &Config,
+    ) -> Option<bool> {
+        self.workspaces
+            .get(self.focused_workspace_idx)
+            .unwrap()
+            .is_on_window_border(window, root_x, root_y, config)
+    }
+
+    pub fn focus_window(&mut self, window: xcb_window_t, conn: &Connection, config: &Config) {
+        self.workspaces
+            .get_mut(self.focused_workspace_idx)
+            .unwrap()
+            .focus_window(window, conn, config);
+        conn.flush();
+    }
+
+    /// Starts moving or resizing the window with the pointer, false if the window can't be
+    #[allow(clippy::too_many_arguments)]
+    pub fn start_mouse_interaction(
         &mut self,
-        x: i32,
-        y: i32,
         window: xcb_window_t,
-        state: u16,
-        detail: xcb_button_t,
+        button: xcb_button_t,
+        root_x: i32,
+        root_y: i32,
+        resize: bool,
         conn: &Connection,
         config: &Config,
-        time: xcb_timestamp_t,
-    ) {
-        self.workspaces
+    ) -> bool {
+        let started = self
+            .workspaces
             .get_mut(self.focused_workspace_idx)
             .unwrap()
-            .handle_button_press(x, y, window, state, detail, conn, config, time);
+            .start_mouse_interaction(window, button, root_x, root_y, resize, conn, config);
+        conn.flush();
+        started
     }
 
     pub fn handle_button_release(&mut self, conn: &Connection) {
@@ -682,6 +1147,159 @@ impl Monitor {
             .handle_button_release(conn);
     }
 
+    /// Replaces names that came from the previous config, names set with `rename_workspace` are
+    /// kept unless the new config names the same workspace
+    pub fn update_workspace_names(
+        &mut self,
+        old_names: &BTreeMap<u32, String>,
+        new_names: &BTreeMap<u32, String>,
+        conn: &Connection,
+        unix_clients: &mut UnixClients,
+    ) {
+        for (workspace_id, old_name) in old_names {
+            if self.workspace_names.get(workspace_id) == Some(old_name) {
+                self.workspace_names.remove(workspace_id);
+            }
+        }
+        for (workspace_id, new_name) in new_names {
+            self.workspace_names.retain(|_, name| name != new_name);
+            self.workspace_names.insert(*workspace_id, new_name.clone());
+        }
+        self.notify_workspace_list(conn, unix_clients);
+    }
+
+    pub fn handle_rename_workspace(
+        &mut self,
+        conn: &Connection,
+        name: Option<String>,
+        unix_clients: &mut UnixClients,
+    ) -> Result<(), String> {
+        let focused_workspace_id = self.workspaces.get(self.focused_workspace_idx).unwrap().id;
+        match name {
+            Some(name) => {
+                if let Some((workspace_id, _)) =
+                    self.workspace_names
+                        .iter()
+                        .find(|(workspace_id, other_name)| {
+                            **workspace_id != focused_workspace_id && **other_name == name
+                        })
+                {
+                    return Err(format!(
+                        "workspace name {} is already used by workspace {}",
+                        name, workspace_id
+                    ));
+                }
+                self.workspace_names.insert(focused_workspace_id, name);
+            }
+            None => {
+                self.workspace_names.remove(&focused_workspace_id);
+            }
+        }
+        self.notify_workspace_list(conn, unix_clients);
+        Ok(())
+    }
+
+    pub fn workspace_id_from_target(&self, target: &WorkspaceTarget) -> Result<u32, String> {
+        match target {
+            WorkspaceTarget::Id(workspace_id) => Ok(*workspace_id),
+            WorkspaceTarget::Name(name) => self
+                .workspace_names
+                .iter()
+                .find(|(_, workspace_name)| *workspace_name == name)
+                .map(|(workspace_id, _)| *workspace_id)
+                .ok_or_else(|| format!("no workspace named {}", name)),
+        }
+    }
+
+    /// Ids of the existing workspaces with their names, empty for unnamed ones
+    pub fn named_workspace_list(&self) -> Vec<(u32, &str)> {
+        self.workspaces
+            .iter()
+            .map(|workspace| {
+                let name = self.workspace_names.get(&workspace.id);
+                (workspace.id, name.map_or("", String::as_str))
+            })
+            .collect()
+    }
+
+    /// Sends the workspace list to unix clients and publishes _NET_DESKTOP_NAMES, where the
+    /// n-th desktop is the workspace with id n + 1
+    fn notify_workspace_list(&self, conn: &Connection, unix_clients: &mut UnixClients) {
+        let named_workspace_list = self.named_workspace_list();
+        unix_clients.notify_all(Message::WorkspaceList(
+            named_workspace_list.iter().map(|(id, _)| *id).collect(),
+        ));
+        unix_clients.notify_all(Message::WorkspaceListNamed(named_workspace_list));
+
+        let last_workspace_id = self
+            .workspaces
+            .iter()
+            .map(|workspace| workspace.id)
+            .chain(self.workspace_names.keys().copied())
+            .fold(9, u32::max);
+        let desktop_names = (1..=last_workspace_id)
+            .map(
+                |workspace_id| match self.workspace_names.get(&workspace_id) {
+                    Some(name) => name.clone(),
+                    None => workspace_id.to_string(),
+                },
+            )
+            .collect::<Vec<_>>();
+        conn.set_desktop_names(&desktop_names);
+    }
+
+    /// With `dynamic_workspaces` removes the empty workspaces other than the focused one and
+    /// keeps exactly one empty workspace after the last one
+    pub fn update_dynamic_workspaces(
+        &mut self,
+        conn: &Connection,
+        config: &Config,
+        unix_clients: &mut UnixClients,
+    ) {
+        if !config.dynamic_workspaces {
+            return;
+        }
+        let workspace_ids = self
+            .workspaces
+            .iter()
+            .map(|workspace| workspace.id)
+            .collect::<Vec<_>>();
+        let focused_workspace_id = workspace_ids[self.focused_workspace_idx];
+        let last_workspace_id = *workspace_ids.last().unwrap();
+        self.workspaces.retain(|workspace| {
+            workspace.id == focused_workspace_id
+                || workspace.id == last_workspace_id
+                || !workspace.is_empty()
+        });
+        // the trailing empty workspace, unless the focused one is already that
+        if let [.., before_last, last] = self.workspaces.as_slice()
+            && last.is_empty()
+            && before_last.is_empty()
+            && last.id != focused_workspace_id
+        {
+            self.workspaces.pop();
+        }
+        let last_workspace = self.workspaces.last().unwrap();
+        if !last_workspace.is_empty() {
+            self.workspaces
+                .push(Workspace::new(last_workspace.id + 1, false));
+        }
+        self.focused_workspace_idx = self
+            .workspaces
+            .iter()
+            .position(|workspace| workspace.id == focused_workspace_id)
+            .unwrap();
+
+        if self
+            .workspaces
+            .iter()
+            .map(|workspace| workspace.id)
+            .ne(workspace_ids)
+        {
+            self.notify_workspace_list(conn, unix_clients);
+        }
+    }
+
     pub fn get_focused_workspace_id(&self) -> Option<u32> {
         if let Some(workspace) = self.workspaces.get(self.focused_workspace_idx) {
             Some(workspace.id)
@@ -690,6 +1308,47 @@ impl Monitor {
         }
     }
 
+    /// Rects tiled and floating windows of all workspaces are configured with
+    pub fn window_geometries(&self, config: &Config) -> Vec<WindowGeometry> {
+        self.workspaces
+            .iter()
+            .flat_map(|workspace| workspace.window_geometries(config))
+            .collect()
+    }
+
+    /// Notifies unix clients about windows opened/closed and focused workspace geometry changes
+    /// since the last call
+    pub fn notify_state_changes(&mut self, config: &Config, unix_clients: &mut UnixClients) {
+        let windows = self
+            .docked
+            .window_iter()
+            .chain(self.workspaces.iter().flat_map(|w| w.window_iter()))
+            .copied()
+            .collect::<Vec<_>>();
+        for window in windows
+            .iter()
+            .filter(|w| !self.notified_windows.contains(w))
+        {
+            unix_clients.notify_all(Message::WindowOpened(*window));
+        }
+        for window in self
+            .notified_windows
+            .iter()
+            .filter(|w| !windows.contains(w))
+        {
+            unix_clients.notify_all(Message::WindowClosed(*window));
+        }
+        self.notified_windows = windows;
+
+        if let Some(workspace) = self.workspaces.get(self.focused_workspace_idx) {
+            let layout = workspace.layout(config);
+            if layout != self.notified_layout {
+                unix_clients.notify_all(Message::LayoutChanged(workspace.id));
+                self.notified_layout = layout;
+            }
+        }
+    }
+
     pub fn handle_destroy_notify(
         &mut self,
         window: xcb_window_t,
@@ -697,6 +1356,17 @@ impl Monitor {
         config: &Config,
     ) {
         trace!("destroy notify for window: {}", window);
+        self.sticky_windows.retain(|w| *w != window);
+        self.border_colors.remove(&window);
+        self.window_history.retain(|w| *w != window);
+        if let Some(window_switcher) = &mut self.window_switcher
+            && let Some(idx) = window_switcher.candidates.iter().position(|w| *w == window)
+        {
+            window_switcher.candidates.remove(idx);
+            if window_switcher.idx > idx {
+                window_switcher.idx -= 1;
+            }
+        }
         if let Some((index, _)) = self
             .docked
             .window_iter()
@@ -721,7 +1391,14 @@ impl Monitor {
                 let avail_rect = self
                     .rect
                     .available_rect_after_adding_rects(self.docked.rect_iter());
-                workspace.handle_destroy_notify(index, window_type, conn, config, &avail_rect);
+                workspace.handle_destroy_notify(
+                    window,
+                    index,
+                    window_type,
+                    conn,
+                    config,
+                    &avail_rect,
+                );
                 break;
             }
         }
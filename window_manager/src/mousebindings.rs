@@ -0,0 +1,249 @@
+use std::cmp::Reverse;
+
+use log::{error, trace};
+use x11_bindings::{
+    bindings::{
+        XCB_BUTTON_MASK_1, XCB_MOD_MASK_2, XCB_MOD_MASK_LOCK, XCB_NONE, xcb_button_t,
+        xcb_mod_mask_t, xcb_timestamp_t, xcb_window_t,
+    },
+    connection::Connection,
+};
+
+use crate::{
+    bar_message::UnixClients,
+    config::Config,
+    keybindings::{
+        KeybindingAction, Keybindings, execute_keybinding_action, keybinding_action_from_str,
+        modifier_from_str,
+    },
+    monitor::Monitor,
+};
+
+/// Used when the config has no `mousebindings`
+const DEFAULT_MOUSEBINDINGS: [&str; 2] = ["Alt+Button1 move", "Alt+Button3 resize"];
+
+/// Where a click has to land for a mouse binding to trigger
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MouseContext {
+    /// inside a tiled or floating window, also used for its border without a border binding
+    Window,
+    /// on the border of a tiled or floating window
+    Border,
+    /// on the bare root window
+    Root,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MouseAction {
+    /// drag a floating window while the button is held
+    Move,
+    /// resize a floating window while the button is held
+    Resize,
+    /// focus the clicked window, the click is still passed on to it
+    Focus,
+    /// window and border bindings focus the clicked window before running the action
+    Action(KeybindingAction),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Mousebinding {
+    pub modifiers: xcb_mod_mask_t,
+    pub modifiers_count: usize,
+    pub button: xcb_button_t,
+    pub context: MouseContext,
+    pub action: MouseAction,
+}
+
+impl Mousebinding {
+    fn matches(&self, modifiers: xcb_mod_mask_t, button: xcb_button_t) -> bool {
+        (modifiers & self.modifiers) == self.modifiers && self.button == button
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn handle_button_press(
+    mousebindings: &[Mousebinding],
+    keybindings: &mut Keybindings,
+    conn: &Connection,
+    config: &Config,
+    monitor: &mut Monitor,
+    root_x: i32,
+    root_y: i32,
+    child: xcb_window_t,
+    state: u16,
+    button: xcb_button_t,
+    time: xcb_timestamp_t,
+    unix_clients: &mut UnixClients,
+) {
+    trace!(
+        "handle button press, state: {}, button: {}, child: {}",
+        state, button, child
+    );
+    // modifier bits are the ones below the button masks
+    let modifiers = state as u32 & (XCB_BUTTON_MASK_1 - 1) & !(XCB_MOD_MASK_2 | XCB_MOD_MASK_LOCK);
+    let contexts: &[MouseContext] = if child == XCB_NONE {
+        &[MouseContext::Root]
+    } else {
+        match monitor.is_on_window_border(child, root_x, root_y, config) {
+            Some(true) => &[MouseContext::Border, MouseContext::Window],
+            Some(false) => &[MouseContext::Window],
+            None => &[],
+        }
+    };
+    let mousebinding = contexts.iter().find_map(|context| {
+        mousebindings.iter().find(|mousebinding| {
+            mousebinding.context == *context && mousebinding.matches(modifiers, button)
+        })
+    });
+    let consumed = match mousebinding.map(|mousebinding| &mousebinding.action) {
+        Some(MouseAction::Move) => {
+            monitor.start_mouse_interaction(child, button, root_x, root_y, false, conn, config)
+        }
+        Some(MouseAction::Resize) => {
+            monitor.start_mouse_interaction(child, button, root_x, root_y, true, conn, config)
+        }
+        Some(MouseAction::Focus) => {
+            monitor.focus_window(child, conn, config);
+            false
+        }
+        Some(MouseAction::Action(action)) => {
+            if child != XCB_NONE {
+                monitor.focus_window(child, conn, config);
+            }
+            if let Err(err) =
+                execute_keybinding_action(action, keybindings, conn, config, monitor, unix_clients)
+            {
+                error!("{}", err);
+            }
+            true
+        }
+        None => false,
+    };
+    // clicks the window manager didn't act upon still reach the window below the pointer
+    if consumed {
+        conn.allow_events_async(time);
+    } else {
+        conn.allow_events(time);
+    }
+    conn.flush();
+}
+
+/// Root bindings aren't grabbed, presses on the root window itself are selected instead
+pub fn mousebindings_grab(mousebindings: &[Mousebinding], conn: &Connection) {
+    for mousebinding in mousebindings
+        .iter()
+        .filter(|mousebinding| mousebinding.context != MouseContext::Root)
+    {
+        let (modifiers, button) = (mousebinding.modifiers, mousebinding.button);
+        conn.grab_button(modifiers, button);
+        conn.grab_button(modifiers | XCB_MOD_MASK_2, button);
+        conn.grab_button(modifiers | XCB_MOD_MASK_LOCK, button);
+        conn.grab_button(modifiers | XCB_MOD_MASK_2 | XCB_MOD_MASK_LOCK, button);
+    }
+}
+
+pub fn mousebindings_ungrab(mousebindings: &[Mousebinding], conn: &Connection) {
+    for mousebinding in mousebindings
+        .iter()
+        .filter(|mousebinding| mousebinding.context != MouseContext::Root)
+    {
+        let (modifiers, button) = (mousebinding.modifiers, mousebinding.button);
+        conn.ungrab_button(modifiers, button);
+        conn.ungrab_button(modifiers | XCB_MOD_MASK_2, button);
+        conn.ungrab_button(modifiers | XCB_MOD_MASK_LOCK, button);
+        conn.ungrab_button(modifiers | XCB_MOD_MASK_2 | XCB_MOD_MASK_LOCK, button);
+    }
+}
+
+pub fn mousebindings_from_config(config: &Config) -> Vec<Mousebinding> {
+    let mut mousebindings = match &config.mousebindings {
+        Some(mousebinding_strs) => mousebinding_strs
+            .iter()
+            .filter_map(|mousebinding_str| mousebinding_from_string(mousebinding_str))
+            .collect::<Vec<_>>(),
+        None => DEFAULT_MOUSEBINDINGS
+            .iter()
+            .filter_map(|mousebinding_str| mousebinding_from_string(mousebinding_str))
+            .collect::<Vec<_>>(),
+    };
+    mousebindings.sort_by_key(|e| Reverse(e.modifiers_count));
+
+    mousebindings
+}
+
+/// Buttons are `Button1` to `Button9` or named: left, middle, right, scroll_up, scroll_down,
+/// scroll_left and scroll_right
+fn button_from_str(button_str: &str) -> Option<xcb_button_t> {
+    match button_str.to_lowercase().as_str() {
+        "left" => Some(1),
+        "middle" => Some(2),
+        "right" => Some(3),
+        "scroll_up" => Some(4),
+        "scroll_down" => Some(5),
+        "scroll_left" => Some(6),
+        "scroll_right" => Some(7),
+        button_str => button_str
+            .strip_prefix("button")?
+            .parse::<xcb_button_t>()
+            .ok()
+            .filter(|button| (1..=9).contains(button)),
+    }
+}
+
+/// `[--root|--border] Modifiers+Button action`, e.g. `Alt+Button1 move` or
+/// `--root Button4 switch_to_workspace prev`. Actions are move, resize, focus or any action
+/// accepted by keybindings
+fn mousebinding_from_string(mousebinding_str: &str) -> Option<Mousebinding> {
+    let mousebinding_str = mousebinding_str.trim();
+    let (context, rest) = match mousebinding_str.split_once(char::is_whitespace) {
+        Some(("--root", rest)) => (MouseContext::Root, rest.trim_start()),
+        Some(("--border", rest)) => (MouseContext::Border, rest.trim_start()),
+        _ => (MouseContext::Window, mousebinding_str),
+    };
+    let (buttons_comb_str, action_str) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+
+    let mut modifiers: xcb_mod_mask_t = 0;
+    let mut modifiers_count: usize = 0;
+    let buttons_comb = buttons_comb_str.split('+').collect::<Vec<_>>();
+    let (button_str, mod_strs) = buttons_comb.split_last().unwrap();
+    for mod_str in mod_strs {
+        if let Some(modifier) = modifier_from_str(mod_str) {
+            modifiers |= modifier;
+            modifiers_count += 1;
+        } else {
+            error!("failed to extract modifier from string: {}", mod_str);
+            return None;
+        }
+    }
+    let Some(button) = button_from_str(button_str) else {
+        error!("failed to extract button from string: {}", button_str);
+        return None;
+    };
+
+    let action = match action_str.trim() {
+        "" => {
+            error!(
+                "failed to find command in mousebinding: {}",
+                mousebinding_str
+            );
+            return None;
+        }
+        "move" => MouseAction::Move,
+        "resize" => MouseAction::Resize,
+        "focus" => MouseAction::Focus,
+        action_str => match keybinding_action_from_str(action_str) {
+            Ok(action) => MouseAction::Action(action),
+            Err(err) => {
+                error!("{}, mousebinding: {}", err, mousebinding_str);
+                return None;
+            }
+        },
+    };
+    Some(Mousebinding {
+        modifiers,
+        modifiers_count,
+        button,
+        context,
+        action,
+    })
+}
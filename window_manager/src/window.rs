@@ -1,6 +1,7 @@
 use std::ops::RangeBounds;
 
 use base::Rect;
+use serde::Serialize;
 use x11_bindings::bindings::xcb_window_t;
 
 #[allow(dead_code)]
@@ -11,9 +12,16 @@ pub struct Window {
     pub visible: bool,
 }
 
-#[derive(Debug)]
+/// Columns of windows, each with one rect. Tiled columns can hold several windows stacked
+/// top to bottom within the rect, floating and docked ones always hold a single window
+#[derive(Debug, Serialize)]
 pub struct WindowsCollection {
+    /// focused, or last focused, window of each column
     windows: Vec<xcb_window_t>,
+    columns: Vec<Vec<xcb_window_t>>,
+    /// share of the column height of each window in `columns`, 1.0 on average
+    #[serde(skip)]
+    height_shares: Vec<Vec<f32>>,
     rects: Vec<Rect>,
     visibles: Vec<bool>,
 }
@@ -21,15 +29,21 @@ pub struct WindowsCollection {
 impl WindowsCollection {
     pub fn new(reserved_capacity: usize) -> Self {
         let mut windows = Vec::new();
+        let mut columns = Vec::new();
+        let mut height_shares = Vec::new();
         let mut rects = Vec::new();
         let mut visibles = Vec::new();
         if reserved_capacity > 0 {
             windows.reserve(reserved_capacity);
+            columns.reserve(reserved_capacity);
+            height_shares.reserve(reserved_capacity);
             rects.reserve(reserved_capacity);
             visibles.reserve(reserved_capacity);
         }
         Self {
             windows,
+            columns,
+            height_shares,
             rects,
             visibles,
         }
@@ -39,6 +53,11 @@ impl WindowsCollection {
         let mut indices: Vec<usize> = (0..self.rects.len()).collect();
         indices.sort_by_key(|&i| self.rects[i].x);
         self.windows = indices.iter().map(|&i| self.windows[i].clone()).collect();
+        self.columns = indices.iter().map(|&i| self.columns[i].clone()).collect();
+        self.height_shares = indices
+            .iter()
+            .map(|&i| self.height_shares[i].clone())
+            .collect();
         self.rects = indices.iter().map(|&i| self.rects[i].clone()).collect();
         self.visibles = indices.iter().map(|&i| self.visibles[i].clone()).collect();
     }
@@ -47,6 +66,11 @@ impl WindowsCollection {
         let mut indices: Vec<usize> = (0..self.rects.len()).collect();
         indices.sort_by_key(|&i| self.rects[i].y);
         self.windows = indices.iter().map(|&i| self.windows[i].clone()).collect();
+        self.columns = indices.iter().map(|&i| self.columns[i].clone()).collect();
+        self.height_shares = indices
+            .iter()
+            .map(|&i| self.height_shares[i].clone())
+            .collect();
         self.rects = indices.iter().map(|&i| self.rects[i].clone()).collect();
         self.visibles = indices.iter().map(|&i| self.visibles[i].clone()).collect();
     }
@@ -54,19 +78,194 @@ impl WindowsCollection {
     #[inline]
     pub fn add(&mut self, window: xcb_window_t, rect: Rect, visible: bool) {
         self.windows.push(window);
+        self.columns.push(vec![window]);
+        self.height_shares.push(vec![1.0]);
         self.rects.push(rect);
         self.visibles.push(visible);
     }
 
+    /// Removes the active window of the column, see `remove_window_at`
     #[inline]
     pub fn remove_at(&mut self, index: usize) -> (xcb_window_t, Rect, bool) {
+        self.remove_window_at(index, self.windows[index])
+    }
+
+    /// Removes a window of the column at `index`, the column itself only goes away with its last
+    /// window. The window above the removed one, or the new top one, becomes active
+    pub fn remove_window_at(
+        &mut self,
+        index: usize,
+        window: xcb_window_t,
+    ) -> (xcb_window_t, Rect, bool) {
+        let column = &mut self.columns[index];
+        if column.len() > 1 {
+            let row = column.iter().position(|w| *w == window).unwrap();
+            column.remove(row);
+            self.height_shares[index].remove(row);
+            if self.windows[index] == window {
+                self.windows[index] = column[row.saturating_sub(1)];
+            }
+            return (window, self.rects[index].clone(), self.visibles[index]);
+        }
+        self.windows.remove(index);
+        self.columns.remove(index);
+        self.height_shares.remove(index);
         (
-            self.windows.remove(index),
+            window,
             self.rects.remove(index),
             self.visibles.remove(index),
         )
     }
 
+    /// Adds a window at the bottom of the column at `index` and makes it active
+    pub fn stack_window_at(&mut self, index: usize, window: xcb_window_t) {
+        self.columns[index].push(window);
+        self.height_shares[index].push(1.0);
+        self.windows[index] = window;
+    }
+
+    /// Makes a window of the column at `index` its active one
+    pub fn set_active_window_at(&mut self, index: usize, window: xcb_window_t) {
+        if self.columns[index].contains(&window) {
+            self.windows[index] = window;
+        }
+    }
+
+    /// Windows of the column at `index` top to bottom
+    #[inline]
+    pub fn column(&self, index: usize) -> &[xcb_window_t] {
+        &self.columns[index]
+    }
+
+    /// Window next to the active one of the column at `index`, above it when `above` is set
+    pub fn column_neighbour(&self, index: usize, above: bool) -> Option<xcb_window_t> {
+        let column = &self.columns[index];
+        let row = column.iter().position(|w| *w == self.windows[index])?;
+        if above {
+            row.checked_sub(1).map(|row| column[row])
+        } else {
+            column.get(row + 1).copied()
+        }
+    }
+
+    /// Swaps the active window of the column at `index` with its neighbour above or below,
+    /// false if there is none
+    pub fn swap_in_column(&mut self, index: usize, above: bool) -> bool {
+        let column = &mut self.columns[index];
+        let row = column
+            .iter()
+            .position(|w| *w == self.windows[index])
+            .unwrap();
+        let target_row = if above {
+            match row.checked_sub(1) {
+                Some(target_row) => target_row,
+                None => return false,
+            }
+        } else if row + 1 < column.len() {
+            row + 1
+        } else {
+            return false;
+        };
+        column.swap(row, target_row);
+        self.height_shares[index].swap(row, target_row);
+        true
+    }
+
+    /// Grows the active window of the column at `index` by `pixels`, or shrinks it when
+    /// negative. The windows next to it absorb the difference, the nearest ones first, and none
+    /// gets shorter than `min_height`. False if nothing changed
+    pub fn resize_window_height_at(
+        &mut self,
+        index: usize,
+        pixels: i32,
+        spacing: u32,
+        min_height: u32,
+    ) -> bool {
+        let column = &self.columns[index];
+        let Some(row) = column.iter().position(|w| *w == self.windows[index]) else {
+            return false;
+        };
+        if column.len() < 2 {
+            return false;
+        }
+        let mut heights = self.column_window_heights(index, spacing);
+        let count = heights.len() as u32;
+        let total_height: u32 = heights.iter().sum();
+        let max_height = total_height.saturating_sub(min_height * (count - 1));
+        let new_height = (heights[row] as i64 + pixels as i64)
+            .clamp(min_height.min(max_height) as i64, max_height as i64)
+            as u32;
+        if new_height == heights[row] {
+            return false;
+        }
+
+        // nearest rows first, the one below before the one above at the same distance
+        let neighbour_rows = (1..heights.len())
+            .flat_map(|distance| [row.checked_add(distance), row.checked_sub(distance)])
+            .flatten()
+            .filter(|neighbour_row| *neighbour_row < heights.len())
+            .collect::<Vec<_>>();
+        if new_height > heights[row] {
+            let mut remaining = new_height - heights[row];
+            for neighbour_row in neighbour_rows {
+                let taken = remaining.min(heights[neighbour_row].saturating_sub(min_height));
+                heights[neighbour_row] -= taken;
+                remaining -= taken;
+            }
+            heights[row] = new_height - remaining;
+        } else {
+            heights[neighbour_rows[0]] += heights[row] - new_height;
+            heights[row] = new_height;
+        }
+
+        let total_height = total_height.max(1) as f32;
+        self.height_shares[index] = heights
+            .iter()
+            .map(|height| *height as f32 * count as f32 / total_height)
+            .collect();
+        true
+    }
+
+    /// Heights of the windows of the column at `index` top to bottom, its height without the
+    /// `spacing` between them is split by the height shares, the last window takes the remainder
+    fn column_window_heights(&self, index: usize, spacing: u32) -> Vec<u32> {
+        let shares = &self.height_shares[index];
+        let count = shares.len() as u32;
+        let avail_height = self.rects[index]
+            .height
+            .saturating_sub(spacing * count.saturating_sub(1));
+        let total_share: f64 = shares.iter().map(|share| *share as f64).sum();
+        let mut heights = shares
+            .iter()
+            .map(|share| (avail_height as f64 * *share as f64 / total_share).round() as u32)
+            .collect::<Vec<_>>();
+        if let Some((last, above)) = heights.split_last_mut() {
+            *last = avail_height.saturating_sub(above.iter().sum());
+        }
+        heights
+    }
+
+    /// Rects of the windows of the column at `index`, splitting its height by the height shares
+    /// of the windows with `spacing` pixels between them
+    pub fn column_window_rects(&self, index: usize, spacing: u32) -> Vec<(xcb_window_t, Rect)> {
+        let rect = &self.rects[index];
+        let mut y = rect.y;
+        self.columns[index]
+            .iter()
+            .zip(self.column_window_heights(index, spacing))
+            .map(|(window, height)| {
+                let window_rect = Rect {
+                    x: rect.x,
+                    y,
+                    width: rect.width,
+                    height,
+                };
+                y += (height + spacing) as i32;
+                (*window, window_rect)
+            })
+            .collect()
+    }
+
     #[inline]
     pub fn at(&self, index: usize) -> Option<(xcb_window_t, &Rect)> {
         if index < self.windows.len() {
@@ -137,9 +336,12 @@ impl WindowsCollection {
         self.rects[index] = new_rect;
     }
 
+    /// Swaps whole columns, rects are swapped separately
     #[inline]
     pub fn swap_windows(&mut self, index_lhs: usize, index_rhs: usize) {
         self.windows.swap(index_lhs, index_rhs);
+        self.columns.swap(index_lhs, index_rhs);
+        self.height_shares.swap(index_lhs, index_rhs);
     }
 
     #[inline]
@@ -180,9 +382,15 @@ impl WindowsCollection {
         self.rects.iter_mut()
     }
 
+    /// All windows, including the ones stacked below or above the active window of a column
+    #[inline]
+    pub fn window_iter(&self) -> std::iter::Flatten<std::slice::Iter<'_, Vec<xcb_window_t>>> {
+        self.columns.iter().flatten()
+    }
+
     #[inline]
-    pub fn window_iter(&self) -> std::slice::Iter<'_, xcb_window_t> {
-        self.windows.iter()
+    pub fn update_visible_at(&mut self, index: usize, visible: bool) {
+        self.visibles[index] = visible;
     }
 
     #[inline]
@@ -231,13 +439,12 @@ impl WindowsCollection {
         &mut self.rects[start..end]
     }
 
+    /// Index of the column holding the window
     #[inline]
     pub fn index_of(&self, window: xcb_window_t) -> Option<usize> {
-        if let Some((idx, _)) = self.windows.iter().enumerate().find(|(_, w)| **w == window) {
-            Some(idx)
-        } else {
-            None
-        }
+        self.columns
+            .iter()
+            .position(|column| column.contains(&window))
     }
 
     #[inline]
@@ -312,3 +519,161 @@ impl<'a> IntoIterator for &'a WindowsCollection {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column_rect(height: u32) -> Rect {
+        Rect {
+            x: 10,
+            y: 20,
+            width: 300,
+            height,
+        }
+    }
+
+    fn heights(collection: &WindowsCollection, index: usize, spacing: u32) -> Vec<u32> {
+        collection
+            .column_window_rects(index, spacing)
+            .iter()
+            .map(|(_, rect)| rect.height)
+            .collect()
+    }
+
+    #[test]
+    fn remove_last_window_of_column_removes_column() {
+        let mut collection = WindowsCollection::new(0);
+        collection.add(1, column_rect(100), true);
+        collection.add(2, column_rect(100), false);
+        collection.stack_window_at(1, 3);
+
+        let (window, _, _) = collection.remove_window_at(0, 1);
+        assert_eq!(window, 1);
+        assert_eq!(collection.len(), 1);
+        assert_eq!(collection.index_of(1), None);
+        assert_eq!(collection.index_of(2), Some(0));
+        assert_eq!(collection.column(0), &[2, 3]);
+        assert_eq!(collection.at_visible(0), Some(false));
+
+        collection.remove_window_at(0, 3);
+        assert_eq!(collection.len(), 1);
+        let (window, _, visible) = collection.remove_at(0);
+        assert_eq!((window, visible), (2, false));
+        assert!(collection.is_empty());
+        assert_eq!(collection.window_iter().next(), None);
+    }
+
+    #[test]
+    fn active_window_after_removal() {
+        let mut collection = WindowsCollection::new(0);
+        collection.add(1, column_rect(100), true);
+        collection.stack_window_at(0, 2);
+        collection.stack_window_at(0, 3);
+        collection.stack_window_at(0, 4);
+        assert_eq!(collection.index_window(0), 4);
+
+        // the window above the removed active one becomes active
+        collection.set_active_window_at(0, 3);
+        collection.remove_at(0);
+        assert_eq!(collection.index_window(0), 2);
+
+        // removing another window keeps the active one
+        collection.remove_window_at(0, 4);
+        assert_eq!(collection.index_window(0), 2);
+
+        // the new top window becomes active when the top one is removed
+        collection.set_active_window_at(0, 1);
+        collection.remove_at(0);
+        assert_eq!(collection.index_window(0), 2);
+        assert_eq!(collection.column(0), &[2]);
+
+        // a window of another column can't become active
+        collection.set_active_window_at(0, 5);
+        assert_eq!(collection.index_window(0), 2);
+    }
+
+    #[test]
+    fn column_neighbours_and_swaps() {
+        let mut collection = WindowsCollection::new(0);
+        collection.add(1, column_rect(100), true);
+        collection.stack_window_at(0, 2);
+        collection.stack_window_at(0, 3);
+        collection.set_active_window_at(0, 2);
+        assert_eq!(collection.column_neighbour(0, true), Some(1));
+        assert_eq!(collection.column_neighbour(0, false), Some(3));
+
+        assert!(collection.swap_in_column(0, true));
+        assert_eq!(collection.column(0), &[2, 1, 3]);
+        assert_eq!(collection.index_window(0), 2);
+        assert_eq!(collection.column_neighbour(0, true), None);
+        assert!(!collection.swap_in_column(0, true));
+
+        collection.set_active_window_at(0, 3);
+        assert_eq!(collection.column_neighbour(0, false), None);
+        assert!(!collection.swap_in_column(0, false));
+    }
+
+    #[test]
+    fn column_window_rects_split_height_with_spacing() {
+        let mut collection = WindowsCollection::new(0);
+        collection.add(1, column_rect(100), true);
+        assert_eq!(
+            collection.column_window_rects(0, 10),
+            vec![(1, column_rect(100))]
+        );
+
+        collection.stack_window_at(0, 2);
+        collection.stack_window_at(0, 3);
+        let rects = collection.column_window_rects(0, 10);
+        // 80 pixels left after the spacing, the last window takes the remainder
+        let expected = [(1, 20, 27), (2, 57, 27), (3, 94, 26)];
+        assert_eq!(rects.len(), expected.len());
+        for ((window, rect), (expected_window, y, height)) in rects.iter().zip(expected) {
+            assert_eq!(*window, expected_window);
+            assert_eq!((rect.x, rect.width), (10, 300));
+            assert_eq!((rect.y, rect.height), (y, height));
+        }
+    }
+
+    #[test]
+    fn resize_window_height_within_column() {
+        let mut collection = WindowsCollection::new(0);
+        collection.add(1, column_rect(210), true);
+        // a single window fills its column
+        assert!(!collection.resize_window_height_at(0, 20, 10, 10));
+
+        collection.stack_window_at(0, 2);
+        collection.stack_window_at(0, 3);
+        collection.update_rect_at(0, column_rect(320));
+        assert_eq!(heights(&collection, 0, 10), vec![100, 100, 100]);
+
+        // the window above the bottom one gives up the height
+        assert!(collection.resize_window_height_at(0, 30, 10, 10));
+        assert_eq!(heights(&collection, 0, 10), vec![100, 70, 130]);
+
+        // the nearest window below first, then the one above, none below the minimum
+        collection.set_active_window_at(0, 1);
+        assert!(collection.resize_window_height_at(0, 100, 10, 10));
+        assert_eq!(heights(&collection, 0, 10), vec![200, 10, 90]);
+        assert!(collection.resize_window_height_at(0, 500, 10, 10));
+        assert_eq!(heights(&collection, 0, 10), vec![280, 10, 10]);
+        assert!(!collection.resize_window_height_at(0, 1, 10, 10));
+
+        // shrinking gives the height to the window below
+        assert!(collection.resize_window_height_at(0, -80, 10, 10));
+        assert_eq!(heights(&collection, 0, 10), vec![200, 90, 10]);
+
+        // shares follow the windows and scale with the column
+        assert!(collection.swap_in_column(0, false));
+        assert_eq!(collection.column(0), &[2, 1, 3]);
+        assert_eq!(heights(&collection, 0, 10), vec![90, 200, 10]);
+        collection.update_rect_at(0, column_rect(620));
+        assert_eq!(heights(&collection, 0, 10), vec![180, 400, 20]);
+
+        // a stacked window gets the average height share, the others keep their proportions
+        collection.remove_window_at(0, 3);
+        collection.stack_window_at(0, 4);
+        assert_eq!(heights(&collection, 0, 10), vec![138, 308, 154]);
+    }
+}
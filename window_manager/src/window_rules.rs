@@ -0,0 +1,214 @@
+use base::Rect;
+use x11_bindings::bindings::xcb_window_t;
+
+use crate::{
+    config::{Config, WindowRule},
+    connection::Connection,
+};
+
+/// Properties of a window the `[[rule]]` matchers are checked against
+#[derive(Debug)]
+pub struct WindowProperties {
+    pub class: Option<String>,
+    pub instance: Option<String>,
+    pub title: Option<String>,
+    pub role: Option<String>,
+    pub window_type: &'static str,
+    pub transient: bool,
+}
+
+impl WindowProperties {
+    pub fn new(
+        window: xcb_window_t,
+        class_instance: &Option<(String, String)>,
+        conn: &Connection,
+    ) -> Self {
+        let (class, instance) = match class_instance {
+            Some((class, instance)) => (Some(class.clone()), Some(instance.clone())),
+            None => (None, None),
+        };
+        Self {
+            class,
+            instance,
+            title: conn.window_title(window),
+            role: conn.window_role(window),
+            window_type: conn.window_type_name(window),
+            transient: conn.window_transient_for(window).is_some(),
+        }
+    }
+}
+
+/// Actions of all the rules matching a window
+#[derive(Debug, Default)]
+pub struct WindowRuleActions {
+    pub workspace: Option<u32>,
+    pub floating: Option<bool>,
+    pub width_percent: Option<f64>,
+    pub rect: Option<Rect>,
+    pub border_color: Option<u32>,
+    pub no_focus: bool,
+    pub sticky: bool,
+    pub fullscreen: bool,
+    pub opacity: Option<f64>,
+}
+
+/// Merges the actions of the matching rules in config order, so later rules win
+pub fn window_rule_actions(config: &Config, properties: &WindowProperties) -> WindowRuleActions {
+    let mut actions = WindowRuleActions::default();
+    for rule in config
+        .rule
+        .iter()
+        .flatten()
+        .filter(|rule| rule_matches(rule, properties))
+    {
+        actions.workspace = rule.workspace.or(actions.workspace);
+        actions.floating = rule.floating.or(actions.floating);
+        actions.width_percent = rule.width_percent.or(actions.width_percent);
+        actions.rect = rule.rect.clone().or(actions.rect);
+        actions.border_color = rule.border_color_int.or(actions.border_color);
+        actions.no_focus = rule.no_focus.unwrap_or(actions.no_focus);
+        actions.sticky = rule.sticky.unwrap_or(actions.sticky);
+        actions.fullscreen = rule.fullscreen.unwrap_or(actions.fullscreen);
+        actions.opacity = rule.opacity.or(actions.opacity);
+    }
+    actions
+}
+
+fn rule_matches(rule: &WindowRule, properties: &WindowProperties) -> bool {
+    fn matches(expected: &Option<String>, actual: &Option<String>) -> bool {
+        expected.is_none() || expected.as_ref() == actual.as_ref()
+    }
+
+    matches(&rule.class, &properties.class)
+        && matches(&rule.instance, &properties.instance)
+        && matches(&rule.role, &properties.role)
+        && rule
+            .window_type
+            .as_ref()
+            .is_none_or(|window_type| window_type == properties.window_type)
+        && rule
+            .transient
+            .is_none_or(|transient| transient == properties.transient)
+        && rule.title_regex.as_ref().is_none_or(|title_regex| {
+            properties
+                .title
+                .as_ref()
+                .is_some_and(|title| title_regex.is_match(title))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::*;
+
+    fn properties(class: &str, title: Option<&str>) -> WindowProperties {
+        WindowProperties {
+            class: Some(class.to_string()),
+            instance: Some(class.to_lowercase()),
+            title: title.map(str::to_string),
+            role: None,
+            window_type: "normal",
+            transient: false,
+        }
+    }
+
+    fn title_rule(title: &str) -> WindowRule {
+        WindowRule {
+            title: Some(title.to_string()),
+            title_regex: Some(Regex::new(title).unwrap()),
+            ..Default::default()
+        }
+    }
+
+    fn config_with_rules(rules: Vec<WindowRule>) -> Config {
+        Config {
+            rule: Some(rules),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn no_matching_rule() {
+        let rule = WindowRule {
+            class: Some("Slack".to_string()),
+            workspace: Some(5),
+            ..Default::default()
+        };
+        assert!(!rule_matches(&rule, &properties("Alacritty", None)));
+
+        let config = config_with_rules(vec![rule]);
+        let actions = window_rule_actions(&config, &properties("Alacritty", None));
+        assert_eq!(actions.workspace, None);
+        assert_eq!(actions.floating, None);
+        assert!(!actions.no_focus);
+    }
+
+    #[test]
+    fn title_regex_match() {
+        let rule = title_rule("(?i)picker");
+        assert!(rule_matches(
+            &rule,
+            &properties("Gimp", Some("Color Picker"))
+        ));
+        assert!(rule_matches(
+            &rule,
+            &properties("Gimp", Some("picker - gimp"))
+        ));
+        assert!(!rule_matches(&rule, &properties("Gimp", Some("Layers"))));
+    }
+
+    #[test]
+    fn missing_title_does_not_match_title_rule() {
+        assert!(!rule_matches(&title_rule(".*"), &properties("Gimp", None)));
+    }
+
+    #[test]
+    fn type_and_transient_match() {
+        let rule = WindowRule {
+            window_type: Some("dialog".to_string()),
+            transient: Some(true),
+            ..Default::default()
+        };
+        let mut dialog = properties("Gimp", None);
+        dialog.window_type = "dialog";
+        dialog.transient = true;
+        assert!(rule_matches(&rule, &dialog));
+        dialog.transient = false;
+        assert!(!rule_matches(&rule, &dialog));
+        assert!(!rule_matches(&rule, &properties("Gimp", None)));
+    }
+
+    #[test]
+    fn later_rules_override_earlier_ones() {
+        let config = config_with_rules(vec![
+            WindowRule {
+                class: Some("Gimp".to_string()),
+                workspace: Some(2),
+                floating: Some(true),
+                no_focus: Some(true),
+                ..Default::default()
+            },
+            WindowRule {
+                workspace: Some(3),
+                no_focus: Some(false),
+                opacity: Some(0.9),
+                ..title_rule("Picker")
+            },
+        ]);
+
+        let actions = window_rule_actions(&config, &properties("Gimp", Some("Color Picker")));
+        assert_eq!(actions.workspace, Some(3));
+        // actions the later rule doesn't set are kept from the earlier one
+        assert_eq!(actions.floating, Some(true));
+        assert!(!actions.no_focus);
+        assert_eq!(actions.opacity, Some(0.9));
+
+        // only the first rule matches
+        let actions = window_rule_actions(&config, &properties("Gimp", Some("Layers")));
+        assert_eq!(actions.workspace, Some(2));
+        assert!(actions.no_focus);
+        assert_eq!(actions.opacity, None);
+    }
+}
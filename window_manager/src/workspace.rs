@@ -1,22 +1,32 @@
-use std::i32;
+use std::{collections::HashMap, i32};
 
 use base::Rect;
 use log::{trace, warn};
+use serde::Serialize;
 use x11_bindings::{
     bindings::{
-        XCB_BUTTON_MASK_1, XCB_BUTTON_MASK_3, XCB_CW_BORDER_PIXEL, XCB_CW_EVENT_MASK,
-        XCB_EVENT_MASK_BUTTON_MOTION, XCB_EVENT_MASK_BUTTON_RELEASE, XCB_EVENT_MASK_ENTER_WINDOW,
-        XCB_EVENT_MASK_FOCUS_CHANGE, XCB_EVENT_MASK_LEAVE_WINDOW, XCB_EVENT_MASK_POINTER_MOTION,
+        XCB_BUTTON_MASK_1, XCB_CW_BORDER_PIXEL, XCB_CW_EVENT_MASK, XCB_EVENT_MASK_BUTTON_MOTION,
+        XCB_EVENT_MASK_BUTTON_RELEASE, XCB_EVENT_MASK_ENTER_WINDOW, XCB_EVENT_MASK_FOCUS_CHANGE,
+        XCB_EVENT_MASK_LEAVE_WINDOW, XCB_EVENT_MASK_POINTER_MOTION, XCB_EVENT_MASK_PROPERTY_CHANGE,
         XCB_ICCCM_SIZE_HINT_BASE_SIZE, XCB_ICCCM_SIZE_HINT_P_MAX_SIZE,
         XCB_ICCCM_SIZE_HINT_P_MIN_SIZE, XCB_ICCCM_SIZE_HINT_P_POSITION, XCB_ICCCM_SIZE_HINT_P_SIZE,
-        XCB_MOD_MASK_1, xcb_button_t, xcb_size_hints_t, xcb_timestamp_t, xcb_window_t,
+        xcb_button_t, xcb_size_hints_t, xcb_window_t,
     },
     connection::WindowType,
 };
 
-use crate::{config::Config, connection::Connection, window::WindowsCollection};
+use crate::{
+    animation::WindowGeometry,
+    config::{CenterFocusedColumn, Config},
+    connection::Connection,
+    keybindings::Direction,
+    window::WindowsCollection,
+};
 
-#[derive(Debug)]
+/// Column widths `cycle_width` goes through unless `preset_column_widths` is configured
+pub const DEFAULT_PRESET_COLUMN_WIDTHS: [f64; 4] = [1.0 / 3.0, 0.5, 2.0 / 3.0, 1.0];
+
+#[derive(Debug, Serialize)]
 pub struct Workspace {
     pub id: u32,
     pub normal: WindowsCollection,
@@ -25,8 +35,20 @@ pub struct Workspace {
     focused_idx: usize,
     focused_type: WindowType,
     is_visible: bool,
+    #[serde(skip)]
     focused_via_keyboard: bool,
+    #[serde(skip)]
     window_mouse_interaction: Option<WindowMouseInteraction>,
+    /// windows in the order they were focused, the most recently focused last
+    #[serde(skip)]
+    focus_history: Vec<xcb_window_t>,
+    /// window covering the whole monitor without a border and the rect it covers, the window
+    /// keeps its own rect to return to
+    #[serde(skip)]
+    fullscreen: Option<(xcb_window_t, Rect)>,
+    /// widths of maximized columns before they were maximized, keyed by a window of the column
+    #[serde(skip)]
+    maximized_column_widths: HashMap<xcb_window_t, u32>,
 }
 
 impl Workspace {
@@ -41,48 +63,74 @@ impl Workspace {
             is_visible,
             focused_via_keyboard: false,
             window_mouse_interaction: None,
+            focus_history: vec![],
+            fullscreen: None,
+            maximized_column_widths: HashMap::new(),
         }
     }
 
+    /// On a hidden workspace the window is shifted out of the screen the same way as the other
+    /// windows of the workspace and unmapped
+    #[allow(clippy::too_many_arguments)]
     pub fn handle_existing_floating_window(
         &mut self,
         window: xcb_window_t,
         rect: &Rect,
         monitor_rect: &Rect,
+        from_workspace_id: u32,
+        focus: bool,
         conn: &Connection,
         config: &Config,
     ) {
-        // conn.window_configure(window, &window_rect, config.border_size);
-        // // self.apply_rounded_mask(window, window_rect.width, window_rect.height, conn);
-
-        // conn.change_window_attrs(
-        //     window,
-        //     XCB_CW_EVENT_MASK,
-        //     XCB_EVENT_MASK_FOCUS_CHANGE
-        //         | XCB_EVENT_MASK_ENTER_WINDOW
-        //         | XCB_EVENT_MASK_LEAVE_WINDOW
-        //         | XCB_EVENT_MASK_BUTTON_MOTION,
-        // );
-
-        // conn.map_window(window);
-
-        // self.floating.add(window, window_rect, true);
-        // self.set_focused(window, WindowType::Floating, conn, config);
-        // self.focused_via_keyboard = true;
-        // self.reset_window_interaction_state(conn);
+        let mut window_rect = rect.clone();
+        if !self.is_visible {
+            let hide_below = from_workspace_id < self.id;
+            window_rect.y += if hide_below {
+                monitor_rect.height as i32
+            } else {
+                -(monitor_rect.height as i32)
+            };
+        }
+        conn.window_configure(window, &window_rect, config.border_size);
+
+        self.floating.add(window, window_rect, true);
+        if self.is_visible {
+            conn.map_window(window);
+        } else {
+            conn.unmap_window(window);
+        }
+        if focus {
+            self.set_focused(window, WindowType::Floating, conn, config);
+            self.focused_via_keyboard = true;
+        } else {
+            conn.change_window_attrs(
+                window,
+                XCB_CW_BORDER_PIXEL,
+                config.border_color_inactive_int.unwrap(),
+            );
+        }
+        self.reset_window_interaction_state(conn);
     }
 
+    /// `rect` overrides the size hints of the window, `from_workspace_id` is the focused workspace
+    /// in case this one is hidden
+    #[allow(clippy::too_many_arguments)]
     pub fn handle_new_floating_window(
         &mut self,
         window: xcb_window_t,
         rect_hints: Option<xcb_size_hints_t>,
+        rect: Option<Rect>,
+        focus: bool,
+        from_workspace_id: u32,
         monitor_rect: &Rect,
         conn: &Connection,
         config: &Config,
     ) {
+        let rect_hints = if rect.is_some() { None } else { rect_hints };
         let center_x = (monitor_rect.x + monitor_rect.width as i32) / 2;
         let center_y = (monitor_rect.y + monitor_rect.height as i32) / 2;
-        let mut rect = {
+        let rule_rect = rect;
+        let mut rect = rule_rect.clone().unwrap_or_else(|| {
             let width: u32 = 800;
             let height: u32 = 600;
             Rect {
@@ -91,9 +139,9 @@ impl Workspace {
                 width,
                 height,
             }
-        };
+        });
 
-        let mut rect_hints_relevant = false;
+        let mut rect_hints_relevant = rule_rect.is_some();
         if let Some(hints) = rect_hints {
             if hints.flags & XCB_ICCCM_SIZE_HINT_P_POSITION != 0 {
                 rect.x = hints.x;
@@ -155,25 +203,25 @@ impl Workspace {
             rect = adjusted_rect;
         }
 
-        conn.window_configure(window, &rect, config.border_size);
-
         conn.change_window_attrs(
             window,
             XCB_CW_EVENT_MASK,
             XCB_EVENT_MASK_FOCUS_CHANGE
                 | XCB_EVENT_MASK_ENTER_WINDOW
                 | XCB_EVENT_MASK_LEAVE_WINDOW
-                | XCB_EVENT_MASK_BUTTON_MOTION,
+                | XCB_EVENT_MASK_BUTTON_MOTION
+                | XCB_EVENT_MASK_PROPERTY_CHANGE,
         );
 
-        conn.map_window(window);
-
-        conn.grab_button(window);
-
-        self.floating.add(window, rect, true);
-        self.set_focused(window, WindowType::Floating, conn, config);
-        self.focused_via_keyboard = true;
-        self.reset_window_interaction_state(conn);
+        self.handle_existing_floating_window(
+            window,
+            &rect,
+            monitor_rect,
+            from_workspace_id,
+            focus,
+            conn,
+            config,
+        );
     }
 
     // pub fn raise_all_floating_windows(&self, conn: &Connection) {
@@ -184,18 +232,50 @@ impl Workspace {
     //     }
     // }
 
+    /// `width_percent` is used instead of default_screen_width_percent_tiling, `from_workspace_id`
+    /// is the focused workspace in case this one is hidden
+    #[allow(clippy::too_many_arguments)]
     pub fn handle_new_normal_window(
         &mut self,
         window: xcb_window_t,
+        width_percent: f64,
+        focus: bool,
+        from_workspace_id: u32,
         monitor_rect: &Rect,
         conn: &Connection,
         config: &Config,
     ) {
         let avail_rect = self.available_rectangle(monitor_rect, config);
-        let window_width = ((avail_rect.x + avail_rect.width as i32) as f64
-            * config.default_screen_width_percent_tiling) as u32
+        let window_width = ((avail_rect.x + avail_rect.width as i32) as f64 * width_percent) as u32
             - config.border_size * 2;
         let window_height = avail_rect.height - config.border_size * 2;
+
+        conn.change_window_attrs(
+            window,
+            XCB_CW_EVENT_MASK,
+            XCB_EVENT_MASK_FOCUS_CHANGE
+                | XCB_EVENT_MASK_ENTER_WINDOW
+                | XCB_EVENT_MASK_LEAVE_WINDOW
+                | XCB_EVENT_MASK_PROPERTY_CHANGE,
+        );
+
+        if !self.is_visible {
+            let window_rect = Rect {
+                x: avail_rect.x,
+                y: avail_rect.y,
+                width: window_width,
+                height: window_height,
+            };
+            self.handle_existing_normal_window(
+                window,
+                window_rect,
+                monitor_rect,
+                from_workspace_id,
+                conn,
+                config,
+            );
+            return;
+        }
         let focused_idx =
             if self.focused_type == WindowType::Normal && self.focused_idx < self.normal.len() {
                 Some(self.focused_idx)
@@ -230,20 +310,20 @@ impl Workspace {
             }
         }
 
-        conn.change_window_attrs(
-            window,
-            XCB_CW_EVENT_MASK,
-            XCB_EVENT_MASK_FOCUS_CHANGE | XCB_EVENT_MASK_ENTER_WINDOW | XCB_EVENT_MASK_LEAVE_WINDOW,
-        );
-
         self.normal.add(window, window_rect, false);
         self.normal.sort_by_rect_x_asc();
-        self.normal
-            .iter()
-            .for_each(|(w, rect, _)| conn.window_configure(*w, rect, config.border_size));
+        self.configure_normal_windows(conn, config);
         self.fix_windows_visibility(&avail_rect, conn);
-        self.set_focused(window, WindowType::Normal, conn, config);
-        self.focused_via_keyboard = true;
+        if focus {
+            self.set_focused(window, WindowType::Normal, conn, config);
+            self.focused_via_keyboard = true;
+        } else {
+            conn.change_window_attrs(
+                window,
+                XCB_CW_BORDER_PIXEL,
+                config.border_color_inactive_int.unwrap(),
+            );
+        }
         self.reset_window_interaction_state(conn);
     }
 
@@ -304,9 +384,7 @@ impl Workspace {
             .add(window, window_rect.clone(), !self.is_visible);
         self.normal.sort_by_rect_x_asc();
         if self.is_visible {
-            self.normal
-                .iter()
-                .for_each(|(w, rect, _)| conn.window_configure(*w, rect, config.border_size));
+            self.configure_normal_windows(conn, config);
             self.fix_windows_visibility(monitor_rect, conn);
         } else {
             conn.unmap_window(window);
@@ -341,19 +419,17 @@ impl Workspace {
                 if self.focused_idx > 0 {
                     let new_focused_idx = self.focused_idx - 1;
                     let avail_rect = self.available_rectangle(monitor_rect, config);
-                    let move_right_x = {
-                        let focused_rect = self.normal.at_rect(new_focused_idx).unwrap();
-                        if focused_rect.x < avail_rect.x {
-                            avail_rect.x - focused_rect.x
-                        } else {
-                            0
-                        }
-                    };
-                    if move_right_x > 0 {
-                        for (window, rect, _) in self.normal.iter_mut() {
-                            rect.x += move_right_x;
-                            conn.window_configure(*window, rect, config.border_size);
+                    let move_x = self.focus_change_scroll_offset(
+                        self.focused_idx,
+                        new_focused_idx,
+                        &avail_rect,
+                        config,
+                    );
+                    if move_x != 0 {
+                        for rect in self.normal.rect_iter_mut() {
+                            rect.x += move_x;
                         }
+                        self.configure_normal_windows(conn, config);
                         self.fix_windows_visibility(monitor_rect, conn);
                     }
                     self.set_focused_by_index(new_focused_idx, self.focused_type, conn, config);
@@ -382,23 +458,17 @@ impl Workspace {
                 if self.focused_idx < self.normal.len() - 1 {
                     let new_focused_idx = self.focused_idx + 1;
                     let avail_rect = self.available_rectangle(monitor_rect, config);
-                    let move_left_x = {
-                        let focused_rect = self.normal.at_rect(new_focused_idx).unwrap();
-                        if focused_rect.x + focused_rect.width as i32
-                            > avail_rect.x + avail_rect.width as i32
-                        {
-                            focused_rect.x + focused_rect.width as i32
-                                - avail_rect.x
-                                - avail_rect.width as i32
-                        } else {
-                            0
-                        }
-                    };
-                    if move_left_x > 0 {
-                        for (window, rect, _) in self.normal.iter_mut() {
-                            rect.x -= move_left_x;
-                            conn.window_configure(*window, rect, config.border_size);
+                    let move_x = self.focus_change_scroll_offset(
+                        self.focused_idx,
+                        new_focused_idx,
+                        &avail_rect,
+                        config,
+                    );
+                    if move_x != 0 {
+                        for rect in self.normal.rect_iter_mut() {
+                            rect.x += move_x;
                         }
+                        self.configure_normal_windows(conn, config);
                         self.fix_windows_visibility(monitor_rect, conn);
                     }
                     self.set_focused_by_index(new_focused_idx, self.focused_type, conn, config);
@@ -416,6 +486,48 @@ impl Workspace {
         }
     }
 
+    /// How far the strip moves when focus goes from the column at `prev_idx` to the one at
+    /// `new_idx`, following `center_focused_column` and `left_align_fitting_strip`
+    fn focus_change_scroll_offset(
+        &self,
+        prev_idx: usize,
+        new_idx: usize,
+        avail_rect: &Rect,
+        config: &Config,
+    ) -> i32 {
+        let avail_right = avail_rect.x + avail_rect.width as i32;
+        if config.left_align_fitting_strip {
+            let first_rect = self.normal.index_rect(0);
+            let last_rect = self.normal.index_rect(self.normal.len() - 1);
+            if last_rect.x + last_rect.width as i32 - first_rect.x <= avail_rect.width as i32 {
+                return avail_rect.x - first_rect.x;
+            }
+        }
+        let rect = self.normal.index_rect(new_idx);
+        let center_move_x =
+            avail_rect.x + avail_rect.width as i32 / 2 - (rect.x + rect.width as i32 / 2);
+        let minimal_move_x = if rect.x < avail_rect.x {
+            avail_rect.x - rect.x
+        } else if rect.x + rect.width as i32 > avail_right {
+            avail_right - rect.x - rect.width as i32
+        } else {
+            0
+        };
+        match config.center_focused_column {
+            CenterFocusedColumn::Never => minimal_move_x,
+            CenterFocusedColumn::Always => center_move_x,
+            CenterFocusedColumn::OnOverflow => {
+                let prev_rect = self.normal.index_rect(prev_idx);
+                let prev_x = prev_rect.x + minimal_move_x;
+                if prev_x >= avail_rect.x && prev_x + prev_rect.width as i32 <= avail_right {
+                    minimal_move_x
+                } else {
+                    center_move_x
+                }
+            }
+        }
+    }
+
     pub fn handle_move_window_left(
         &mut self,
         conn: &Connection,
@@ -444,11 +556,6 @@ impl Workspace {
                     }
                     self.focused_idx = target_idx;
 
-                    let (win_newly_focused, rect_newly_focused) = self.normal.index(target_idx);
-
-                    let (win_prev_focused, rect_prev_focused) =
-                        self.normal.index(currently_focused_idx);
-
                     // adjust viewport if required
                     let move_right_x = {
                         let focused_rect_after_swap = self.normal.index_rect(self.focused_idx);
@@ -459,22 +566,14 @@ impl Workspace {
                         }
                     };
                     if move_right_x != 0 {
-                        for (window, rect, _) in self.normal.iter_mut() {
+                        for rect in self.normal.rect_iter_mut() {
                             rect.x += move_right_x;
-                            conn.window_configure(*window, &rect, config.border_size);
                         }
+                        self.configure_normal_windows(conn, config);
                         self.fix_windows_visibility(monitor_rect, conn);
                     } else {
-                        conn.window_configure(
-                            win_newly_focused,
-                            rect_newly_focused,
-                            config.border_size,
-                        );
-                        conn.window_configure(
-                            win_prev_focused,
-                            rect_prev_focused,
-                            config.border_size,
-                        );
+                        self.configure_normal_column(target_idx, conn, config);
+                        self.configure_normal_column(currently_focused_idx, conn, config);
                     }
                     self.focused_via_keyboard = true;
                     self.reset_window_interaction_state(conn);
@@ -518,10 +617,7 @@ impl Workspace {
                     }
                     self.focused_idx = target_idx;
 
-                    let (win_newly_focused, rect_newly_focused) = self.normal.index(target_idx);
-
-                    let (win_prev_focused, rect_prev_focused) =
-                        self.normal.index(currently_focused_idx);
+                    let rect_newly_focused = self.normal.index_rect(target_idx);
 
                     // adjust viewport if required
                     let move_left_x = {
@@ -536,22 +632,14 @@ impl Workspace {
                         }
                     };
                     if move_left_x > 0 {
-                        for (window, rect, _) in self.normal.iter_mut() {
+                        for rect in self.normal.rect_iter_mut() {
                             rect.x -= move_left_x;
-                            conn.window_configure(*window, rect, config.border_size);
                         }
+                        self.configure_normal_windows(conn, config);
                         self.fix_windows_visibility(monitor_rect, conn);
                     } else {
-                        conn.window_configure(
-                            win_newly_focused,
-                            rect_newly_focused,
-                            config.border_size,
-                        );
-                        conn.window_configure(
-                            win_prev_focused,
-                            rect_prev_focused,
-                            config.border_size,
-                        );
+                        self.configure_normal_column(target_idx, conn, config);
+                        self.configure_normal_column(currently_focused_idx, conn, config);
                     }
                     self.focused_via_keyboard = true;
                     self.reset_window_interaction_state(conn);
@@ -612,6 +700,11 @@ impl Workspace {
     ) {
         self.focused_idx = index;
         self.focused_type = window_type;
+        if window_type == WindowType::Normal {
+            self.normal.set_active_window_at(index, window);
+        }
+        self.focus_history.retain(|w| *w != window);
+        self.focus_history.push(window);
         conn.change_window_attrs(
             window,
             XCB_CW_BORDER_PIXEL,
@@ -642,6 +735,7 @@ impl Workspace {
                 if self.focused_idx >= self.normal.len() {
                     self.focused_idx = self.normal.len() - 1;
                 }
+                self.forget_maximized_column_width(self.focused_idx);
                 let focused_rect = self.normal.index_rect(self.focused_idx).clone();
                 let new_width = (focused_rect.width as i32 + size_change_pixels)
                     .clamp(config.minimum_width_tiling as i32, avail_rect.width as i32);
@@ -674,9 +768,7 @@ impl Workspace {
                         rect.x += move_x_from_right;
                     }
                 }
-                for (window, rect, _) in self.normal.iter() {
-                    conn.window_configure(*window, rect, config.border_size);
-                }
+                self.configure_normal_windows(conn, config);
 
                 self.fix_windows_visibility(monitor_rect, conn);
                 self.focused_via_keyboard = true;
@@ -689,6 +781,203 @@ impl Workspace {
         }
     }
 
+    /// Grows or shrinks the focused window within its column, the other windows of the column
+    /// absorb the difference
+    pub fn handle_resize_window_vertical(
+        &mut self,
+        conn: &Connection,
+        config: &Config,
+        size_change_pixels: i32,
+    ) {
+        match self.focused_type {
+            WindowType::Normal => {
+                if self.focused_idx >= self.normal.len() {
+                    return;
+                }
+                let spacing = config.inner_gap + config.border_size * 2;
+                if self.normal.resize_window_height_at(
+                    self.focused_idx,
+                    size_change_pixels,
+                    spacing,
+                    config.minimum_height_tiling,
+                ) {
+                    self.configure_normal_column(self.focused_idx, conn, config);
+                }
+                self.focused_via_keyboard = true;
+                self.reset_window_interaction_state(conn);
+            }
+            WindowType::Floating => {
+                trace!("vertical resize window event received when floating window is focused");
+            }
+            WindowType::Docked => warn!("vertical resize window when docked window is focused"),
+        }
+    }
+
+    /// Snaps the focused column to the next (or previous) preset width, wrapping around, and
+    /// reflows the other columns around it
+    pub fn cycle_column_width(
+        &mut self,
+        forward: bool,
+        conn: &Connection,
+        config: &Config,
+        monitor_rect: &Rect,
+    ) {
+        if self.focused_type != WindowType::Normal || self.focused_idx >= self.normal.len() {
+            return;
+        }
+        let avail_rect = self.available_rectangle(monitor_rect, config);
+        let mut preset_widths = config
+            .preset_column_widths
+            .as_deref()
+            .unwrap_or(&DEFAULT_PRESET_COLUMN_WIDTHS)
+            .iter()
+            .map(|width_percent| {
+                let outer_width =
+                    ((avail_rect.width + config.inner_gap) as f64 * width_percent) as u32;
+                outer_width
+                    .saturating_sub(config.inner_gap + config.border_size * 2)
+                    .max(config.minimum_width_tiling)
+            })
+            .collect::<Vec<_>>();
+        preset_widths.sort_unstable();
+        let current_width = self.normal.index_rect(self.focused_idx).width;
+        let new_width = if forward {
+            preset_widths
+                .iter()
+                .find(|width| **width > current_width)
+                .or(preset_widths.first())
+        } else {
+            preset_widths
+                .iter()
+                .rev()
+                .find(|width| **width < current_width)
+                .or(preset_widths.last())
+        };
+        let Some(&new_width) = new_width else {
+            return;
+        };
+        trace!("cycle column width from {} to {}", current_width, new_width);
+        self.forget_maximized_column_width(self.focused_idx);
+        self.set_focused_column_width(new_width, conn, config, monitor_rect);
+    }
+
+    /// Makes the focused column take the whole available width with the gaps kept, or returns
+    /// it to the width it had before
+    pub fn toggle_maximize_column(
+        &mut self,
+        conn: &Connection,
+        config: &Config,
+        monitor_rect: &Rect,
+    ) {
+        if self.focused_type != WindowType::Normal || self.focused_idx >= self.normal.len() {
+            return;
+        }
+        let new_width = match self.forget_maximized_column_width(self.focused_idx) {
+            Some(width) => width,
+            None => {
+                let avail_rect = self.available_rectangle(monitor_rect, config);
+                let rect = self.normal.index_rect(self.focused_idx);
+                let window = self.normal.index_window(self.focused_idx);
+                self.maximized_column_widths.insert(window, rect.width);
+                avail_rect.width.saturating_sub(config.border_size * 2)
+            }
+        };
+        trace!("maximize column toggled, new width: {}", new_width);
+        self.set_focused_column_width(new_width, conn, config, monitor_rect);
+    }
+
+    /// Drops the width the column at `index` had before it was maximized and returns it
+    fn forget_maximized_column_width(&mut self, index: usize) -> Option<u32> {
+        let column = self.normal.column(index);
+        let window = column
+            .iter()
+            .find(|window| self.maximized_column_widths.contains_key(window))?;
+        self.maximized_column_widths.remove(window)
+    }
+
+    fn set_focused_column_width(
+        &mut self,
+        width: u32,
+        conn: &Connection,
+        config: &Config,
+        monitor_rect: &Rect,
+    ) {
+        let avail_rect = self.available_rectangle(monitor_rect, config);
+        self.normal.index_rect_mut(self.focused_idx).width = width;
+        self.fix_existing_normal_windows(&avail_rect, conn, config);
+        let window = self.normal.index_window(self.focused_idx);
+        self.focus_window_via_keyboard(window, conn, config, monitor_rect);
+    }
+
+    /// Makes the window cover `screen_rect` above the docks without a border, or returns it to
+    /// its own rect, only one window of the workspace is fullscreen at a time
+    pub fn set_window_fullscreen(
+        &mut self,
+        window: xcb_window_t,
+        fullscreen: bool,
+        screen_rect: &Rect,
+        conn: &Connection,
+        config: &Config,
+    ) {
+        if self.find_window_info_by_xcb_id(window).is_none()
+            || self.is_window_fullscreen(window) == fullscreen
+        {
+            return;
+        }
+        trace!("set fullscreen of window {} to {}", window, fullscreen);
+        let previous = if fullscreen {
+            self.fullscreen.replace((window, screen_rect.clone()))
+        } else {
+            self.fullscreen.take()
+        };
+        if let Some((previous_window, _)) = previous {
+            conn.window_set_fullscreen_state(previous_window, false);
+            self.configure_window(previous_window, conn, config);
+        }
+        if fullscreen {
+            conn.window_set_fullscreen_state(window, true);
+            self.configure_window(window, conn, config);
+        }
+        if self.is_visible {
+            self.fix_windows_visibility(screen_rect, conn);
+            if fullscreen {
+                conn.window_raise(window);
+            }
+        }
+    }
+
+    /// Tiled or floating window that has, or last had, focus on this workspace
+    pub fn focused_window(&self) -> Option<xcb_window_t> {
+        match self.focused_type {
+            WindowType::Normal => self.normal.at_window(self.focused_idx),
+            WindowType::Floating => self.floating.at_window(self.focused_idx),
+            WindowType::Docked => None,
+        }
+    }
+
+    pub fn toggle_focused_window_fullscreen(
+        &mut self,
+        screen_rect: &Rect,
+        conn: &Connection,
+        config: &Config,
+    ) {
+        let focused_window_maybe = match self.focused_type {
+            WindowType::Normal => self.normal.at_window(self.focused_idx),
+            WindowType::Floating => self.floating.at_window(self.focused_idx),
+            WindowType::Docked => None,
+        };
+        if let Some(window) = focused_window_maybe {
+            let fullscreen = !self.is_window_fullscreen(window);
+            self.set_window_fullscreen(window, fullscreen, screen_rect, conn, config);
+        }
+    }
+
+    pub fn is_window_fullscreen(&self, window: xcb_window_t) -> bool {
+        self.fullscreen
+            .as_ref()
+            .is_some_and(|(fullscreen_window, _)| *fullscreen_window == window)
+    }
+
     pub fn show_all_windows(&mut self, monitor_rect: &Rect, conn: &Connection, config: &Config) {
         if self.is_visible {
             warn!(
@@ -749,11 +1038,16 @@ impl Workspace {
             );
         }
 
-        for (window, rect, visible) in self.normal.iter_mut() {
+        for rect in self.normal.rect_iter_mut() {
             rect.y += move_y;
-            conn.window_configure(*window, rect, config.border_size);
-            if *visible {
-                conn.map_window(*window);
+        }
+        self.configure_normal_windows(conn, config);
+        for index in 0..self.normal.len() {
+            if self.normal.at_visible(index) == Some(true) {
+                self.normal
+                    .column(index)
+                    .iter()
+                    .for_each(|window| conn.map_window(*window));
             }
         }
         // self.fix_existing_normal_windows(&avail_rect, conn, config);
@@ -783,6 +1077,10 @@ impl Workspace {
             };
             self.set_focused_by_index(focused_idx, self.focused_type, conn, config);
         }
+        if let Some((window, _)) = &self.fullscreen {
+            self.configure_window(*window, conn, config);
+            conn.window_raise(*window);
+        }
         self.focused_via_keyboard = true;
         self.reset_window_interaction_state(conn);
     }
@@ -807,11 +1105,16 @@ impl Workspace {
         } else {
             -(monitor_rect.height as i32)
         };
-        for (window, rect, visible) in self.normal.iter_mut() {
+        for rect in self.normal.rect_iter_mut() {
             rect.y += move_y;
-            conn.window_configure(*window, rect, config.border_size);
-            if *visible {
-                conn.unmap_window(*window);
+        }
+        self.configure_normal_windows(conn, config);
+        for index in 0..self.normal.len() {
+            if self.normal.at_visible(index) == Some(true) {
+                self.normal
+                    .column(index)
+                    .iter()
+                    .for_each(|window| conn.unmap_window(*window));
             }
         }
         for (window, rect, visible) in self.floating.iter_mut() {
@@ -842,17 +1145,21 @@ impl Workspace {
         let avail_rect = self.available_rectangle(monitor_rect, config);
         match self.focused_type {
             WindowType::Normal if self.focused_idx < self.normal.len() => {
+                let column_removed = self.normal.column(self.focused_idx).len() == 1;
                 let (removed_window, removed_window_rect, _) =
                     self.normal.remove_at(self.focused_idx);
-                let new_focused_idx = if self.focused_idx > 0 {
+                self.forget_removed_window(removed_window, conn);
+                let new_focused_idx = if column_removed && self.focused_idx > 0 {
                     self.focused_idx - 1
                 } else {
                     self.focused_idx
                 };
-                if new_focused_idx < self.normal.len() {
-                    self.set_focused_by_index(new_focused_idx, self.focused_type, conn, config);
-                } else if !self.floating.is_empty() {
-                    self.set_focused_by_index(0, WindowType::Floating, conn, config);
+                if !self.focus_previous_window(conn, config) {
+                    if new_focused_idx < self.normal.len() {
+                        self.set_focused_by_index(new_focused_idx, self.focused_type, conn, config);
+                    } else if !self.floating.is_empty() {
+                        self.set_focused_by_index(0, WindowType::Floating, conn, config);
+                    }
                 }
                 self.fix_existing_normal_windows(&avail_rect, conn, config);
                 self.focused_via_keyboard = true;
@@ -863,12 +1170,18 @@ impl Workspace {
             WindowType::Floating if self.focused_idx < self.floating.len() => {
                 let (removed_window, removed_window_rect, _) =
                     self.floating.remove_at(self.focused_idx);
+                self.forget_removed_window(removed_window, conn);
                 let new_focused_idx = if self.focused_idx > 0 {
                     self.focused_idx - 1
                 } else {
                     self.focused_idx
                 };
-                if new_focused_idx < self.floating.len() {
+                if self.focus_previous_window(conn, config) {
+                    if self.focused_type == WindowType::Normal {
+                        self.fix_existing_normal_windows(&avail_rect, conn, config);
+                        self.focused_via_keyboard = true;
+                    }
+                } else if new_focused_idx < self.floating.len() {
                     self.set_focused_by_index(self.focused_idx, self.focused_type, conn, config);
                 } else if !self.normal.is_empty() {
                     let mut closest_dist = i32::MAX;
@@ -1069,25 +1382,274 @@ impl Workspace {
             let avail_center_x = avail_rect.x + (avail_rect.width as i32) / 2;
             let focused_center_x = focused_rect.x + (focused_rect.width as i32) / 2;
             let move_x = avail_center_x - focused_center_x;
-            self.normal.iter_mut().for_each(|(window, rect, _)| {
-                rect.x += move_x;
-                conn.window_configure(*window, rect, config.border_size)
-            });
+            self.normal
+                .rect_iter_mut()
+                .for_each(|rect| rect.x += move_x);
+            self.configure_normal_windows(conn, config);
             self.floating.iter_mut().for_each(|(window, rect, _)| {
                 rect.x += move_x;
                 conn.window_configure(*window, rect, config.border_size)
             });
+            if let Some((window, _)) = &self.fullscreen {
+                self.configure_window(*window, conn, config);
+            }
             self.fix_windows_visibility(&avail_rect, conn);
         }
     }
 
-    pub fn find_window_info_by_xcb_id(&self, window: xcb_window_t) -> Option<(usize, WindowType)> {
-        if let Some((index, _)) = self
-            .normal
-            .window_iter()
-            .enumerate()
-            .find(|(_, w)| **w == window)
+    /// Moves the focused window between the tiled strip and the floating layer
+    pub fn toggle_focused_window_floating(
+        &mut self,
+        monitor_rect: &Rect,
+        conn: &Connection,
+        config: &Config,
+    ) {
+        if let Some((window, _, window_type)) = self.pop_focused_window(monitor_rect, conn, config)
         {
+            trace!(
+                "toggle floating for window {}, was {:?}",
+                window, window_type
+            );
+            match window_type {
+                WindowType::Normal => self.handle_new_floating_window(
+                    window,
+                    None,
+                    None,
+                    true,
+                    self.id,
+                    monitor_rect,
+                    conn,
+                    config,
+                ),
+                WindowType::Floating => self.handle_new_normal_window(
+                    window,
+                    config.default_screen_width_percent_tiling,
+                    true,
+                    self.id,
+                    monitor_rect,
+                    conn,
+                    config,
+                ),
+                WindowType::Docked => {}
+            }
+        }
+    }
+
+    /// Shifts the tiled strip without changing focus unless the focused window goes out of view,
+    /// stops once the first or the last window is fully visible
+    pub fn scroll_strip(
+        &mut self,
+        direction: Direction,
+        pixels: i32,
+        conn: &Connection,
+        config: &Config,
+        monitor_rect: &Rect,
+    ) {
+        if self.normal.is_empty() {
+            return;
+        }
+        let avail_rect = self.available_rectangle(monitor_rect, config);
+        let first_rect = self.normal.index_rect(0);
+        let last_rect = self.normal.index_rect(self.normal.len() - 1);
+        let strip_left = first_rect.x;
+        let strip_right = last_rect.x + last_rect.width as i32 + config.border_size as i32 * 2;
+        let move_x = match direction {
+            Direction::Left => pixels.min(avail_rect.x - strip_left).max(0),
+            Direction::Right => -pixels
+                .min(strip_right - (avail_rect.x + avail_rect.width as i32))
+                .max(0),
+            Direction::Up | Direction::Down => 0,
+        };
+        if move_x == 0 {
+            return;
+        }
+        for rect in self.normal.rect_iter_mut() {
+            rect.x += move_x;
+        }
+        self.configure_normal_windows(conn, config);
+        self.fix_windows_visibility(&avail_rect, conn);
+
+        if self.focused_type == WindowType::Normal
+            && self.normal.at_visible(self.focused_idx) == Some(false)
+        {
+            let closest_visible_idx = self
+                .normal
+                .visible_iter()
+                .enumerate()
+                .filter(|(_, visible)| **visible)
+                .map(|(idx, _)| idx)
+                .min_by_key(|idx| idx.abs_diff(self.focused_idx));
+            if let Some(idx) = closest_visible_idx {
+                self.set_focused_by_index(idx, WindowType::Normal, conn, config);
+            }
+        }
+    }
+
+    /// Focuses the window above or below the focused one in its column, false if there is none
+    pub fn focus_window_in_column(
+        &mut self,
+        above: bool,
+        conn: &Connection,
+        config: &Config,
+    ) -> bool {
+        if self.focused_type != WindowType::Normal || self.focused_idx >= self.normal.len() {
+            return false;
+        }
+        let Some(window) = self.normal.column_neighbour(self.focused_idx, above) else {
+            return false;
+        };
+        self.set_focused_by_index_window(
+            self.focused_idx,
+            window,
+            WindowType::Normal,
+            conn,
+            config,
+        );
+        self.focused_via_keyboard = true;
+        self.reset_window_interaction_state(conn);
+        true
+    }
+
+    /// Swaps the focused window with the one above or below it in its column, false if there is
+    /// none
+    pub fn move_window_in_column(
+        &mut self,
+        above: bool,
+        conn: &Connection,
+        config: &Config,
+    ) -> bool {
+        if self.focused_type != WindowType::Normal
+            || self.focused_idx >= self.normal.len()
+            || !self.normal.swap_in_column(self.focused_idx, above)
+        {
+            return false;
+        }
+        self.configure_normal_column(self.focused_idx, conn, config);
+        self.focused_via_keyboard = true;
+        self.reset_window_interaction_state(conn);
+        true
+    }
+
+    /// Moves the focused window to the bottom of the column on its left or right
+    pub fn consume_window(
+        &mut self,
+        direction: Direction,
+        conn: &Connection,
+        config: &Config,
+        monitor_rect: &Rect,
+    ) {
+        if self.focused_type != WindowType::Normal || self.focused_idx >= self.normal.len() {
+            return;
+        }
+        let mut target_idx = match direction {
+            Direction::Left if self.focused_idx > 0 => self.focused_idx - 1,
+            Direction::Right if self.focused_idx + 1 < self.normal.len() => self.focused_idx + 1,
+            _ => return,
+        };
+        let column_removed = self.normal.column(self.focused_idx).len() == 1;
+        let (window, _, _) = self.normal.remove_at(self.focused_idx);
+        if column_removed && target_idx > self.focused_idx {
+            target_idx -= 1;
+        }
+        self.normal.stack_window_at(target_idx, window);
+        if self.normal.at_visible(target_idx) == Some(true) {
+            conn.map_window(window);
+        } else {
+            conn.unmap_window(window);
+        }
+        self.focused_idx = target_idx;
+        let avail_rect = self.available_rectangle(monitor_rect, config);
+        self.fix_existing_normal_windows(&avail_rect, conn, config);
+        self.focus_window_via_keyboard(window, conn, config, monitor_rect);
+    }
+
+    /// Moves the focused window out of its column into a new column on the right
+    pub fn expel_window(&mut self, conn: &Connection, config: &Config, monitor_rect: &Rect) {
+        if self.focused_type != WindowType::Normal
+            || self.focused_idx >= self.normal.len()
+            || self.normal.column(self.focused_idx).len() == 1
+        {
+            return;
+        }
+        let (window, column_rect, _) = self.normal.remove_at(self.focused_idx);
+        self.handle_existing_normal_window(
+            window,
+            column_rect,
+            monitor_rect,
+            self.id,
+            conn,
+            config,
+        );
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.window_iter().next().is_none()
+    }
+
+    pub fn window_iter(&self) -> impl Iterator<Item = &xcb_window_t> {
+        self.normal
+            .window_iter()
+            .chain(self.floating.window_iter())
+            .chain(self.docked.window_iter())
+    }
+
+    /// Windows with their current rects, used to detect geometry changes
+    pub fn layout(&self, config: &Config) -> Vec<(xcb_window_t, Rect)> {
+        let spacing = config.inner_gap + config.border_size * 2;
+        (0..self.normal.len())
+            .flat_map(|index| self.normal.column_window_rects(index, spacing))
+            .chain(
+                self.floating
+                    .iter()
+                    .map(|(window, rect, _)| (*window, rect.clone())),
+            )
+            .collect()
+    }
+
+    /// Rects windows are configured with and whether they're mapped
+    pub fn window_geometries(&self, config: &Config) -> Vec<WindowGeometry> {
+        let spacing = config.inner_gap + config.border_size * 2;
+        let mut geometries = vec![];
+        for index in 0..self.normal.len() {
+            let mapped = self.is_visible && self.normal.at_visible(index) == Some(true);
+            for (window, rect) in self.normal.column_window_rects(index, spacing) {
+                geometries.push(self.window_geometry(window, rect, mapped, config));
+            }
+        }
+        for (window, rect, visible) in self.floating.iter() {
+            let mapped = self.is_visible && *visible;
+            geometries.push(self.window_geometry(*window, rect.clone(), mapped, config));
+        }
+        geometries
+    }
+
+    fn window_geometry(
+        &self,
+        window: xcb_window_t,
+        rect: Rect,
+        mapped: bool,
+        config: &Config,
+    ) -> WindowGeometry {
+        match &self.fullscreen {
+            Some((fullscreen_window, fullscreen_rect)) if *fullscreen_window == window => {
+                WindowGeometry {
+                    window,
+                    rect: fullscreen_rect.clone(),
+                    border_size: 0,
+                    mapped,
+                }
+            }
+            _ => WindowGeometry {
+                window,
+                rect,
+                border_size: config.border_size,
+                mapped,
+            },
+        }
+    }
+
+    pub fn find_window_info_by_xcb_id(&self, window: xcb_window_t) -> Option<(usize, WindowType)> {
+        if let Some(index) = self.normal.index_of(window) {
             Some((index, WindowType::Normal))
         } else if let Some((index, _)) = self
             .floating
@@ -1101,26 +1663,186 @@ impl Workspace {
         }
     }
 
+    /// Removes a floating window without changing focus, e.g. a sticky window following a
+    /// workspace switch, returns its rect and whether it was fullscreen
+    pub fn pop_floating_window(&mut self, window: xcb_window_t) -> Option<(Rect, bool)> {
+        let index = self.floating.index_of(window)?;
+        let (_, rect, _) = self.floating.remove_at(index);
+        let was_fullscreen = self.is_window_fullscreen(window);
+        if was_fullscreen {
+            self.fullscreen = None;
+        }
+        self.focus_history.retain(|w| *w != window);
+        if self.focused_type == WindowType::Floating && self.focused_idx > index {
+            self.focused_idx -= 1;
+        }
+        Some((rect, was_fullscreen))
+    }
+
+    /// Drops the state kept for a window leaving the workspace, it doesn't stay fullscreen
+    fn forget_removed_window(&mut self, window: xcb_window_t, conn: &Connection) {
+        self.focus_history.retain(|w| *w != window);
+        self.maximized_column_widths.remove(&window);
+        if self.is_window_fullscreen(window) {
+            self.fullscreen = None;
+            conn.window_set_fullscreen_state(window, false);
+        }
+    }
+
+    /// Focuses the most recently focused window still on this workspace, false if there is none
+    fn focus_previous_window(&mut self, conn: &Connection, config: &Config) -> bool {
+        while let Some(window) = self.focus_history.pop() {
+            if let Some((index, window_type)) = self.find_window_info_by_xcb_id(window) {
+                self.set_focused_by_index_window(index, window, window_type, conn, config);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Focuses a window of this workspace clicked with the mouse
+    pub fn focus_window(&mut self, window: xcb_window_t, conn: &Connection, config: &Config) {
+        if let Some((index, window_type)) = self.find_window_info_by_xcb_id(window) {
+            self.set_focused_by_index_window(index, window, window_type, conn, config);
+            self.focused_via_keyboard = false;
+        }
+    }
+
+    /// Focuses a window of this workspace as keyboard navigation does, scrolling the strip so a
+    /// tiled window is fully visible
+    pub fn focus_window_via_keyboard(
+        &mut self,
+        window: xcb_window_t,
+        conn: &Connection,
+        config: &Config,
+        monitor_rect: &Rect,
+    ) {
+        let Some((index, window_type)) = self.find_window_info_by_xcb_id(window) else {
+            return;
+        };
+        if window_type == WindowType::Normal {
+            let avail_rect = self.available_rectangle(monitor_rect, config);
+            let rect = self.normal.index_rect(index);
+            let move_x = if rect.x < avail_rect.x {
+                avail_rect.x - rect.x
+            } else if rect.x + rect.width as i32 > avail_rect.x + avail_rect.width as i32 {
+                avail_rect.x + avail_rect.width as i32 - rect.x - rect.width as i32
+            } else {
+                0
+            };
+            if move_x != 0 {
+                for rect in self.normal.rect_iter_mut() {
+                    rect.x += move_x;
+                }
+                self.configure_normal_windows(conn, config);
+                self.fix_windows_visibility(&avail_rect, conn);
+            }
+        }
+        self.set_focused_by_index_window(index, window, window_type, conn, config);
+        self.focused_via_keyboard = true;
+        self.reset_window_interaction_state(conn);
+    }
+
+    /// Some(true) if the point in root coordinates is on the border of the window, None if the
+    /// window isn't tiled or floating on this workspace
+    pub fn is_on_window_border(
+        &self,
+        window: xcb_window_t,
+        x: i32,
+        y: i32,
+        config: &Config,
+    ) -> Option<bool> {
+        let rect = match self.find_window_info_by_xcb_id(window)? {
+            (index, WindowType::Normal) => self
+                .normal
+                .column_window_rects(index, config.inner_gap + config.border_size * 2)
+                .into_iter()
+                .find(|(w, _)| *w == window)
+                .map(|(_, rect)| rect)?,
+            (index, WindowType::Floating) => self.floating.index_rect(index).clone(),
+            (_, WindowType::Docked) => return None,
+        };
+        let inner_rect = Rect {
+            x: rect.x + config.border_size as i32,
+            y: rect.y + config.border_size as i32,
+            width: rect.width,
+            height: rect.height,
+        };
+        Some(!inner_rect.point_within(x, y))
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn handle_destroy_notify(
         &mut self,
+        window: xcb_window_t,
         index: usize,
         window_type: WindowType,
         conn: &Connection,
         config: &Config,
         monitor_rect: &Rect,
     ) {
-        match window_type {
-            WindowType::Normal => {
-                self.normal.remove_at(index);
-                let avail_rect = self.available_rectangle(monitor_rect, config);
-                self.fix_existing_normal_windows(&avail_rect, conn, config);
-            }
-            WindowType::Floating => {
-                self.floating.remove_at(index);
-            }
-            WindowType::Docked => {
-                self.docked.remove_at(index);
+        let collection = match window_type {
+            WindowType::Normal => &mut self.normal,
+            WindowType::Floating => &mut self.floating,
+            WindowType::Docked => &mut self.docked,
+        };
+        let was_focused = self.focused_type == window_type
+            && self.focused_idx == index
+            && collection.at_window(index) == Some(window);
+        let columns_count = collection.len();
+        collection.remove_window_at(index, window);
+        let column_removed = collection.len() < columns_count;
+        self.focus_history.retain(|w| *w != window);
+        self.maximized_column_widths.remove(&window);
+        if self.is_window_fullscreen(window) {
+            self.fullscreen = None;
+        }
+        if was_focused {
+            if !self.focus_previous_window(conn, config) {
+                match Self::neighbour_focus_after_removal(
+                    index,
+                    window_type,
+                    self.normal.len(),
+                    self.floating.len(),
+                ) {
+                    Some((new_focused_idx, new_focused_type)) => {
+                        self.set_focused_by_index(new_focused_idx, new_focused_type, conn, config)
+                    }
+                    None => {
+                        self.focused_idx = 0;
+                        self.focused_type = WindowType::Normal;
+                    }
+                }
             }
+        } else if column_removed && self.focused_type == window_type && self.focused_idx > index {
+            self.focused_idx -= 1;
+        }
+        if window_type == WindowType::Normal {
+            let avail_rect = self.available_rectangle(monitor_rect, config);
+            self.fix_existing_normal_windows(&avail_rect, conn, config);
+        }
+    }
+
+    /// Slot to focus when the focused window at `index` is gone and no window of the focus
+    /// history is left: the same index clamped to its collection, or the first window of the
+    /// other one
+    fn neighbour_focus_after_removal(
+        index: usize,
+        window_type: WindowType,
+        normal_len: usize,
+        floating_len: usize,
+    ) -> Option<(usize, WindowType)> {
+        let (len, other_type, other_len) = match window_type {
+            WindowType::Normal => (normal_len, WindowType::Floating, floating_len),
+            WindowType::Floating => (floating_len, WindowType::Normal, normal_len),
+            WindowType::Docked => (0, WindowType::Normal, normal_len),
+        };
+        if len > 0 {
+            Some((index.min(len - 1), window_type))
+        } else if other_len > 0 {
+            Some((0, other_type))
+        } else {
+            None
         }
     }
 
@@ -1162,9 +1884,7 @@ impl Workspace {
             }
         }
 
-        self.normal
-            .iter()
-            .for_each(|(window, rect, _)| conn.window_configure(*window, rect, config.border_size));
+        self.configure_normal_windows(conn, config);
 
         if self.is_visible {
             self.fix_windows_visibility(avail_rect, conn);
@@ -1172,18 +1892,64 @@ impl Workspace {
     }
 
     fn fix_windows_visibility(&mut self, monitor_rect: &Rect, conn: &Connection) {
-        for (&mut window, rect, visible) in self.normal.iter_mut() {
-            let intersects = rect.intersects_with(monitor_rect);
-            if intersects != *visible {
-                *visible = intersects;
-                if intersects {
-                    conn.map_window(window);
-                } else {
-                    conn.unmap_window(window);
+        for index in 0..self.normal.len() {
+            // the fullscreen window stays mapped wherever its column is scrolled to
+            let intersects = self.normal.index_rect(index).intersects_with(monitor_rect)
+                || self
+                    .normal
+                    .column(index)
+                    .iter()
+                    .any(|window| self.is_window_fullscreen(*window));
+            if self.normal.at_visible(index) != Some(intersects) {
+                self.normal.update_visible_at(index, intersects);
+                for window in self.normal.column(index) {
+                    if intersects {
+                        conn.map_window(*window);
+                    } else {
+                        conn.unmap_window(*window);
+                    }
                 }
             }
         }
     }
+
+    /// Configures the windows of the tiled column at `index` stacked within its rect
+    fn configure_normal_column(&self, index: usize, conn: &Connection, config: &Config) {
+        let spacing = config.inner_gap + config.border_size * 2;
+        for (window, rect) in self.normal.column_window_rects(index, spacing) {
+            match &self.fullscreen {
+                Some((fullscreen_window, fullscreen_rect)) if *fullscreen_window == window => {
+                    conn.window_configure(window, fullscreen_rect, 0)
+                }
+                _ => conn.window_configure(window, &rect, config.border_size),
+            }
+        }
+    }
+
+    /// Configures a tiled or floating window with its rect, or over the screen when fullscreen
+    fn configure_window(&self, window: xcb_window_t, conn: &Connection, config: &Config) {
+        match self.find_window_info_by_xcb_id(window) {
+            Some((index, WindowType::Normal)) => self.configure_normal_column(index, conn, config),
+            Some((_, WindowType::Floating)) => match &self.fullscreen {
+                Some((fullscreen_window, fullscreen_rect)) if *fullscreen_window == window => {
+                    conn.window_configure(window, fullscreen_rect, 0)
+                }
+                _ => {
+                    let rect = self
+                        .floating
+                        .index_rect(self.floating.index_of(window).unwrap());
+                    conn.window_configure(window, rect, config.border_size)
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn configure_normal_windows(&self, conn: &Connection, config: &Config) {
+        for index in 0..self.normal.len() {
+            self.configure_normal_column(index, conn, config);
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -1191,101 +1957,93 @@ enum WindowMouseInteraction {
     Move {
         window: xcb_window_t,
         index: usize,
+        /// state mask of the button that has to stay pressed for the interaction to go on
+        button_mask: u32,
         offset_x: i32,
         offset_y: i32,
     },
     Resize {
         window: xcb_window_t,
         index: usize,
+        button_mask: u32,
         offset_x: i32,
         offset_y: i32,
     },
 }
 
 impl Workspace {
-    pub fn handle_button_press(
+    /// Starts moving or resizing a floating window while `button` is held, returns false when
+    /// the window isn't floating or the pointer couldn't be grabbed
+    #[allow(clippy::too_many_arguments)]
+    pub fn start_mouse_interaction(
         &mut self,
-        x: i32,
-        y: i32,
         window: xcb_window_t,
-        state: u16,
-        detail: xcb_button_t,
+        button: xcb_button_t,
+        root_x: i32,
+        root_y: i32,
+        resize: bool,
         conn: &Connection,
         config: &Config,
-        time: xcb_timestamp_t,
-    ) {
-        let is_alt_pressed = (state as u32 & XCB_MOD_MASK_1) == XCB_MOD_MASK_1;
-        let is_left_button_pressed = detail == 1;
-        let is_right_button_pressed = detail == 3;
+    ) -> bool {
         trace!(
-            "handle_button_press - is_alt_pressed: {}, is_left_button_pressed: {}, is_right_button_pressed: {}",
-            is_alt_pressed, is_left_button_pressed, is_right_button_pressed
+            "start_mouse_interaction - window: {}, button: {}, resize: {}",
+            window, button, resize
         );
-        let mut handled = false;
-        if is_alt_pressed && (is_left_button_pressed || is_right_button_pressed) {
-            if let Some((window_type, index)) = {
-                if let Some(index) = self.floating.index_of(window) {
-                    Some((WindowType::Floating, index))
-                } else {
-                    None
-                }
-            } {
-                if window_type == WindowType::Floating {
-                    if self.focused_type != WindowType::Floating
-                        || self.floating.at_window(self.focused_idx).unwrap() != window
-                    {
-                        self.set_focused(window, window_type, conn, config);
-                    }
-                    if let Err(err) = conn.grab_pointer(
-                        XCB_EVENT_MASK_POINTER_MOTION | XCB_EVENT_MASK_BUTTON_RELEASE,
-                        window,
-                        window,
-                    ) {
-                        warn!(
-                            "failed to grab pointer to drag window {}, err: {}, is_alt_pressed: {}",
-                            window, err, is_alt_pressed
-                        );
-                    } else {
-                        trace!(
-                            "start dragging window {}, is_alt_pressed: {}",
-                            window, is_alt_pressed
-                        );
-                        // let window_x = self.floating.index_rect(index).x;
-                        // let window_y = self.floating.index_rect(index).y;
-                        let offset_x = x;
-                        let offset_y = y;
-                        if is_left_button_pressed {
-                            self.window_mouse_interaction = Some(WindowMouseInteraction::Move {
-                                window,
-                                index,
-                                offset_x,
-                                offset_y,
-                            })
-                        } else {
-                            self.window_mouse_interaction = Some(WindowMouseInteraction::Resize {
-                                window,
-                                index,
-                                offset_x,
-                                offset_y,
-                            })
-                        }
-                        trace!(
-                            "window_mouse_interaction: {:?}",
-                            self.window_mouse_interaction
-                        );
-                        handled = true;
-                    }
-                    conn.flush();
-                } else {
-                    trace!("clicked window is not floating window");
-                }
-            } else {
-                trace!("clicked window is not found amongst floating windows");
-            }
+        // scroll buttons are never held, they have no state mask
+        let button_mask = match button {
+            1..=5 => XCB_BUTTON_MASK_1 << (button - 1),
+            _ => return false,
+        };
+        let Some(index) = self.floating.index_of(window) else {
+            trace!("clicked window is not found amongst floating windows");
+            return false;
+        };
+        if self.is_window_fullscreen(window) {
+            trace!("clicked window is fullscreen");
+            return false;
+        }
+        if self.focused_type != WindowType::Floating
+            || self.floating.at_window(self.focused_idx) != Some(window)
+        {
+            self.set_focused(window, WindowType::Floating, conn, config);
         }
-        if !handled {
-            conn.allow_events(time);
+        if let Err(err) = conn.grab_pointer(
+            XCB_EVENT_MASK_POINTER_MOTION | XCB_EVENT_MASK_BUTTON_RELEASE,
+            window,
+            window,
+        ) {
+            warn!(
+                "failed to grab pointer to drag window {}, err: {}",
+                window, err
+            );
+            return false;
         }
+        // motion events are reported relative to the window inside its border
+        let rect = self.floating.index_rect(index);
+        let offset_x = root_x - rect.x - config.border_size as i32;
+        let offset_y = root_y - rect.y - config.border_size as i32;
+        self.window_mouse_interaction = Some(if resize {
+            WindowMouseInteraction::Resize {
+                window,
+                index,
+                button_mask,
+                offset_x,
+                offset_y,
+            }
+        } else {
+            WindowMouseInteraction::Move {
+                window,
+                index,
+                button_mask,
+                offset_x,
+                offset_y,
+            }
+        });
+        trace!(
+            "window_mouse_interaction: {:?}",
+            self.window_mouse_interaction
+        );
+        true
     }
 
     pub fn handle_button_release(&mut self, conn: &Connection) {
@@ -1304,22 +2062,20 @@ impl Workspace {
         config: &Config,
         monitor_rect: &Rect,
     ) {
-        let is_alt_pressed = (state & XCB_MOD_MASK_1) == XCB_MOD_MASK_1;
-        let is_left_button_pressed = (state & XCB_BUTTON_MASK_1) == XCB_BUTTON_MASK_1;
-        let is_right_button_pressed = (state & XCB_BUTTON_MASK_3) == XCB_BUTTON_MASK_3;
         trace!(
-            "MotionNotify x: {}, y: {}, window: {}, is_alt_pressed: {}, left_button: {}, right_button: {}",
-            x, y, window_id, is_alt_pressed, is_left_button_pressed, is_right_button_pressed
+            "MotionNotify x: {}, y: {}, window: {}, state: {}",
+            x, y, window_id, state
         );
         if let Some(interaction) = &self.window_mouse_interaction {
             match interaction {
                 WindowMouseInteraction::Move {
                     window,
                     index,
+                    button_mask,
                     offset_x,
                     offset_y,
                 } => {
-                    if !is_left_button_pressed || *window != window_id {
+                    if state & button_mask == 0 || *window != window_id {
                         self.reset_window_interaction_state(conn);
                         conn.flush();
                     } else {
@@ -1343,10 +2099,11 @@ impl Workspace {
                 WindowMouseInteraction::Resize {
                     window,
                     index,
+                    button_mask,
                     offset_x,
                     offset_y,
                 } => {
-                    if !is_right_button_pressed || *window != window_id {
+                    if state & button_mask == 0 || *window != window_id {
                         self.reset_window_interaction_state(conn);
                         conn.flush();
                     } else {
@@ -1370,6 +2127,7 @@ impl Workspace {
                         self.window_mouse_interaction = Some(WindowMouseInteraction::Resize {
                             window: *window,
                             index: *index,
+                            button_mask: *button_mask,
                             offset_x: *offset_x,
                             offset_y: *offset_y,
                         })
@@ -1386,3 +2144,39 @@ impl Workspace {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn neighbour_focus_after_removal() {
+        // a column in the middle is gone, the one that took its place gets focus
+        assert_eq!(
+            Workspace::neighbour_focus_after_removal(1, WindowType::Normal, 3, 0),
+            Some((1, WindowType::Normal))
+        );
+        // the last column is gone, the index is clamped to the new last one
+        assert_eq!(
+            Workspace::neighbour_focus_after_removal(3, WindowType::Normal, 3, 1),
+            Some((2, WindowType::Normal))
+        );
+        assert_eq!(
+            Workspace::neighbour_focus_after_removal(2, WindowType::Floating, 1, 2),
+            Some((1, WindowType::Floating))
+        );
+        // nothing left in the collection, the other one is used
+        assert_eq!(
+            Workspace::neighbour_focus_after_removal(0, WindowType::Normal, 0, 2),
+            Some((0, WindowType::Floating))
+        );
+        assert_eq!(
+            Workspace::neighbour_focus_after_removal(0, WindowType::Floating, 4, 0),
+            Some((0, WindowType::Normal))
+        );
+        assert_eq!(
+            Workspace::neighbour_focus_after_removal(0, WindowType::Normal, 0, 0),
+            None
+        );
+    }
+}
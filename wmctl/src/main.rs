@@ -0,0 +1,293 @@
+use std::{io::Write, os::unix::net::UnixStream, path::PathBuf, process::ExitCode};
+
+use base::bar_message::{self, DEFAULT_MAX_FRAME_SIZE, FrameDecoder, Message};
+use clap::{Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+#[command(subcommand_required = true)]
+struct Cli {
+    /// path to the unix socket the window manager listens on, by default $X11_WM_RUST_SOCKET
+    /// or $XDG_RUNTIME_DIR/x11_wm_rust.<display>.socket
+    #[arg(long)]
+    socket: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum Dimension {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Topic {
+    Workspace,
+    KeyboardLayout,
+    FocusedWindow,
+    Window,
+    Layout,
+    Config,
+    Mode,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum QueryKind {
+    /// the monitor/workspace/window tree as JSON
+    Tree,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// switch to the workspace with the given id
+    Workspace { id: u32 },
+    /// move the focused window to the workspace with the given id
+    MoveToWorkspace { id: u32 },
+    /// move focus to the window in the given direction
+    Focus {
+        #[arg(value_enum)]
+        direction: Direction,
+    },
+    /// move the focused window in the given direction
+    Move {
+        #[arg(value_enum)]
+        direction: Direction,
+    },
+    /// grow (positive) or shrink (negative) the focused window by the given amount of pixels
+    Resize {
+        #[arg(value_enum)]
+        dimension: Dimension,
+        #[arg(allow_hyphen_values = true)]
+        pixels: i32,
+    },
+    /// close the focused window
+    Kill,
+    /// scroll the strip so that the focused window is centered
+    Center,
+    /// run a program the same way an `exec` keybinding does
+    Exec {
+        #[arg(trailing_var_arg = true, required = true)]
+        command: Vec<String>,
+    },
+    /// send a raw command written in the keybinding action syntax of the config
+    Command {
+        #[arg(trailing_var_arg = true, required = true)]
+        command: Vec<String>,
+    },
+    /// print the requested state of the window manager
+    Query {
+        #[arg(value_enum)]
+        what: QueryKind,
+    },
+    /// print the monitor/workspace/window tree as JSON, same as `query tree`
+    Tree,
+    /// print events of the given comma separated topics until the window manager exits
+    Subscribe {
+        #[arg(value_enum, value_delimiter = ',', required = true)]
+        topics: Vec<Topic>,
+    },
+}
+
+impl Direction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Left => "left",
+            Direction::Right => "right",
+            Direction::Up => "up",
+            Direction::Down => "down",
+        }
+    }
+}
+
+impl Dimension {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Dimension::Horizontal => "horizontal",
+            Dimension::Vertical => "vertical",
+        }
+    }
+}
+
+impl Topic {
+    fn as_message_topic(&self) -> bar_message::Topic {
+        match self {
+            Topic::Workspace => bar_message::Topic::Workspace,
+            Topic::KeyboardLayout => bar_message::Topic::KeyboardLayout,
+            Topic::FocusedWindow => bar_message::Topic::FocusedWindow,
+            Topic::Window => bar_message::Topic::Window,
+            Topic::Layout => bar_message::Topic::Layout,
+            Topic::Config => bar_message::Topic::Config,
+            Topic::Mode => bar_message::Topic::Mode,
+        }
+    }
+}
+
+impl Commands {
+    /// Window manager command in the keybinding action syntax, `None` for non-command requests.
+    fn as_command_string(&self) -> Option<String> {
+        match self {
+            Commands::Workspace { id } => Some(format!("switch_to_workspace {}", id)),
+            Commands::MoveToWorkspace { id } => {
+                Some(format!("move_focused_window_to_workspace {}", id))
+            }
+            Commands::Focus { direction } => Some(format!("focus_window {}", direction.as_str())),
+            Commands::Move { direction } => Some(format!("move_window {}", direction.as_str())),
+            Commands::Resize { dimension, pixels } => Some(format!(
+                "window_size_change {} {}",
+                dimension.as_str(),
+                pixels
+            )),
+            Commands::Kill => Some("kill_focused_window".to_string()),
+            Commands::Center => Some("center_focused_window".to_string()),
+            Commands::Exec { command } => Some(format!("exec {}", command.join(" "))),
+            Commands::Command { command } => Some(command.join(" ")),
+            Commands::Query { .. } | Commands::Tree | Commands::Subscribe { .. } => None,
+        }
+    }
+}
+
+/// Blocks until the next frame from the window manager, closed connection is an error
+fn read_frame(stream: &mut UnixStream, decoder: &mut FrameDecoder) -> Result<Vec<u8>, String> {
+    match decoder.read_frame(stream) {
+        Ok(Some(frame)) => Ok(frame),
+        Ok(None) => Err("window manager closed the connection".to_string()),
+        Err(err) => Err(format!("failed to read from window manager: {}", err)),
+    }
+}
+
+fn send_command(stream: &mut UnixStream, command: &str) -> Result<(), String> {
+    stream
+        .write_all(&Message::Command(command).as_bytes())
+        .map_err(|err| format!("failed to send command: {}", err))?;
+    let mut decoder = FrameDecoder::new(DEFAULT_MAX_FRAME_SIZE);
+    loop {
+        let frame = read_frame(stream, &mut decoder)?;
+        // other clients' actions are broadcast to everyone, skip until our reply arrives
+        match Message::from_bytes(&frame) {
+            Some(Message::CommandReply(reply)) => return reply.map_err(|err| err.to_owned()),
+            Some(_) => continue,
+            None => return Err("failed to parse reply from window manager".to_string()),
+        }
+    }
+}
+
+fn query_tree(stream: &mut UnixStream) -> Result<(), String> {
+    stream
+        .write_all(&Message::RequestTree.as_bytes())
+        .map_err(|err| format!("failed to send tree request: {}", err))?;
+    let mut decoder = FrameDecoder::new(DEFAULT_MAX_FRAME_SIZE);
+    loop {
+        let frame = read_frame(stream, &mut decoder)?;
+        match Message::from_bytes(&frame) {
+            Some(Message::Tree(json)) => {
+                println!("{}", json);
+                return Ok(());
+            }
+            Some(_) => continue,
+            None => return Err("failed to parse tree from window manager".to_string()),
+        }
+    }
+}
+
+fn subscribe(stream: &mut UnixStream, topics: &[Topic]) -> Result<(), String> {
+    let message_topics = topics.iter().map(Topic::as_message_topic).collect();
+    stream
+        .write_all(&Message::Subscribe(message_topics).as_bytes())
+        .map_err(|err| format!("failed to send subscribe request: {}", err))?;
+    // initial state is sent regardless of topics, so it is still filtered below
+    let init = Message::RequestClientInit {
+        version: bar_message::PROTOCOL_VERSION,
+        supported: bar_message::supported_message_tags(),
+    };
+    stream
+        .write_all(&init.as_bytes())
+        .map_err(|err| format!("failed to send init request: {}", err))?;
+    let mut decoder = FrameDecoder::new(DEFAULT_MAX_FRAME_SIZE);
+    loop {
+        let frame = match decoder.read_frame(stream) {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return Ok(()),
+            Err(err) => return Err(format!("failed to read from window manager: {}", err)),
+        };
+        match Message::from_bytes(&frame) {
+            Some(Message::KeyboardLayout(name)) if topics.contains(&Topic::KeyboardLayout) => {
+                println!("keyboard_layout {}", name);
+            }
+            Some(Message::WorkspaceList(ids)) if topics.contains(&Topic::Workspace) => {
+                let ids = ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+                println!("workspaces {}", ids.join(" "));
+            }
+            Some(Message::WorkspaceListNamed(workspaces)) if topics.contains(&Topic::Workspace) => {
+                let workspaces = workspaces
+                    .iter()
+                    .map(|(id, name)| {
+                        if name.is_empty() {
+                            id.to_string()
+                        } else {
+                            format!("{}:{}", id, name)
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                println!("workspace_names {}", workspaces.join(" "));
+            }
+            Some(Message::WorkspaceActive(id)) if topics.contains(&Topic::Workspace) => {
+                println!("workspace {}", id);
+            }
+            Some(Message::FocusedWindow(window, title)) => {
+                println!("focused_window {} {}", window, title);
+            }
+            Some(Message::WindowOpened(window)) => println!("window_opened {}", window),
+            Some(Message::WindowClosed(window)) => println!("window_closed {}", window),
+            Some(Message::LayoutChanged(workspace_id)) => {
+                println!("layout_changed {}", workspace_id);
+            }
+            Some(Message::ConfigReloaded) => println!("config_reloaded"),
+            Some(Message::ModeChanged(mode)) if topics.contains(&Topic::Mode) => {
+                println!("mode {}", mode);
+            }
+            Some(Message::ClientInitReply(Err(err))) => return Err(err.to_owned()),
+            Some(_) => {}
+            None => return Err("failed to parse message from window manager".to_string()),
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Cli::parse();
+
+    let socket_path = args
+        .socket
+        .clone()
+        .unwrap_or_else(bar_message::socket_path_from_env);
+    let mut stream = match UnixStream::connect(&socket_path) {
+        Ok(stream) => stream,
+        Err(err) => {
+            eprintln!("failed to connect to {}: {}", socket_path.display(), err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let result = match &args.command {
+        Commands::Query {
+            what: QueryKind::Tree,
+        }
+        | Commands::Tree => query_tree(&mut stream),
+        Commands::Subscribe { topics } => subscribe(&mut stream, topics),
+        command => send_command(&mut stream, &command.as_command_string().unwrap()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -7,6 +7,8 @@
 #include <chrono>
 #include <cstdint>
 #include <fstream>
+#include <map>
+#include <string>
 #include <xcb/xcb.h>
 #include <sys/poll.h>
 
@@ -73,9 +75,13 @@ class GUI_Main
     CPU_Usage    m_cpu_usage{};
 
     std::string m_keyboard_layout_name{};
+    // binding mode of the window manager, shown unless it is "default"
+    std::string m_mode_name{};
 
     std::vector<uint32_t> m_workspaces{1};
     uint32_t              m_active_workspace = 1;
+    // names of the named workspaces by id
+    std::map<uint32_t, std::string> m_workspace_names{};
 
     Alsa m_alsa{};
 };
@@ -6,28 +6,66 @@
 #include <cstring>
 #include <iostream>
 #include <optional>
+#include <string>
 #include <sys/types.h>
 #include <type_traits>
 #include <vector>
 
+// tags and protocol version are shared with the window manager, see protocol/messages.def
+#define WM_PROTOCOL_VERSION(version) inline constexpr uint32_t PROTOCOL_VERSION = version;
+#define WM_MESSAGE(name, tag)
+#include "../../protocol/messages.def"
+#undef WM_PROTOCOL_VERSION
+#undef WM_MESSAGE
+
 enum class MessageType : uint8_t
 {
-    KeyboardLayout    = 0,
-    WorkspaceList     = 1,
-    WorkspaceActive   = 2,
-    RequestClientInit = 3,
+#define WM_PROTOCOL_VERSION(version)
+#define WM_MESSAGE(name, tag) name = tag,
+#include "../../protocol/messages.def"
+#undef WM_PROTOCOL_VERSION
+#undef WM_MESSAGE
+};
+
+// message types the bar is able to parse, announced to the window manager in RequestClientInit
+inline const std::vector<uint8_t> SUPPORTED_MESSAGE_TYPES = {
+    (uint8_t)MessageType::KeyboardLayout,
+    (uint8_t)MessageType::WorkspaceList,
+    (uint8_t)MessageType::WorkspaceActive,
+    (uint8_t)MessageType::ClientInitReply,
+    (uint8_t)MessageType::ModeChanged,
+    (uint8_t)MessageType::WorkspaceListNamed,
 };
 
 struct RequestClientInit
 {
+    uint32_t             version = PROTOCOL_VERSION;
+    std::vector<uint8_t> supported{SUPPORTED_MESSAGE_TYPES};
+};
+
+struct WorkspaceNamed
+{
+    uint32_t    id;
+    // empty for workspaces without a name
+    std::string name;
+};
+
+struct ClientInitReply
+{
+    // set when the window manager rejected the bar, e.g. due to a protocol version mismatch
+    std::optional<std::string> error;
+    std::vector<uint8_t>       supported;
 };
 
 union MessageRaw
 {
-    std::string                keyboard_layout_name;
-    std::uint32_t              workspace_active_id = 0;
-    std::vector<std::uint32_t> workspace_list_ids;
-    RequestClientInit          request_client_init;
+    std::string                 keyboard_layout_name;
+    std::uint32_t               workspace_active_id = 0;
+    std::vector<std::uint32_t>  workspace_list_ids;
+    RequestClientInit           request_client_init;
+    ClientInitReply             client_init_reply;
+    std::string                 mode_name;
+    std::vector<WorkspaceNamed> workspace_list_named;
 
     MessageRaw() {}
     ~MessageRaw() {}
@@ -55,6 +93,21 @@ template <> struct MessageTypeDataType<MessageType::RequestClientInit>
     using type = RequestClientInit;
 };
 
+template <> struct MessageTypeDataType<MessageType::ClientInitReply>
+{
+    using type = ClientInitReply;
+};
+
+template <> struct MessageTypeDataType<MessageType::ModeChanged>
+{
+    using type = std::string;
+};
+
+template <> struct MessageTypeDataType<MessageType::WorkspaceListNamed>
+{
+    using type = std::vector<WorkspaceNamed>;
+};
+
 template <MessageType E> using MessageTypeDataTypeT = typename MessageTypeDataType<E>::type;
 
 template <auto E> inline constexpr std::integral_constant<decltype(E), E> tag{};
@@ -80,6 +133,22 @@ class Message
         {
             new (&m_message_raw.workspace_list_ids) std::vector(std::forward<T>(value));
         }
+        else if constexpr (E == MessageType::RequestClientInit)
+        {
+            new (&m_message_raw.request_client_init) RequestClientInit(std::forward<T>(value));
+        }
+        else if constexpr (E == MessageType::ClientInitReply)
+        {
+            new (&m_message_raw.client_init_reply) ClientInitReply(std::forward<T>(value));
+        }
+        else if constexpr (E == MessageType::ModeChanged)
+        {
+            new (&m_message_raw.mode_name) std::string(std::forward<T>(value));
+        }
+        else if constexpr (E == MessageType::WorkspaceListNamed)
+        {
+            new (&m_message_raw.workspace_list_named) std::vector(std::forward<T>(value));
+        }
     }
 
     ~Message() { deinit(); }
@@ -97,6 +166,13 @@ class Message
                 new (&m_message_raw.keyboard_layout_name)
                     std::string(std::move(other.m_message_raw.keyboard_layout_name));
                 break;
+            case MessageType::ModeChanged:
+                new (&m_message_raw.mode_name) std::string(std::move(other.m_message_raw.mode_name));
+                break;
+            case MessageType::WorkspaceListNamed:
+                new (&m_message_raw.workspace_list_named)
+                    std::vector(std::move(other.m_message_raw.workspace_list_named));
+                break;
             case MessageType::WorkspaceActive:
                 new (&m_message_raw.workspace_active_id) uint32_t(other.m_message_raw.workspace_active_id);
                 break;
@@ -104,7 +180,15 @@ class Message
                 new (&m_message_raw.workspace_list_ids) std::vector(other.m_message_raw.workspace_list_ids);
                 break;
             case MessageType::RequestClientInit:
+                new (&m_message_raw.request_client_init)
+                    RequestClientInit(std::move(other.m_message_raw.request_client_init));
+                break;
+            case MessageType::ClientInitReply:
+                new (&m_message_raw.client_init_reply)
+                    ClientInitReply(std::move(other.m_message_raw.client_init_reply));
                 break;
+            default:
+                Assert(false);
             }
         }
     }
@@ -124,6 +208,13 @@ class Message
                     new (&m_message_raw.keyboard_layout_name)
                         std::string(std::move(other.m_message_raw.keyboard_layout_name));
                     break;
+                case MessageType::ModeChanged:
+                    new (&m_message_raw.mode_name) std::string(std::move(other.m_message_raw.mode_name));
+                    break;
+                case MessageType::WorkspaceListNamed:
+                    new (&m_message_raw.workspace_list_named)
+                        std::vector(std::move(other.m_message_raw.workspace_list_named));
+                    break;
                 case MessageType::WorkspaceActive:
                     new (&m_message_raw.workspace_active_id) uint32_t(other.m_message_raw.workspace_active_id);
                     break;
@@ -131,7 +222,15 @@ class Message
                     new (&m_message_raw.workspace_list_ids) std::vector(other.m_message_raw.workspace_list_ids);
                     break;
                 case MessageType::RequestClientInit:
+                    new (&m_message_raw.request_client_init)
+                        RequestClientInit(std::move(other.m_message_raw.request_client_init));
+                    break;
+                case MessageType::ClientInitReply:
+                    new (&m_message_raw.client_init_reply)
+                        ClientInitReply(std::move(other.m_message_raw.client_init_reply));
                     break;
+                default:
+                    Assert(false);
                 }
             }
         }
@@ -152,6 +251,14 @@ class Message
         {
             return &m_message_raw.keyboard_layout_name;
         }
+        else if constexpr (E == MessageType::ModeChanged)
+        {
+            return &m_message_raw.mode_name;
+        }
+        else if constexpr (E == MessageType::WorkspaceListNamed)
+        {
+            return &m_message_raw.workspace_list_named;
+        }
         else if constexpr (E == MessageType::WorkspaceActive)
         {
             return &m_message_raw.workspace_active_id;
@@ -164,6 +271,10 @@ class Message
         {
             return &m_message_raw.request_client_init;
         }
+        else if constexpr (E == MessageType::ClientInitReply)
+        {
+            return &m_message_raw.client_init_reply;
+        }
         Assert(false);
     }
 
@@ -175,12 +286,20 @@ class Message
         {
         case MessageType::KeyboardLayout:
             return &m_message_raw.keyboard_layout_name;
+        case MessageType::ModeChanged:
+            return &m_message_raw.mode_name;
+        case MessageType::WorkspaceListNamed:
+            return &m_message_raw.workspace_list_named;
         case MessageType::WorkspaceActive:
             return &m_message_raw.workspace_active_id;
         case MessageType::WorkspaceList:
             return &m_message_raw.workspace_list_ids;
         case MessageType::RequestClientInit:
             return &m_message_raw.request_client_init;
+        case MessageType::ClientInitReply:
+            return &m_message_raw.client_init_reply;
+        default:
+            break;
         }
         Assert(false);
     }
@@ -221,8 +340,17 @@ class Message
             break;
         }
         case MessageType::RequestClientInit:
+        {
+            auto&       init          = m_message_raw.request_client_init;
+            const char* version_bytes = reinterpret_cast<const char*>(&init.version);
+            bytes.insert(bytes.end(), version_bytes, version_bytes + sizeof(init.version));
+            bytes.insert(bytes.end(), init.supported.begin(), init.supported.end());
             break;
         }
+        default:
+            // the rest is only ever sent by the window manager
+            Assert(false);
+        }
         size_t size_bytes = bytes.size() - sizeof(size_t);
         std::memcpy(bytes.data(), &size_bytes, sizeof(size_t));
     }
@@ -246,6 +374,11 @@ class Message
             std::string layout_name(data, data_size);
             return Message(tag<MessageType::KeyboardLayout>, std::move(layout_name));
         }
+        case (uint8_t)MessageType::ModeChanged:
+        {
+            std::string mode_name(data, data_size);
+            return Message(tag<MessageType::ModeChanged>, std::move(mode_name));
+        }
         case (uint8_t)MessageType::WorkspaceActive:
         {
             uint32_t workspace_id;
@@ -278,6 +411,59 @@ class Message
 
             return Message(tag<MessageType::WorkspaceList>, std::move(workspaces));
         }
+        case (uint8_t)MessageType::WorkspaceListNamed:
+        {
+            if (data_size < sizeof(size_t))
+            {
+                std::cerr << "WorkspaceListNamed: data_size < sizeof(size_t)" << '\n';
+                return std::nullopt;
+            }
+            size_t len_workspaces;
+            std::memcpy(&len_workspaces, data, sizeof(len_workspaces));
+            size_t offset = sizeof(size_t);
+
+            // each entry is the id followed by the name length and the name itself
+            std::vector<WorkspaceNamed> workspaces{};
+            for (size_t i = 0; i < len_workspaces; ++i)
+            {
+                uint32_t id;
+                uint32_t name_len;
+                if (data_size - offset < sizeof(id) + sizeof(name_len))
+                {
+                    std::cerr << "WorkspaceListNamed: entry " << i << " is truncated" << '\n';
+                    return std::nullopt;
+                }
+                std::memcpy(&id, data + offset, sizeof(id));
+                std::memcpy(&name_len, data + offset + sizeof(id), sizeof(name_len));
+                offset += sizeof(id) + sizeof(name_len);
+                if (data_size - offset < name_len)
+                {
+                    std::cerr << "WorkspaceListNamed: name of entry " << i << " is truncated" << '\n';
+                    return std::nullopt;
+                }
+                workspaces.push_back(WorkspaceNamed{id, std::string(data + offset, name_len)});
+                offset += name_len;
+            }
+            return Message(tag<MessageType::WorkspaceListNamed>, std::move(workspaces));
+        }
+        case (uint8_t)MessageType::ClientInitReply:
+        {
+            if (data_size < 1)
+            {
+                std::cerr << "ClientInitReply: data_size < 1" << '\n';
+                return std::nullopt;
+            }
+            ClientInitReply reply{};
+            if (data[0] == 0)
+            {
+                reply.supported.assign(data + 1, data + data_size);
+            }
+            else
+            {
+                reply.error = std::string(data + 1, data_size - 1);
+            }
+            return Message(tag<MessageType::ClientInitReply>, std::move(reply));
+        }
         default:
             std::cerr << "msg_type_maybe: " << msg_type_maybe << '\n';
             return std::nullopt;
@@ -294,12 +480,24 @@ class Message
         case MessageType::KeyboardLayout:
             m_message_raw.keyboard_layout_name.~basic_string();
             break;
+        case MessageType::ModeChanged:
+            m_message_raw.mode_name.~basic_string();
+            break;
+        case MessageType::WorkspaceListNamed:
+            m_message_raw.workspace_list_named.~vector();
+            break;
         case MessageType::WorkspaceActive:
             break;
         case MessageType::WorkspaceList:
             m_message_raw.workspace_list_ids.~vector();
             break;
         case MessageType::RequestClientInit:
+            m_message_raw.request_client_init.~RequestClientInit();
+            break;
+        case MessageType::ClientInitReply:
+            m_message_raw.client_init_reply.~ClientInitReply();
+            break;
+        default:
             break;
         }
     }
@@ -239,6 +239,19 @@ void GUI_Main::render()
 
                 break;
             }
+            case MessageType::WorkspaceListNamed:
+            {
+                auto workspaces = message.get<MessageType::WorkspaceListNamed>();
+                m_workspace_names.clear();
+                for (auto& workspace : *workspaces)
+                {
+                    if (!workspace.name.empty())
+                    {
+                        m_workspace_names[workspace.id] = std::move(workspace.name);
+                    }
+                }
+                break;
+            }
             case MessageType::WorkspaceActive:
             {
                 auto active_workspace = message.get<MessageType::WorkspaceActive>();
@@ -246,7 +259,22 @@ void GUI_Main::render()
                 std::cout << "updated active worskpace: " << m_active_workspace << '\n';
                 break;
             }
-            case MessageType::RequestClientInit:
+            case MessageType::ModeChanged:
+            {
+                auto str_ptr = message.get<MessageType::ModeChanged>();
+                m_mode_name  = std::move(*str_ptr);
+                break;
+            }
+            case MessageType::ClientInitReply:
+            {
+                auto reply = message.get<MessageType::ClientInitReply>();
+                if (reply->error.has_value())
+                {
+                    std::cerr << "window manager rejected the bar: " << reply->error.value() << '\n';
+                }
+                break;
+            }
+            default:
                 break;
             }
         }
@@ -281,17 +309,29 @@ void GUI_Main::render()
             for (auto workspace : m_workspaces)
             {
                 ImGui::SameLine();
+                std::string label = std::to_string(workspace);
+                if (auto name_it = m_workspace_names.find(workspace); name_it != m_workspace_names.end())
+                {
+                    label += ":" + name_it->second;
+                }
                 if (workspace == m_active_workspace)
                 {
-                    ImGui::Text("[ %d ]", workspace);
+                    ImGui::Text("[ %s ]", label.data());
                 }
                 else
                 {
-                    ImGui::Text("  %d  ", workspace);
+                    ImGui::Text("  %s  ", label.data());
                 }
                 ImGui::SameLine();
                 ImGui::SeparatorEx(ImGuiSeparatorFlags_Vertical);
             }
+            if (!m_mode_name.empty() && m_mode_name != "default")
+            {
+                ImGui::SameLine();
+                ImGui::Text("Mode: %s", m_mode_name.data());
+                ImGui::SameLine();
+                ImGui::SeparatorEx(ImGuiSeparatorFlags_Vertical);
+            }
             ImGui::SameLine();
             ImGui::Dummy(ImVec2(50.0f, text_height));
             ImGui::SameLine();
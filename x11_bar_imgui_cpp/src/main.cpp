@@ -13,6 +13,31 @@
 #include "vulkan_renderer.h"
 
 #include <chrono>
+#include <cstdlib>
+#include <string>
+
+// same lookup as socket_path_from_env in base/src/bar_message.rs
+static std::string default_wm_unix_socket_path()
+{
+    const char* overridden = std::getenv("X11_WM_RUST_SOCKET");
+    if (overridden != nullptr && overridden[0] != '\0')
+        return overridden;
+
+    const char* runtime_dir = std::getenv("XDG_RUNTIME_DIR");
+    std::string dir         = runtime_dir != nullptr && runtime_dir[0] != '\0' ? runtime_dir : "/tmp";
+
+    // "host:1.0" -> "1"
+    const char* display_env = std::getenv("DISPLAY");
+    std::string display     = display_env != nullptr ? display_env : "";
+    if (auto colon = display.rfind(':'); colon != std::string::npos)
+        display = display.substr(colon + 1);
+    if (auto dot = display.find('.'); dot != std::string::npos)
+        display = display.substr(0, dot);
+    if (display.empty())
+        display = "0";
+
+    return dir + "/x11_wm_rust." + display + ".socket";
+}
 
 static void glfw_error_callback(int error, const char* description)
 {
@@ -28,7 +53,7 @@ int main(int argc, char** argv)
     auto        screen_location     = ScreenLocation::Top;
     int         padding_top         = 5;
     int         window_height       = 25;
-    const char* wm_unix_socket_path = "/tmp/x11_wm_rust.socket";
+    std::string wm_unix_socket_path = default_wm_unix_socket_path();
 
     if (argc > 1)
     {
@@ -40,7 +65,8 @@ int main(int argc, char** argv)
                 std::cerr << "x11_bar, help options:\n"
                           << "\t--help      - to get help on possible command line arguments\n"
                           << "\t--font-path - to provide path to a font file\n"
-                          << "\t--font-size - to provide font size\n";
+                          << "\t--font-size - to provide font size\n"
+                          << "\t--socket    - to provide path to the window manager socket\n";
                 return 1;
             }
             if (arg == Lit("--font-path"))
@@ -82,6 +108,17 @@ int main(int argc, char** argv)
                 }
                 continue;
             }
+            if (arg == Lit("--socket"))
+            {
+                if (i + 1 == argc)
+                {
+                    std::cerr << "no value provided for --socket command line argument.\n";
+                    return 1;
+                }
+                ++i;
+                wm_unix_socket_path = argv[i];
+                continue;
+            }
             if (arg == Lit("--location"))
             {
                 if (i + 1 == argc)
@@ -128,7 +165,7 @@ int main(int argc, char** argv)
 
     VulkanRenderer renderer(window);
 
-    GUI_Main app{window, font_path, font_size, screen_location, window_height, wm_unix_socket_path};
+    GUI_Main app{window, font_path, font_size, screen_location, window_height, wm_unix_socket_path.c_str()};
 
     ImVec4 clear_color = ImVec4(0.45f, 0.55f, 0.60f, 1.00f);
 
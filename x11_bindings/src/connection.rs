@@ -1,62 +1,79 @@
-use std::{collections::HashMap, ffi::CString, mem::MaybeUninit, os::fd::RawFd};
+use std::{cell::Cell, collections::HashMap, ffi::CString, mem::MaybeUninit, os::fd::RawFd};
 
 use base::Rect;
+use serde::Serialize;
 
 use crate::bindings::{
     XCB_ACCESS, XCB_ALLOC, XCB_ALLOW_ASYNC_POINTER, XCB_ALLOW_REPLAY_POINTER, XCB_ATOM,
-    XCB_ATOM_ATOM, XCB_ATOM_STRING, XCB_ATOM_WM_CLASS, XCB_BUTTON_MASK_1, XCB_BUTTON_PRESS,
-    XCB_BUTTON_RELEASE, XCB_CLIENT_MESSAGE, XCB_COLORMAP, XCB_CONFIG_WINDOW_BORDER_WIDTH,
-    XCB_CONFIG_WINDOW_HEIGHT, XCB_CONFIG_WINDOW_STACK_MODE, XCB_CONFIG_WINDOW_WIDTH,
-    XCB_CONFIG_WINDOW_X, XCB_CONFIG_WINDOW_Y, XCB_COORD_MODE_ORIGIN, XCB_COPY_FROM_PARENT,
-    XCB_CURRENT_TIME, XCB_CURSOR, XCB_CW_CURSOR, XCB_DESTROY_NOTIFY, XCB_DRAWABLE,
-    XCB_ENTER_NOTIFY, XCB_EVENT_MASK_BUTTON_PRESS, XCB_EVENT_MASK_NO_EVENT, XCB_FOCUS_IN,
-    XCB_FOCUS_OUT, XCB_FONT, XCB_G_CONTEXT, XCB_GET_PROPERTY_TYPE_ANY, XCB_GRAB_MODE_ASYNC,
-    XCB_GRAB_MODE_SYNC, XCB_ID_CHOICE, XCB_IMAGE_FORMAT_XY_PIXMAP, XCB_IMAGE_FORMAT_Z_PIXMAP,
+    XCB_ATOM_ATOM, XCB_ATOM_CARDINAL, XCB_ATOM_STRING, XCB_ATOM_WM_CLASS, XCB_ATOM_WM_NAME,
+    XCB_BUTTON_PRESS, XCB_BUTTON_RELEASE, XCB_CLIENT_MESSAGE, XCB_COLORMAP,
+    XCB_CONFIG_WINDOW_BORDER_WIDTH, XCB_CONFIG_WINDOW_HEIGHT, XCB_CONFIG_WINDOW_STACK_MODE,
+    XCB_CONFIG_WINDOW_WIDTH, XCB_CONFIG_WINDOW_X, XCB_CONFIG_WINDOW_Y, XCB_COORD_MODE_ORIGIN,
+    XCB_COPY_FROM_PARENT, XCB_CURRENT_TIME, XCB_CURSOR, XCB_CW_CURSOR, XCB_DESTROY_NOTIFY,
+    XCB_DRAWABLE, XCB_ENTER_NOTIFY, XCB_EVENT_MASK_BUTTON_PRESS, XCB_EVENT_MASK_BUTTON_RELEASE,
+    XCB_EVENT_MASK_NO_EVENT, XCB_FOCUS_IN, XCB_FOCUS_OUT, XCB_FONT, XCB_G_CONTEXT,
+    XCB_GET_PROPERTY_TYPE_ANY, XCB_GRAB_MODE_ASYNC, XCB_GRAB_MODE_SYNC, XCB_GRAB_STATUS_SUCCESS,
+    XCB_ID_CHOICE, XCB_IMAGE_FORMAT_XY_PIXMAP, XCB_IMAGE_FORMAT_Z_PIXMAP,
     XCB_IMAGE_ORDER_LSB_FIRST, XCB_IMPLEMENTATION, XCB_INPUT_FOCUS_POINTER_ROOT, XCB_KEY_PRESS,
-    XCB_LEAVE_NOTIFY, XCB_LENGTH, XCB_MAP_REQUEST, XCB_MATCH, XCB_MOD_MASK_1, XCB_MOD_MASK_ANY,
-    XCB_MOTION_NOTIFY, XCB_NAME, XCB_NONE, XCB_PIXMAP, XCB_PROP_MODE_REPLACE, XCB_RANDR_NOTIFY,
-    XCB_RANDR_NOTIFY_MASK_SCREEN_CHANGE, XCB_RANDR_SCREEN_CHANGE_NOTIFY, XCB_SHAPE_SK_BOUNDING,
-    XCB_SHAPE_SO_SET, XCB_STACK_MODE_ABOVE, XCB_UNMAP_NOTIFY, XCB_WINDOW,
-    XCB_WINDOW_CLASS_INPUT_OUTPUT, XCB_XKB_EVENT_TYPE_CONTROLS_NOTIFY,
-    XCB_XKB_EVENT_TYPE_NAMES_NOTIFY, XCB_XKB_EVENT_TYPE_STATE_NOTIFY, XCB_XKB_STATE_NOTIFY,
-    XCloseDisplay, XDefaultRootWindow, XDefineCursor, XDisplay, XFree, XGetAtomName,
-    XGetXCBConnection, XKB_CONTEXT_NO_FLAGS, XKB_KEYMAP_COMPILE_NO_FLAGS,
-    XKB_KEYMAP_FORMAT_TEXT_V1, XKB_X11_MIN_MAJOR_XKB_VERSION, XKB_X11_MIN_MINOR_XKB_VERSION,
-    XKB_X11_SETUP_XKB_EXTENSION_NO_FLAGS, XOpenDisplay, XcursorFilenameLoadCursor,
-    XkbAllControlsMaskConst, XkbAllocKeyboard, XkbFreeKeyboard, XkbGetControls, XkbGetNames,
-    XkbGroupNamesMask, XkbSymbolsNameMask, xcb_allow_events, xcb_allow_t, xcb_arc_t, xcb_atom_t,
-    xcb_button_press_event_t, xcb_button_release_event_t, xcb_button_t, xcb_change_gc,
-    xcb_change_property, xcb_change_window_attributes, xcb_change_window_attributes_checked,
-    xcb_client_message_data_t, xcb_client_message_event_t, xcb_configure_window,
-    xcb_configure_window_checked, xcb_connection_has_error, xcb_connection_t, xcb_create_cursor,
-    xcb_create_cursor_checked, xcb_create_gc, xcb_create_gc_checked, xcb_create_pixmap,
-    xcb_create_pixmap_checked, xcb_create_window, xcb_cursor_context_free, xcb_cursor_context_new,
-    xcb_cursor_context_t, xcb_cursor_load_cursor, xcb_cursor_t, xcb_cw_t,
-    xcb_destroy_notify_event_t, xcb_destroy_window, xcb_disconnect, xcb_enter_notify_event_t,
-    xcb_event_mask_t, xcb_ewmh_connection_t, xcb_ewmh_get_atoms_reply_t,
+    XCB_KEY_RELEASE, XCB_LEAVE_NOTIFY, XCB_LENGTH, XCB_MAP_REQUEST, XCB_MAPPING_NOTIFY, XCB_MATCH,
+    XCB_MOD_MASK_1, XCB_MOTION_NOTIFY, XCB_NAME, XCB_NONE, XCB_PIXMAP, XCB_PROP_MODE_REPLACE,
+    XCB_PROPERTY_NOTIFY, XCB_RANDR_NOTIFY, XCB_RANDR_NOTIFY_MASK_SCREEN_CHANGE,
+    XCB_RANDR_SCREEN_CHANGE_NOTIFY, XCB_SHAPE_SK_BOUNDING, XCB_SHAPE_SO_SET, XCB_STACK_MODE_ABOVE,
+    XCB_UNMAP_NOTIFY, XCB_WINDOW, XCB_WINDOW_CLASS_INPUT_OUTPUT,
+    XCB_XKB_EVENT_TYPE_CONTROLS_NOTIFY, XCB_XKB_EVENT_TYPE_MAP_NOTIFY,
+    XCB_XKB_EVENT_TYPE_NAMES_NOTIFY, XCB_XKB_EVENT_TYPE_NEW_KEYBOARD_NOTIFY,
+    XCB_XKB_EVENT_TYPE_STATE_NOTIFY, XCB_XKB_MAP_NOTIFY, XCB_XKB_NEW_KEYBOARD_NOTIFY,
+    XCB_XKB_STATE_NOTIFY, XCloseDisplay, XDefaultRootWindow, XDefineCursor, XDisplay, XFree,
+    XGetAtomName, XGetXCBConnection, XKB_CONTEXT_NO_FLAGS, XKB_KEY_NoSymbol,
+    XKB_KEYMAP_COMPILE_NO_FLAGS, XKB_KEYMAP_FORMAT_TEXT_V1, XKB_X11_MIN_MAJOR_XKB_VERSION,
+    XKB_X11_MIN_MINOR_XKB_VERSION, XKB_X11_SETUP_XKB_EXTENSION_NO_FLAGS, XOpenDisplay,
+    XcursorFilenameLoadCursor, XkbAllControlsMaskConst, XkbAllocKeyboard, XkbFreeKeyboard,
+    XkbGetControls, XkbGetNames, XkbGroupNamesMask, XkbSymbolsNameMask, xcb_allow_events,
+    xcb_allow_t, xcb_arc_t, xcb_atom_t, xcb_button_press_event_t, xcb_button_release_event_t,
+    xcb_button_t, xcb_change_gc, xcb_change_property, xcb_change_window_attributes,
+    xcb_change_window_attributes_checked, xcb_client_message_data_t, xcb_client_message_event_t,
+    xcb_configure_window, xcb_configure_window_checked, xcb_connection_has_error, xcb_connection_t,
+    xcb_create_cursor, xcb_create_cursor_checked, xcb_create_gc, xcb_create_gc_checked,
+    xcb_create_pixmap, xcb_create_pixmap_checked, xcb_create_window, xcb_cursor_context_free,
+    xcb_cursor_context_new, xcb_cursor_context_t, xcb_cursor_load_cursor, xcb_cursor_t, xcb_cw_t,
+    xcb_destroy_notify_event_t, xcb_destroy_window, xcb_discard_reply, xcb_disconnect,
+    xcb_enter_notify_event_t, xcb_event_mask_t, xcb_ewmh_connection_t, xcb_ewmh_get_atoms_reply_t,
     xcb_ewmh_get_atoms_reply_wipe, xcb_ewmh_get_cardinal_reply, xcb_ewmh_get_wm_desktop,
-    xcb_ewmh_get_wm_strut_partial, xcb_ewmh_get_wm_strut_partial_reply,
-    xcb_ewmh_get_wm_window_type, xcb_ewmh_get_wm_window_type_reply, xcb_ewmh_init_atoms,
-    xcb_ewmh_init_atoms_replies, xcb_ewmh_set_supported_checked, xcb_ewmh_wm_strut_partial_t,
-    xcb_flush, xcb_focus_in_event_t, xcb_focus_out_event_t, xcb_free_gc, xcb_free_pixmap, xcb_gc_t,
+    xcb_ewmh_get_wm_state, xcb_ewmh_get_wm_state_reply, xcb_ewmh_get_wm_strut_partial,
+    xcb_ewmh_get_wm_strut_partial_reply, xcb_ewmh_get_wm_window_type,
+    xcb_ewmh_get_wm_window_type_reply, xcb_ewmh_init_atoms, xcb_ewmh_init_atoms_replies,
+    xcb_ewmh_set_desktop_names, xcb_ewmh_set_supported_checked,
+    xcb_ewmh_wm_state_action_t_XCB_EWMH_WM_STATE_ADD,
+    xcb_ewmh_wm_state_action_t_XCB_EWMH_WM_STATE_REMOVE, xcb_ewmh_wm_strut_partial_t, xcb_flush,
+    xcb_focus_in_event_t, xcb_focus_out_event_t, xcb_free_gc, xcb_free_pixmap, xcb_gc_t,
     xcb_gcontext_t, xcb_generate_id, xcb_generic_error_t, xcb_generic_event_t,
     xcb_get_file_descriptor, xcb_get_property, xcb_get_property_reply, xcb_get_property_value,
     xcb_get_property_value_length, xcb_get_setup, xcb_get_window_attributes,
-    xcb_get_window_attributes_reply, xcb_grab_button, xcb_grab_key, xcb_grab_pointer,
-    xcb_grab_pointer_reply, xcb_icccm_get_wm_normal_hints, xcb_icccm_get_wm_normal_hints_reply,
+    xcb_get_window_attributes_reply, xcb_grab_button, xcb_grab_key, xcb_grab_keyboard,
+    xcb_grab_keyboard_reply, xcb_grab_pointer, xcb_grab_pointer_reply,
+    xcb_icccm_get_wm_normal_hints, xcb_icccm_get_wm_normal_hints_reply,
+    xcb_icccm_get_wm_transient_for, xcb_icccm_get_wm_transient_for_reply,
     xcb_icccm_set_wm_normal_hints, xcb_image_create, xcb_image_create_native, xcb_image_destroy,
     xcb_image_put, xcb_intern_atom, xcb_intern_atom_cookie_t, xcb_intern_atom_reply,
-    xcb_key_press_event_t, xcb_keycode_t, xcb_leave_notify_event_t, xcb_map_request_event_t,
-    xcb_map_window, xcb_mod_mask_t, xcb_motion_notify_event_t, xcb_notify_mode_t, xcb_pixmap_t,
-    xcb_point_t, xcb_poll_for_event, xcb_poly_fill_arc, xcb_poly_fill_rectangle, xcb_poly_point,
-    xcb_put_image, xcb_query_extension, xcb_query_extension_reply, xcb_randr_notify_event_t,
+    xcb_key_press_event_t, xcb_key_release_event_t, xcb_keycode_t, xcb_leave_notify_event_t,
+    xcb_map_request_event_t, xcb_map_window, xcb_mapping_notify_event_t,
+    xcb_mapping_t_XCB_MAPPING_KEYBOARD, xcb_mod_mask_t, xcb_motion_notify_event_t,
+    xcb_notify_mode_t, xcb_pixmap_t, xcb_point_t, xcb_poll_for_event, xcb_poly_fill_arc,
+    xcb_poly_fill_rectangle, xcb_poly_point, xcb_property_notify_event_t, xcb_put_image,
+    xcb_query_extension, xcb_query_extension_reply, xcb_randr_notify_event_t,
     xcb_randr_screen_change_notify_event_t, xcb_randr_select_input_checked, xcb_rectangle_t,
     xcb_request_check, xcb_screen_t, xcb_send_event, xcb_set_input_focus, xcb_setup_roots_iterator,
-    xcb_shape_mask, xcb_size_hints_t, xcb_timestamp_t, xcb_ungrab_key, xcb_ungrab_pointer,
-    xcb_unmap_notify_event_t, xcb_unmap_window, xcb_wait_for_event, xcb_window_t,
-    xcb_xkb_select_events, xcb_xkb_state_notify_event_t, xkb_context, xkb_context_new, xkb_keymap,
-    xkb_keymap_get_as_string, xkb_state, xkb_state_update_mask,
-    xkb_x11_get_core_keyboard_device_id, xkb_x11_keymap_new_from_device,
+    xcb_shape_mask, xcb_size_hints_t, xcb_timestamp_t, xcb_ungrab_button, xcb_ungrab_key,
+    xcb_ungrab_keyboard, xcb_ungrab_pointer, xcb_unmap_notify_event_t, xcb_unmap_window,
+    xcb_wait_for_event, xcb_window_t,
+    xcb_xkb_per_client_flag_t_XCB_XKB_PER_CLIENT_FLAG_DETECTABLE_AUTO_REPEAT,
+    xcb_xkb_per_client_flags, xcb_xkb_select_events, xcb_xkb_state_notify_event_t, xkb_context,
+    xkb_context_new, xkb_keycode_t, xkb_keymap, xkb_keymap_get_as_string,
+    xkb_keymap_key_get_syms_by_level, xkb_keymap_max_keycode, xkb_keymap_min_keycode,
+    xkb_keymap_num_layouts_for_key, xkb_keymap_num_levels_for_key, xkb_keymap_unref,
+    xkb_keysym_flags_XKB_KEYSYM_CASE_INSENSITIVE, xkb_keysym_flags_XKB_KEYSYM_NO_FLAGS,
+    xkb_keysym_from_name, xkb_keysym_t, xkb_state, xkb_state_key_get_one_sym, xkb_state_unref,
+    xkb_state_update_mask, xkb_x11_get_core_keyboard_device_id, xkb_x11_keymap_new_from_device,
     xkb_x11_setup_xkb_extension, xkb_x11_state_new_from_device,
 };
 
@@ -68,6 +85,7 @@ pub enum ConnectionError {
     UnableToSetSupportedEwmhAtoms,
     UnableToChangeWindowAttrs((xcb_window_t, String)),
     UnableToGrabPointer((xcb_window_t, String)),
+    UnableToGrabKeyboard(String),
     UnableToQueryRandrExtension(String),
 }
 
@@ -92,6 +110,9 @@ impl std::fmt::Display for ConnectionError {
                 "Unable to grab pointer for window {}, error {}",
                 window, err
             ),
+            ConnectionError::UnableToGrabKeyboard(err) => {
+                write!(f, "Unable to grab keyboard, error {}", err)
+            }
             ConnectionError::UnableToQueryRandrExtension(err) => {
                 write!(f, "Unable to query randr extension, error {}", err)
             }
@@ -109,8 +130,9 @@ pub struct Connection {
     xkb_event_base: u8,
     xkb_device_id: i32,
     xkb_context: *mut xkb_context,
-    xkb_keymap: *mut xkb_keymap,
-    xkb_state: *mut xkb_state,
+    // replaced when the keyboard mapping changes
+    xkb_keymap: Cell<*mut xkb_keymap>,
+    xkb_state: Cell<*mut xkb_state>,
 }
 
 // unsafe impl Send for Connection {}
@@ -240,7 +262,9 @@ impl Connection {
         let xkb_state = unsafe { xkb_x11_state_new_from_device(xkb_keymap, conn, xkb_device_id) };
         let mask = XCB_XKB_EVENT_TYPE_STATE_NOTIFY
             | XCB_XKB_EVENT_TYPE_CONTROLS_NOTIFY
-            | XCB_XKB_EVENT_TYPE_NAMES_NOTIFY;
+            | XCB_XKB_EVENT_TYPE_NAMES_NOTIFY
+            | XCB_XKB_EVENT_TYPE_NEW_KEYBOARD_NOTIFY
+            | XCB_XKB_EVENT_TYPE_MAP_NOTIFY;
         unsafe {
             xcb_xkb_select_events(
                 conn,
@@ -253,6 +277,22 @@ impl Connection {
                 std::ptr::null_mut(),
             )
         };
+        // held keys produce repeated KeyPress without KeyRelease in between, so that release
+        // bindings and modifier taps aren't triggered by autorepeat
+        let detectable_auto_repeat =
+            xcb_xkb_per_client_flag_t_XCB_XKB_PER_CLIENT_FLAG_DETECTABLE_AUTO_REPEAT;
+        unsafe {
+            let cookie = xcb_xkb_per_client_flags(
+                conn,
+                xkb_device_id as u16,
+                detectable_auto_repeat,
+                detectable_auto_repeat,
+                0,
+                0,
+                0,
+            );
+            xcb_discard_reply(conn, cookie.sequence);
+        };
 
         Ok(Self {
             display,
@@ -262,8 +302,8 @@ impl Connection {
             xkb_event_base,
             xkb_device_id,
             xkb_context,
-            xkb_keymap,
-            xkb_state,
+            xkb_keymap: Cell::new(xkb_keymap),
+            xkb_state: Cell::new(xkb_state),
             randr_event_base,
         })
     }
@@ -385,6 +425,47 @@ impl Connection {
         unsafe { xcb_ungrab_pointer(self.conn, XCB_CURRENT_TIME) };
     }
 
+    /// Redirects every key press to the root window, fails if another client holds the grab
+    pub fn grab_keyboard(&self) -> Result<(), ConnectionError> {
+        let cookie = unsafe {
+            xcb_grab_keyboard(
+                self.conn,
+                0,
+                self.root(),
+                XCB_CURRENT_TIME,
+                XCB_GRAB_MODE_ASYNC as u8,
+                XCB_GRAB_MODE_ASYNC as u8,
+            )
+        };
+        let mut error: *mut xcb_generic_error_t = std::ptr::null_mut();
+        let reply = unsafe { xcb_grab_keyboard_reply(self.conn, cookie, &mut error) };
+        if !error.is_null() {
+            unsafe { libc::free(error as *mut std::ffi::c_void) };
+            return Err(ConnectionError::UnableToGrabKeyboard(
+                "grab request failed".to_string(),
+            ));
+        }
+        if reply.is_null() {
+            return Err(ConnectionError::UnableToGrabKeyboard(
+                "no reply to grab request".to_string(),
+            ));
+        }
+        let status = unsafe { *reply }.status;
+        unsafe { libc::free(reply as *mut std::ffi::c_void) };
+        if status as u32 != XCB_GRAB_STATUS_SUCCESS {
+            return Err(ConnectionError::UnableToGrabKeyboard(format!(
+                "grab status: {}",
+                status
+            )));
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn ungrab_keyboard(&self) {
+        unsafe { xcb_ungrab_keyboard(self.conn, XCB_CURRENT_TIME) };
+    }
+
     #[allow(dead_code)]
     pub fn change_cursor(&self, new_name: &str) {
         if let Ok(cstr) = std::ffi::CString::new(new_name) {
@@ -476,6 +557,165 @@ impl Connection {
         None
     }
 
+    /// Returns _NET_WM_NAME, falling back to WM_NAME for clients that don't set it
+    pub fn window_title(&self, window: xcb_window_t) -> Option<String> {
+        let net_wm_name = unsafe { *self.ewmh }._NET_WM_NAME;
+        self.window_string_property(window, net_wm_name)
+            .or_else(|| self.window_string_property(window, XCB_ATOM_WM_NAME))
+    }
+
+    /// Returns WM_WINDOW_ROLE, e.g. `pop-up` or `browser`
+    pub fn window_role(&self, window: xcb_window_t) -> Option<String> {
+        let role_atom = self.intern_atom("WM_WINDOW_ROLE")?;
+        self.window_string_property(window, role_atom)
+            .map(|role| role.trim_end_matches('\0').to_string())
+    }
+
+    /// Returns WM_TRANSIENT_FOR, the window a dialog or picker belongs to
+    pub fn window_transient_for(&self, window: xcb_window_t) -> Option<xcb_window_t> {
+        let mut transient_for: xcb_window_t = XCB_NONE;
+        let mut error: *mut xcb_generic_error_t = std::ptr::null_mut();
+        let res = unsafe {
+            xcb_icccm_get_wm_transient_for_reply(
+                self.conn,
+                xcb_icccm_get_wm_transient_for(self.conn, window),
+                &mut transient_for,
+                &mut error,
+            )
+        };
+        if !error.is_null() {
+            unsafe { libc::free(error as *mut libc::c_void) };
+            return None;
+        }
+        if res == 1 && transient_for != XCB_NONE {
+            Some(transient_for)
+        } else {
+            None
+        }
+    }
+
+    /// Sets _NET_WM_WINDOW_OPACITY read by compositors, 0.0 is transparent and 1.0 opaque
+    pub fn window_set_opacity(&self, window: xcb_window_t, opacity: f64) {
+        if let Some(opacity_atom) = self.intern_atom("_NET_WM_WINDOW_OPACITY") {
+            let value = (opacity.clamp(0.0, 1.0) * u32::MAX as f64) as u32;
+            self.change_property(
+                window,
+                opacity_atom,
+                XCB_ATOM_CARDINAL,
+                32,
+                1,
+                &value as *const u32 as *const ::std::os::raw::c_void,
+            );
+        }
+    }
+
+    /// Publishes _NET_DESKTOP_NAMES, the n-th name belongs to the n-th desktop
+    pub fn set_desktop_names(&self, names: &[String]) {
+        let mut names_data = Vec::new();
+        for name in names {
+            names_data.extend_from_slice(name.as_bytes());
+            names_data.push(0);
+        }
+        unsafe {
+            xcb_ewmh_set_desktop_names(
+                self.ewmh,
+                0,
+                names_data.len() as u32,
+                names_data.as_ptr() as *const ::std::os::raw::c_char,
+            )
+        };
+    }
+
+    /// Adds or removes _NET_WM_STATE_FULLSCREEN, other _NET_WM_STATE atoms are kept
+    pub fn window_set_fullscreen_state(&self, window: xcb_window_t, fullscreen: bool) {
+        let (wm_state, wm_state_fullscreen) = unsafe {
+            (
+                (*self.ewmh)._NET_WM_STATE,
+                (*self.ewmh)._NET_WM_STATE_FULLSCREEN,
+            )
+        };
+        let mut state_atoms = self.window_state_atoms(window);
+        state_atoms.retain(|atom| *atom != wm_state_fullscreen);
+        if fullscreen {
+            state_atoms.push(wm_state_fullscreen);
+        }
+        self.change_property(
+            window,
+            wm_state,
+            XCB_ATOM_ATOM,
+            32,
+            state_atoms.len(),
+            state_atoms.as_ptr() as *const ::std::os::raw::c_void,
+        );
+    }
+
+    /// Whether the window already carries _NET_WM_STATE_FULLSCREEN when it is mapped
+    pub fn window_requests_fullscreen(&self, window: xcb_window_t) -> bool {
+        let wm_state_fullscreen = unsafe { (*self.ewmh)._NET_WM_STATE_FULLSCREEN };
+        self.window_state_atoms(window)
+            .contains(&wm_state_fullscreen)
+    }
+
+    /// Atoms currently listed in the _NET_WM_STATE of the window
+    fn window_state_atoms(&self, window: xcb_window_t) -> Vec<xcb_atom_t> {
+        let mut atoms_reply: MaybeUninit<xcb_ewmh_get_atoms_reply_t> = MaybeUninit::uninit();
+        let res = unsafe {
+            xcb_ewmh_get_wm_state_reply(
+                self.ewmh,
+                xcb_ewmh_get_wm_state(self.ewmh, window),
+                atoms_reply.as_mut_ptr(),
+                std::ptr::null_mut(),
+            )
+        };
+        let mut state_atoms = Vec::new();
+        if res == 1 {
+            unsafe {
+                let atoms_reply = atoms_reply.assume_init_mut();
+                let length = atoms_reply.atoms_len as usize;
+                state_atoms
+                    .extend_from_slice(std::slice::from_raw_parts(atoms_reply.atoms, length));
+                xcb_ewmh_get_atoms_reply_wipe(atoms_reply);
+            }
+        }
+        state_atoms
+    }
+
+    pub fn is_window_title_atom(&self, atom: xcb_atom_t) -> bool {
+        atom == XCB_ATOM_WM_NAME || atom == unsafe { *self.ewmh }._NET_WM_NAME
+    }
+
+    fn window_string_property(&self, window: xcb_window_t, property: xcb_atom_t) -> Option<String> {
+        let mut error: *mut xcb_generic_error_t = std::ptr::null_mut();
+        let reply = unsafe {
+            xcb_get_property_reply(
+                self.conn,
+                xcb_get_property(
+                    self.conn,
+                    0,
+                    window,
+                    property,
+                    XCB_GET_PROPERTY_TYPE_ANY,
+                    0,
+                    1024,
+                ),
+                &mut error,
+            )
+        };
+        if reply.is_null() || !error.is_null() {
+            return None;
+        }
+        let value_ptr = unsafe { xcb_get_property_value(reply) as *const u8 };
+        let value_len = unsafe { xcb_get_property_value_length(reply) as usize };
+        let value = if value_len > 0 {
+            let data = unsafe { std::slice::from_raw_parts(value_ptr, value_len) };
+            Some(String::from_utf8_lossy(data).into_owned())
+        } else {
+            None
+        };
+        unsafe { libc::free(reply as *mut libc::c_void) };
+        value
+    }
+
     pub fn window_rect_hints(&self, window: xcb_window_t) -> Option<xcb_size_hints_t> {
         let mut hints: MaybeUninit<xcb_size_hints_t> = MaybeUninit::uninit();
         let mut error: *mut xcb_generic_error_t = std::ptr::null_mut();
@@ -1341,7 +1581,8 @@ impl Connection {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum WindowType {
     Normal,
     Floating,
@@ -1389,6 +1630,55 @@ impl Connection {
         }
         window_type
     }
+
+    /// Lowercase name of the first _NET_WM_WINDOW_TYPE the window declares without the prefix,
+    /// e.g. `dialog` or `splash`, `normal` when it declares none
+    pub fn window_type_name(&self, window: xcb_window_t) -> &'static str {
+        let mut atoms_reply: MaybeUninit<xcb_ewmh_get_atoms_reply_t> = MaybeUninit::uninit();
+        let res = unsafe {
+            xcb_ewmh_get_wm_window_type_reply(
+                self.ewmh,
+                xcb_ewmh_get_wm_window_type(self.ewmh, window),
+                atoms_reply.as_mut_ptr(),
+                std::ptr::null_mut(),
+            )
+        };
+        let mut type_name = "normal";
+        if res == 1 {
+            unsafe {
+                let ewmh = &*self.ewmh;
+                let type_names = [
+                    (ewmh._NET_WM_WINDOW_TYPE_NORMAL, "normal"),
+                    (ewmh._NET_WM_WINDOW_TYPE_DIALOG, "dialog"),
+                    (ewmh._NET_WM_WINDOW_TYPE_UTILITY, "utility"),
+                    (ewmh._NET_WM_WINDOW_TYPE_TOOLBAR, "toolbar"),
+                    (ewmh._NET_WM_WINDOW_TYPE_SPLASH, "splash"),
+                    (ewmh._NET_WM_WINDOW_TYPE_MENU, "menu"),
+                    (ewmh._NET_WM_WINDOW_TYPE_DROPDOWN_MENU, "dropdown_menu"),
+                    (ewmh._NET_WM_WINDOW_TYPE_POPUP_MENU, "popup_menu"),
+                    (ewmh._NET_WM_WINDOW_TYPE_TOOLTIP, "tooltip"),
+                    (ewmh._NET_WM_WINDOW_TYPE_NOTIFICATION, "notification"),
+                    (ewmh._NET_WM_WINDOW_TYPE_COMBO, "combo"),
+                    (ewmh._NET_WM_WINDOW_TYPE_DND, "dnd"),
+                    (ewmh._NET_WM_WINDOW_TYPE_DOCK, "dock"),
+                    (ewmh._NET_WM_WINDOW_TYPE_DESKTOP, "desktop"),
+                ];
+                let window_type_atoms = atoms_reply.assume_init();
+                let length = window_type_atoms.atoms_len as usize;
+                if let Some(name) = (0..length).find_map(|i| {
+                    let atom = *(window_type_atoms.atoms.add(i));
+                    type_names
+                        .iter()
+                        .find(|(type_atom, _)| *type_atom == atom)
+                        .map(|(_, name)| *name)
+                }) {
+                    type_name = name;
+                }
+                xcb_ewmh_get_atoms_reply_wipe(atoms_reply.as_mut_ptr());
+            }
+        }
+        type_name
+    }
 }
 
 #[repr(u8)]
@@ -1398,26 +1688,37 @@ pub enum MouseButton {
 }
 
 impl Connection {
-    pub fn grab_button(&self, window: xcb_window_t) {
+    /// Pointer is frozen once the grab activates until `allow_events` or `allow_events_async`
+    pub fn grab_button(&self, modifiers: xcb_mod_mask_t, button: xcb_button_t) {
         unsafe {
             xcb_grab_button(
                 self.conn,
                 false as u8,
-                window,
-                XCB_BUTTON_MASK_1 as u16,
+                self.root(),
+                (XCB_EVENT_MASK_BUTTON_PRESS | XCB_EVENT_MASK_BUTTON_RELEASE) as u16,
                 XCB_GRAB_MODE_SYNC as u8,
                 XCB_GRAB_MODE_ASYNC as u8,
-                self.root(),
                 XCB_NONE,
-                XCB_BUTTON_MASK_1 as u8,
-                XCB_MOD_MASK_ANY as u16,
+                XCB_NONE,
+                button,
+                modifiers as u16,
             )
         };
     }
 
+    pub fn ungrab_button(&self, modifiers: xcb_mod_mask_t, button: xcb_button_t) {
+        unsafe { xcb_ungrab_button(self.conn, button, self.root(), modifiers as u16) };
+    }
+
+    /// Replays the frozen click to the window under the pointer as if it wasn't grabbed
     pub fn allow_events(&self, time: xcb_timestamp_t) {
         unsafe { xcb_allow_events(self.conn, XCB_ALLOW_REPLAY_POINTER as u8, time) };
     }
+
+    /// Thaws the pointer keeping the frozen click to the window manager
+    pub fn allow_events_async(&self, time: xcb_timestamp_t) {
+        unsafe { xcb_allow_events(self.conn, XCB_ALLOW_ASYNC_POINTER as u8, time) };
+    }
 }
 
 #[derive(Debug)]
@@ -1490,6 +1791,11 @@ pub enum XcbEvents {
         modifier: u16,
         keycode: xcb_keycode_t,
     },
+    /// only delivered for keys grabbed by the window manager
+    KeyRelease {
+        modifier: u16,
+        keycode: xcb_keycode_t,
+    },
     MapRequst {
         window: xcb_window_t,
     },
@@ -1516,7 +1822,11 @@ pub enum XcbEvents {
     ButtonPress {
         x: i32,
         y: i32,
+        root_x: i32,
+        root_y: i32,
         window: xcb_window_t,
+        /// child of the event window containing the pointer, XCB_NONE on the bare root window
+        child: xcb_window_t,
         state: u16,
         detail: xcb_button_t,
         time: xcb_timestamp_t,
@@ -1528,6 +1838,9 @@ pub enum XcbEvents {
     XkbStateNotify {
         event: *mut xcb_xkb_state_notify_event_t,
     },
+    /// XKB NewKeyboardNotify/MapNotify or core MappingNotify for the keyboard, keycodes may now
+    /// produce different keysyms
+    KeyboardMappingChanged,
     RandrScreenChange {
         width: u16,
         height: u16,
@@ -1538,6 +1851,16 @@ pub enum XcbEvents {
     UnmapNotify {
         window: xcb_window_t,
     },
+    PropertyNotify {
+        window: xcb_window_t,
+        atom: xcb_atom_t,
+    },
+    /// _NET_WM_STATE client message about _NET_WM_STATE_FULLSCREEN, `fullscreen` is None when
+    /// the client asks to toggle the state
+    FullscreenRequest {
+        window: xcb_window_t,
+        fullscreen: Option<bool>,
+    },
 }
 
 impl Connection {
@@ -1579,6 +1902,9 @@ impl Connection {
             let xkb_type = unsafe { *xkb_event }.xkbType as u32;
             return match xkb_type {
                 XCB_XKB_STATE_NOTIFY => Some(Ok(XcbEvents::XkbStateNotify { event: xkb_event })),
+                XCB_XKB_NEW_KEYBOARD_NOTIFY | XCB_XKB_MAP_NOTIFY => {
+                    Some(Ok(XcbEvents::KeyboardMappingChanged))
+                }
                 _ => None,
             };
         }
@@ -1600,6 +1926,13 @@ impl Connection {
                     keycode: unsafe { *event }.detail,
                 }))
             }
+            XCB_KEY_RELEASE => {
+                let event = generic_event as *mut xcb_key_release_event_t;
+                Some(Ok(XcbEvents::KeyRelease {
+                    modifier: unsafe { *event }.state,
+                    keycode: unsafe { *event }.detail,
+                }))
+            }
             XCB_MAP_REQUEST => {
                 let event = generic_event as *mut xcb_map_request_event_t;
                 Some(Ok(XcbEvents::MapRequst {
@@ -1634,7 +1967,10 @@ impl Connection {
                 Some(Ok(XcbEvents::ButtonPress {
                     x: unsafe { *event }.event_x as i32,
                     y: unsafe { *event }.event_y as i32,
+                    root_x: unsafe { *event }.root_x as i32,
+                    root_y: unsafe { *event }.root_y as i32,
                     window: unsafe { *event }.event,
+                    child: unsafe { *event }.child,
                     state: unsafe { *event }.state,
                     detail: unsafe { *event }.detail,
                     time: unsafe { *event }.time,
@@ -1671,6 +2007,45 @@ impl Connection {
                     window: unsafe { *event }.window,
                 }))
             }
+            XCB_MAPPING_NOTIFY => {
+                let event = generic_event as *mut xcb_mapping_notify_event_t;
+                if unsafe { *event }.request as u32 == xcb_mapping_t_XCB_MAPPING_KEYBOARD {
+                    Some(Ok(XcbEvents::KeyboardMappingChanged))
+                } else {
+                    None
+                }
+            }
+            XCB_PROPERTY_NOTIFY => {
+                let event = generic_event as *mut xcb_property_notify_event_t;
+                Some(Ok(XcbEvents::PropertyNotify {
+                    window: unsafe { *event }.window,
+                    atom: unsafe { *event }.atom,
+                }))
+            }
+            XCB_CLIENT_MESSAGE => {
+                let event = unsafe { *(generic_event as *mut xcb_client_message_event_t) };
+                let (wm_state, wm_state_fullscreen) = unsafe {
+                    (
+                        (*self.ewmh)._NET_WM_STATE,
+                        (*self.ewmh)._NET_WM_STATE_FULLSCREEN,
+                    )
+                };
+                let data = unsafe { event.data.data32 };
+                if event.type_ != wm_state
+                    || (data[1] != wm_state_fullscreen && data[2] != wm_state_fullscreen)
+                {
+                    return None;
+                }
+                let fullscreen = match data[0] {
+                    xcb_ewmh_wm_state_action_t_XCB_EWMH_WM_STATE_REMOVE => Some(false),
+                    xcb_ewmh_wm_state_action_t_XCB_EWMH_WM_STATE_ADD => Some(true),
+                    _ => None,
+                };
+                Some(Ok(XcbEvents::FullscreenRequest {
+                    window: event.window,
+                    fullscreen,
+                }))
+            }
             _ => None,
         }
     }
@@ -1681,7 +2056,7 @@ impl Connection {
         unsafe {
             if (*state_notify_event).xkbType == XCB_XKB_STATE_NOTIFY as u8 {
                 xkb_state_update_mask(
-                    self.xkb_state,
+                    self.xkb_state.get(),
                     (*state_notify_event).mods as u32,
                     (*state_notify_event).latchedMods as u32,
                     (*state_notify_event).lockedMods as u32,
@@ -1695,7 +2070,7 @@ impl Connection {
 
     pub fn xkb_keymap_get_as_string(&self) -> Option<Result<String, std::str::Utf8Error>> {
         let cstr_ptr =
-            unsafe { xkb_keymap_get_as_string(self.xkb_keymap, XKB_KEYMAP_FORMAT_TEXT_V1) };
+            unsafe { xkb_keymap_get_as_string(self.xkb_keymap.get(), XKB_KEYMAP_FORMAT_TEXT_V1) };
         if cstr_ptr.is_null() {
             None
         } else {
@@ -1704,6 +2079,66 @@ impl Connection {
         }
     }
 
+    /// Fetches the keymap of the core keyboard again, returns false if it failed and the old one
+    /// is still in use
+    pub fn xkb_reload_keymap(&self) -> bool {
+        let xkb_keymap = unsafe {
+            xkb_x11_keymap_new_from_device(
+                self.xkb_context,
+                self.conn,
+                self.xkb_device_id,
+                XKB_KEYMAP_COMPILE_NO_FLAGS,
+            )
+        };
+        if xkb_keymap.is_null() {
+            return false;
+        }
+        let xkb_state =
+            unsafe { xkb_x11_state_new_from_device(xkb_keymap, self.conn, self.xkb_device_id) };
+        if xkb_state.is_null() {
+            unsafe { xkb_keymap_unref(xkb_keymap) };
+            return false;
+        }
+        unsafe {
+            xkb_state_unref(self.xkb_state.replace(xkb_state));
+            xkb_keymap_unref(self.xkb_keymap.replace(xkb_keymap));
+        }
+        true
+    }
+
+    /// Keysym the key produces with the current modifiers and layout
+    pub fn xkb_keysym_for_keycode(&self, keycode: xcb_keycode_t) -> xkb_keysym_t {
+        unsafe { xkb_state_key_get_one_sym(self.xkb_state.get(), keycode as xkb_keycode_t) }
+    }
+
+    /// Every keycode producing `keysym` on any layout and shift level of the current keymap
+    pub fn xkb_keycodes_for_keysym(&self, keysym: xkb_keysym_t) -> Vec<xcb_keycode_t> {
+        let keymap = self.xkb_keymap.get();
+        let mut keycodes = Vec::new();
+        let min_keycode = unsafe { xkb_keymap_min_keycode(keymap) };
+        // core protocol keycodes fit in a byte
+        let max_keycode = unsafe { xkb_keymap_max_keycode(keymap) }.min(u8::MAX as xkb_keycode_t);
+        for keycode in min_keycode..=max_keycode {
+            let num_layouts = unsafe { xkb_keymap_num_layouts_for_key(keymap, keycode) };
+            let produces_keysym = (0..num_layouts).any(|layout| {
+                let num_levels = unsafe { xkb_keymap_num_levels_for_key(keymap, keycode, layout) };
+                (0..num_levels).any(|level| {
+                    let mut syms: *const xkb_keysym_t = std::ptr::null();
+                    let num_syms = unsafe {
+                        xkb_keymap_key_get_syms_by_level(keymap, keycode, layout, level, &mut syms)
+                    };
+                    num_syms > 0
+                        && unsafe { std::slice::from_raw_parts(syms, num_syms as usize) }
+                            .contains(&keysym)
+                })
+            });
+            if produces_keysym {
+                keycodes.push(keycode as xcb_keycode_t);
+            }
+        }
+        keycodes
+    }
+
     pub fn xkb_get_layout_names(&self) -> Vec<String> {
         let mut layout_names = Vec::new();
         unsafe {
@@ -1755,3 +2190,16 @@ impl Connection {
         layout_names
     }
 }
+
+/// Looks up keysym by its name as in xkbcommon-keysyms.h without the `XKB_KEY_` prefix, e.g.
+/// `Return` or `XF86AudioMute`, falls back to case insensitive match
+pub fn keysym_from_name(name: &str) -> Option<xkb_keysym_t> {
+    let name = CString::new(name).ok()?;
+    [
+        xkb_keysym_flags_XKB_KEYSYM_NO_FLAGS,
+        xkb_keysym_flags_XKB_KEYSYM_CASE_INSENSITIVE,
+    ]
+    .into_iter()
+    .map(|flags| unsafe { xkb_keysym_from_name(name.as_ptr(), flags) })
+    .find(|keysym| *keysym != XKB_KEY_NoSymbol)
+}
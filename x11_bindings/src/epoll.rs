@@ -24,9 +24,18 @@ impl Epoll {
     }
 
     pub fn add_watch(&mut self, client_fd: RawFd) -> Result<(), std::io::Error> {
+        self.add_watch_with_events(client_fd, libc::EPOLLIN as u32)
+    }
+
+    /// `events` is a mask of libc::EPOLL* flags, e.g. `EPOLLIN | EPOLLOUT | EPOLLET`
+    pub fn add_watch_with_events(
+        &mut self,
+        client_fd: RawFd,
+        events: u32,
+    ) -> Result<(), std::io::Error> {
         assert!(!self.is_closed());
         let mut event = libc::epoll_event {
-            events: libc::EPOLLIN as u32,
+            events,
             u64: client_fd as u64,
         };
         let ctl_res =
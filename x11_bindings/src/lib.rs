@@ -1,4 +1,5 @@
 pub mod bindings;
 pub mod connection;
 pub mod epoll;
-pub mod inotify;
\ No newline at end of file
+pub mod inotify;
+pub mod timerfd;
\ No newline at end of file
@@ -0,0 +1,79 @@
+use std::{io::Error, os::fd::RawFd, time::Duration};
+
+/// One-shot monotonic timer to be watched by epoll, becomes readable when it expires
+pub struct TimerFd {
+    pub fd: RawFd,
+}
+
+impl TimerFd {
+    pub fn new() -> Result<Self, std::io::Error> {
+        let fd = unsafe {
+            libc::timerfd_create(
+                libc::CLOCK_MONOTONIC,
+                libc::TFD_NONBLOCK | libc::TFD_CLOEXEC,
+            )
+        };
+        if fd == -1 {
+            return Err(Error::last_os_error());
+        }
+        Ok(Self { fd })
+    }
+
+    /// Restarts the timer, previous expiration is forgotten
+    pub fn arm(&self, timeout: Duration) -> Result<(), std::io::Error> {
+        // zero it_value would disarm the timer instead
+        let timeout = timeout.max(Duration::from_nanos(1));
+        self.set(libc::timespec {
+            tv_sec: timeout.as_secs() as libc::time_t,
+            tv_nsec: timeout.subsec_nanos() as libc::c_long,
+        })
+    }
+
+    pub fn disarm(&self) -> Result<(), std::io::Error> {
+        self.set(libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        })
+    }
+
+    fn set(&self, value: libc::timespec) -> Result<(), std::io::Error> {
+        let new_value = libc::itimerspec {
+            it_interval: libc::timespec {
+                tv_sec: 0,
+                tv_nsec: 0,
+            },
+            it_value: value,
+        };
+        let res = unsafe { libc::timerfd_settime(self.fd, 0, &new_value, std::ptr::null_mut()) };
+        if res == -1 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Clears readiness, returns number of expirations since the last read, 0 if none
+    pub fn read(&self) -> Result<u64, std::io::Error> {
+        let mut expirations = 0u64;
+        let len = unsafe {
+            libc::read(
+                self.fd,
+                &mut expirations as *mut u64 as *mut std::ffi::c_void,
+                size_of::<u64>(),
+            )
+        };
+        if len < 0 {
+            let error = Error::last_os_error();
+            if error.kind() == std::io::ErrorKind::WouldBlock {
+                return Ok(0);
+            }
+            return Err(error);
+        }
+        Ok(expirations)
+    }
+}
+
+impl Drop for TimerFd {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}